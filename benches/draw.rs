@@ -0,0 +1,54 @@
+//! Throughput of sprite drawing with the speed limit off, as when fast
+//! forwarding: every frame runs back to back and is presented.
+
+#![allow(clippy::redundant_field_names)]
+
+#[macro_use]
+extern crate criterion;
+extern crate chip8_emulator;
+
+use criterion::{Criterion, Throughput};
+
+use chip8_emulator::headless::{headless, HeadlessConfig};
+use chip8_emulator::quirks::Quirks;
+
+const FRAMES: usize = 60;
+const CYCLES_PER_FRAME: usize = 1000;
+
+/// Draws a 15 line sprite all over the screen, partly across the edges.
+const DRAW_LOOP: &[u8] = &[
+    0xA0, 0x00, // 200: LD I, #000  The font, as sprite data
+    // loop:
+    0xD0, 0x1F, // 202: DRW V0, V1, 15
+    0x70, 0x03, // 204: ADD V0, 3
+    0x71, 0x05, // 206: ADD V1, 5
+    0x12, 0x02, // 208: JP loop
+];
+
+fn draw(c: &mut Criterion) {
+    let mut group = c.benchmark_group("uncapped");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+
+    for &(name, clip_sprites) in [("clip", true), ("wrap", false)].iter() {
+        let config = HeadlessConfig {
+            quirks: Quirks { clip_sprites: clip_sprites, ..Quirks::default() },
+            cycles_per_frame: CYCLES_PER_FRAME,
+            ..HeadlessConfig::default()
+        };
+
+        group.bench_function(name, |b| {
+            let mut chip8 = headless(DRAW_LOOP, &config).unwrap();
+            b.iter(|| {
+                for _ in 0..FRAMES {
+                    chip8.run_frame();
+                    chip8.present();
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, draw);
+criterion_main!(benches);
@@ -0,0 +1,130 @@
+//! Runs a ROM the way firmware on a microcontroller would, through the part
+//! of the crate that builds without std: the screen goes to an in-memory LCD
+//! buffer and the keys come from a button bitmask. Only `main` uses std, to
+//! read the ROM and print the LCD.
+//!
+//! ```bash
+//! $ cargo run --example embedded -- $ROM_NAME 120
+//! ```
+
+extern crate chip8_emulator;
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+
+use chip8_emulator::chip8::Chip8;
+use chip8_emulator::peripherals::{Chip8Disp, Chip8Input, Chip8Key, Framebuffer, KeyEvent,
+                                  KeyQueue, Region};
+
+/// Draws the hex digits in two rows, used when no ROM is given.
+const DIGITS: &[u8] = &[
+    0x60, 0x00, // 200: LD V0, 0
+    0x61, 0x00, // 202: LD V1, 0
+    0x62, 0x00, // 204: LD V2, 0
+    0xF0, 0x29, // 206: loop: LD F, V0
+    0xD1, 0x25, // 208: DRW V1, V2, 5
+    0x70, 0x01, // 20A: ADD V0, 1
+    0x71, 0x05, // 20C: ADD V1, 5
+    0x31, 0x28, // 20E: SE V1, 40
+    0x12, 0x06, // 210: JP loop
+    0x61, 0x00, // 212: LD V1, 0
+    0x72, 0x06, // 214: ADD V2, 6
+    0x30, 0x10, // 216: SE V0, 16
+    0x12, 0x06, // 218: JP loop
+    0x12, 0x1A, // 21A: end: JP end
+];
+
+/// A 64x32 monochrome LCD laid out like an SSD1306: pages of 8 rows, one
+/// byte per column and page with the top row in the low bit.
+struct Lcd {
+    pages: [[u8; 64]; 4],
+}
+
+impl Chip8Disp for Lcd {
+    fn present(&mut self, frame: &Framebuffer, dirty: Option<Region>) {
+        // Only the changed pixels would be sent over the bus
+        if let Some(dirty) = dirty {
+            for y in dirty.y..(dirty.y + dirty.height) {
+                for x in dirty.x..(dirty.x + dirty.width) {
+                    let bit = 1 << (y % 8);
+                    if frame.pixel(x, y) {
+                        self.pages[y / 8][x] |= bit;
+                    } else {
+                        self.pages[y / 8][x] &= !bit;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Sixteen buttons read as a bitmask, bit N for key N.
+struct Buttons {
+    keys: KeyQueue,
+}
+
+impl Buttons {
+    fn read(&mut self, mask: u16) {
+        let mut pressed = [false; 16];
+        for (key, pressed) in pressed.iter_mut().enumerate() {
+            *pressed = mask & (1 << key) != 0;
+        }
+        self.keys.set_keys(&pressed);
+    }
+}
+
+impl Chip8Input for Buttons {
+    fn key_pressed(&self, key: Chip8Key) -> bool {
+        self.keys.key_pressed(key)
+    }
+
+    fn next_event(&mut self) -> Option<KeyEvent> {
+        self.keys.next_event()
+    }
+
+    fn poll(&mut self) -> bool {
+        false
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut program = Vec::new();
+    match args.next() {
+        Some(path) => {
+            File::open(&path)
+                .and_then(|mut file| file.read_to_end(&mut program))
+                .expect("Unable to read the ROM");
+        }
+        None => program.extend_from_slice(DIGITS),
+    }
+    let frames: usize = args.next().map_or(60, |frames| frames.parse().expect("Invalid frames"));
+
+    let mut chip8 = Chip8::new();
+    // Firmware would seed from a hardware RNG or a free running timer
+    chip8.seed_rng(0x1234_5678);
+    chip8.upload_rom(&program).expect("ROM too long");
+    chip8.connect_display(Lcd { pages: [[0; 64]; 4] });
+    chip8.connect_keyboard(Buttons { keys: KeyQueue::new() });
+
+    for _ in 0..frames {
+        // No buttons are wired up here
+        chip8.keyboard_mut().unwrap().read(0);
+        chip8.poll_input();
+        chip8.run_frame();
+        chip8.present();
+        if let Some(fault) = chip8.fault() {
+            println!("{}", fault);
+            break;
+        }
+    }
+
+    let lcd = chip8.display().unwrap();
+    for y in 0..32 {
+        let line: String = (0..64)
+            .map(|x| if lcd.pages[y / 8][x] & (1 << (y % 8)) != 0 { '#' } else { '.' })
+            .collect();
+        println!("{}", line);
+    }
+}
@@ -0,0 +1,153 @@
+// Canvas frontend for the WebAssembly build in `wasm/`. `pkg/` is written by
+// wasm-bindgen, see the README.
+import init, { Chip8 } from './pkg/chip8_emulator_wasm.js';
+
+// The same layout as the SDL frontend's default keymap
+const KEYMAP = {
+  Digit1: 0x1, Digit2: 0x2, Digit3: 0x3, Digit4: 0xC,
+  KeyQ: 0x4, KeyW: 0x5, KeyE: 0x6, KeyR: 0xD,
+  KeyA: 0x7, KeyS: 0x8, KeyD: 0x9, KeyF: 0xE,
+  KeyZ: 0xA, KeyX: 0x0, KeyC: 0xB, KeyV: 0xF,
+};
+const FOREGROUND = [0xFF, 0xFF, 0xFF];
+const BACKGROUND = [0x00, 0x00, 0x00];
+const FRAME_MS = 1000 / 60;
+
+const canvas = document.getElementById('screen');
+const context = canvas.getContext('2d');
+const image = context.createImageData(64, 32);
+const status = document.getElementById('status');
+const quirks = document.getElementById('quirks');
+const speed = document.getElementById('speed');
+
+let chip8 = null;
+let saved = null;
+let audio = null;
+let lastTime = null;
+let pending = 0;
+
+function draw() {
+  const pixels = chip8.framebuffer();
+  for (let i = 0; i < pixels.length; i++) {
+    const color = pixels[i] ? FOREGROUND : BACKGROUND;
+    image.data.set(color, i * 4);
+    image.data[i * 4 + 3] = 0xFF;
+  }
+  context.putImageData(image, 0, 0);
+}
+
+// A square wave that is muted while the sound timer is off. Browsers only
+// allow audio to start from a user action, so it is created on ROM load.
+function startAudio() {
+  if (audio) {
+    return;
+  }
+  const audioContext = new AudioContext();
+  const oscillator = audioContext.createOscillator();
+  const gain = audioContext.createGain();
+  oscillator.type = 'square';
+  oscillator.frequency.value = 440;
+  gain.gain.value = 0;
+  oscillator.connect(gain).connect(audioContext.destination);
+  oscillator.start();
+  audio = gain.gain;
+}
+
+// Frames run at 60 Hz whatever the refresh rate of the monitor is
+function frame(time) {
+  requestAnimationFrame(frame);
+  if (!chip8) {
+    return;
+  }
+
+  pending += lastTime === null ? FRAME_MS : time - lastTime;
+  lastTime = time;
+  // Don't try to catch up after the tab was in the background
+  pending = Math.min(pending, 4 * FRAME_MS);
+  let changed = false;
+  while (pending >= FRAME_MS) {
+    changed = chip8.runFrame() || changed;
+    pending -= FRAME_MS;
+  }
+  if (changed) {
+    draw();
+  }
+  audio.value = chip8.soundActive() ? 0.1 : 0;
+
+  const fault = chip8.fault();
+  if (fault) {
+    status.textContent = fault;
+  }
+}
+
+function applySettings() {
+  chip8.setQuirks(quirks.value);
+  chip8.setCyclesPerFrame(Math.max(1, parseInt(speed.value, 10) || 1));
+}
+
+document.getElementById('rom').addEventListener('change', async (event) => {
+  const file = event.target.files[0];
+  if (!file) {
+    return;
+  }
+  const rom = new Uint8Array(await file.arrayBuffer());
+  try {
+    if (chip8) {
+      chip8.loadRom(rom);
+    } else {
+      chip8 = new Chip8(rom, Math.floor(Math.random() * 2 ** 32));
+    }
+    applySettings();
+    startAudio();
+    saved = null;
+    status.textContent = file.name;
+  } catch (error) {
+    status.textContent = error;
+  }
+});
+
+quirks.addEventListener('change', () => chip8 && applySettings());
+speed.addEventListener('change', () => chip8 && applySettings());
+
+function saveState() {
+  if (chip8) {
+    saved = chip8.saveState();
+    status.textContent = 'Saved';
+  }
+}
+
+function loadState() {
+  if (chip8 && saved) {
+    chip8.loadState(saved);
+    draw();
+    status.textContent = 'Loaded';
+  }
+}
+
+document.getElementById('save').addEventListener('click', saveState);
+document.getElementById('load').addEventListener('click', loadState);
+
+function onKey(event, pressed) {
+  if (!chip8) {
+    return;
+  }
+  if (event.code in KEYMAP) {
+    event.preventDefault();
+    if (pressed) {
+      chip8.pressKey(KEYMAP[event.code]);
+    } else {
+      chip8.releaseKey(KEYMAP[event.code]);
+    }
+  } else if (pressed && event.code === 'F5') {
+    event.preventDefault();
+    saveState();
+  } else if (pressed && event.code === 'F7') {
+    event.preventDefault();
+    loadState();
+  }
+}
+
+window.addEventListener('keydown', (event) => onKey(event, true));
+window.addEventListener('keyup', (event) => onKey(event, false));
+
+init().then(() => requestAnimationFrame(frame));
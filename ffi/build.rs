@@ -0,0 +1,19 @@
+extern crate cbindgen;
+
+use std::env;
+use std::path::Path;
+
+fn main() {
+    let dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config = cbindgen::Config::from_file(Path::new(&dir).join("cbindgen.toml"))
+        .expect("Failed to read cbindgen.toml");
+
+    // Only rewritten when the API changes, so the checked in header stays
+    // current without touching it on every build
+    cbindgen::generate_with_config(&dir, config)
+        .expect("Failed to generate chip8.h")
+        .write_to_file(Path::new(&dir).join("include").join("chip8.h"));
+
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
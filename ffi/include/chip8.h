@@ -0,0 +1,246 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* Generated by cbindgen from ffi/src/lib.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Bumped whenever functions are added.
+ */
+#define CHIP8_ABI_VERSION 1
+
+#define CHIP8_WIDTH 64
+
+#define CHIP8_HEIGHT 32
+
+/**
+ * A machine, `chip8_t` in C. Only ever handled through a pointer.
+ */
+typedef struct chip8_t chip8_t;
+
+/**
+ * The result of calls that can fail, one of the `CHIP8_` constants below.
+ * A plain integer rather than an enum so that values added later can't be
+ * out of range for older callers.
+ */
+typedef int32_t chip8_status_t;
+
+/**
+ * The CPU registers, as read and written by `chip8_get_registers` and
+ * `chip8_set_registers`.
+ */
+typedef struct {
+  uint8_t v[16];
+  uint16_t i;
+  uint16_t pc;
+  uint8_t delay_timer;
+  uint8_t sound_timer;
+} chip8_registers_t;
+
+#define CHIP8_OK 0
+
+#define CHIP8_NULL_POINTER 1
+
+#define CHIP8_BUFFER_TOO_SMALL 2
+
+#define CHIP8_UNKNOWN_PRESET 3
+
+#define CHIP8_INVALID_KEY 4
+
+#define CHIP8_INVALID_OPCODE 5
+
+#define CHIP8_PROGRAM_TOO_LONG 6
+
+#define CHIP8_WRITE_PAST_END 7
+
+#define CHIP8_NOT_A_STATE 8
+
+#define CHIP8_STATE_VERSION 9
+
+#define CHIP8_INVALID_STACK_POINTER 10
+
+#define CHIP8_FRAME_SIZE 11
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+uint32_t chip8_abi_version(void);
+
+/**
+ * A short description of `status`, valid for the life of the program.
+ */
+const char *chip8_status_message(chip8_status_t status);
+
+/**
+ * A new machine with an empty program. `seed` seeds `RND`, pass e.g. the
+ * time to get different numbers on every run. Free it with
+ * `chip8_destroy`.
+ */
+chip8_t *chip8_create(uint32_t seed);
+
+/**
+ * Free a machine. Does nothing when `chip8` is null.
+ *
+ * # Safety
+ *
+ * `chip8` must be null or a machine from `chip8_create`, and is invalid
+ * afterwards.
+ */
+void chip8_destroy(chip8_t *chip8);
+
+/**
+ * Reset the machine and load `len` bytes of `rom`. Quirks and speed are
+ * kept.
+ *
+ * # Safety
+ *
+ * `chip8` must be null or a machine from `chip8_create` that hasn't been
+ * destroyed. `rom` must be null or valid for reads of `len` bytes.
+ */
+chip8_status_t chip8_load_rom(chip8_t *chip8, const uint8_t *rom, size_t len);
+
+/**
+ * Return to the power-on state with the same program.
+ *
+ * # Safety
+ *
+ * `chip8` must be null or a machine from `chip8_create` that hasn't been
+ * destroyed.
+ */
+void chip8_reset(chip8_t *chip8);
+
+/**
+ * Switch to the quirks of `"chip8"`, `"schip"` or `"xochip"`.
+ *
+ * # Safety
+ *
+ * `chip8` must be null or a machine from `chip8_create` that hasn't been
+ * destroyed. `preset` must be null or a NUL-terminated string.
+ */
+chip8_status_t chip8_set_quirks(chip8_t *chip8, const char *preset);
+
+/**
+ * # Safety
+ *
+ * `chip8` must be null or a machine from `chip8_create` that hasn't been
+ * destroyed.
+ */
+void chip8_set_cycles_per_frame(chip8_t *chip8, size_t cycles);
+
+/**
+ * Execute a single instruction.
+ *
+ * # Safety
+ *
+ * `chip8` must be null or a machine from `chip8_create` that hasn't been
+ * destroyed.
+ */
+void chip8_step(chip8_t *chip8);
+
+/**
+ * Emulate one 60 Hz frame. Returns whether the screen changed since the
+ * last call.
+ *
+ * # Safety
+ *
+ * `chip8` must be null or a machine from `chip8_create` that hasn't been
+ * destroyed.
+ */
+bool chip8_run_frame(chip8_t *chip8);
+
+/**
+ * Press or release a Chip-8 key, 0 to F.
+ *
+ * # Safety
+ *
+ * `chip8` must be null or a machine from `chip8_create` that hasn't been
+ * destroyed.
+ */
+chip8_status_t chip8_set_key(chip8_t *chip8, uint8_t key, bool pressed);
+
+/**
+ * The `CHIP8_WIDTH` by `CHIP8_HEIGHT` screen, one byte per pixel row by
+ * row, 1 when lit. The pointer stays valid until the machine is destroyed
+ * and the pixels are updated by the calls that run or load it.
+ *
+ * # Safety
+ *
+ * `chip8` must be null or a machine from `chip8_create` that hasn't been
+ * destroyed.
+ */
+const uint8_t *chip8_framebuffer(const chip8_t *chip8);
+
+/**
+ * Whether a tone should be playing.
+ *
+ * # Safety
+ *
+ * `chip8` must be null or a machine from `chip8_create` that hasn't been
+ * destroyed.
+ */
+bool chip8_sound_active(const chip8_t *chip8);
+
+/**
+ * Whether the machine halted on a fault, such as an invalid instruction.
+ *
+ * # Safety
+ *
+ * `chip8` must be null or a machine from `chip8_create` that hasn't been
+ * destroyed.
+ */
+bool chip8_faulted(const chip8_t *chip8);
+
+/**
+ * # Safety
+ *
+ * `chip8` must be null or a machine from `chip8_create` that hasn't been
+ * destroyed. `registers` must be null or valid for writes.
+ */
+chip8_status_t chip8_get_registers(const chip8_t *chip8, chip8_registers_t *registers);
+
+/**
+ * Overwrite the registers. A `pc` outside memory faults on the next step.
+ *
+ * # Safety
+ *
+ * `chip8` must be null or a machine from `chip8_create` that hasn't been
+ * destroyed. `registers` must be null or valid for reads.
+ */
+chip8_status_t chip8_set_registers(chip8_t *chip8, const chip8_registers_t *registers);
+
+/**
+ * The size of a save state, the same format as the SDL frontend's slots.
+ */
+size_t chip8_state_size(void);
+
+/**
+ * Write a save state of `chip8_state_size()` bytes to `buffer`.
+ *
+ * # Safety
+ *
+ * `chip8` must be null or a machine from `chip8_create` that hasn't been
+ * destroyed. `buffer` must be null or valid for writes of `len` bytes.
+ */
+chip8_status_t chip8_save_state(const chip8_t *chip8, uint8_t *buffer, size_t len);
+
+/**
+ * Restore a state written by `chip8_save_state`.
+ *
+ * # Safety
+ *
+ * `chip8` must be null or a machine from `chip8_create` that hasn't been
+ * destroyed. `buffer` must be null or valid for reads of `len` bytes.
+ */
+chip8_status_t chip8_load_state(chip8_t *chip8, const uint8_t *buffer, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIP8_H */
@@ -0,0 +1,450 @@
+//! A C API to embed the emulator, built as `libchip8.so` and `libchip8.a`.
+//! The build regenerates `include/chip8.h` from this file with cbindgen.
+//!
+//! The ABI is stable: functions are only ever added, and the values of
+//! `chip8_status_t` never change. `chip8_abi_version` goes up when
+//! something is added. Pointers passed in must be valid for the sizes given,
+//! handles are not thread safe.
+
+// Struct literals spell out `field: field`
+#![allow(clippy::redundant_field_names)]
+
+extern crate chip8_emulator;
+
+use std::os::raw::c_char;
+use std::ffi::CStr;
+use std::ptr;
+use std::slice;
+
+use chip8_emulator::chip8::{Chip8, FRAME_BYTES, STATE_SIZE};
+use chip8_emulator::error::Error;
+use chip8_emulator::peripherals::{Chip8Disp, Chip8Input, Chip8Key, Framebuffer, KeyEvent,
+                                  KeyQueue, Region};
+use chip8_emulator::quirks::Quirks;
+
+/// Bumped whenever functions are added.
+pub const CHIP8_ABI_VERSION: u32 = 1;
+
+pub const CHIP8_WIDTH: usize = 64;
+pub const CHIP8_HEIGHT: usize = 32;
+
+/// The result of calls that can fail, one of the `CHIP8_` constants below.
+/// A plain integer rather than an enum so that values added later can't be
+/// out of range for older callers.
+pub type Status = i32;
+
+pub const CHIP8_OK: Status = 0;
+pub const CHIP8_NULL_POINTER: Status = 1;
+pub const CHIP8_BUFFER_TOO_SMALL: Status = 2;
+pub const CHIP8_UNKNOWN_PRESET: Status = 3;
+pub const CHIP8_INVALID_KEY: Status = 4;
+pub const CHIP8_INVALID_OPCODE: Status = 5;
+pub const CHIP8_PROGRAM_TOO_LONG: Status = 6;
+pub const CHIP8_WRITE_PAST_END: Status = 7;
+pub const CHIP8_NOT_A_STATE: Status = 8;
+pub const CHIP8_STATE_VERSION: Status = 9;
+pub const CHIP8_INVALID_STACK_POINTER: Status = 10;
+pub const CHIP8_FRAME_SIZE: Status = 11;
+
+fn status(result: Result<(), Error>) -> Status {
+    match result {
+        Ok(()) => CHIP8_OK,
+        Err(Error::InvalidKey(_)) => CHIP8_INVALID_KEY,
+        Err(Error::InvalidOpcode(_)) => CHIP8_INVALID_OPCODE,
+        Err(Error::ProgramTooLong(_)) => CHIP8_PROGRAM_TOO_LONG,
+        Err(Error::WritePastEnd(_)) => CHIP8_WRITE_PAST_END,
+        Err(Error::NotAState) => CHIP8_NOT_A_STATE,
+        Err(Error::StateVersion(_)) => CHIP8_STATE_VERSION,
+        Err(Error::InvalidStackPointer) => CHIP8_INVALID_STACK_POINTER,
+        Err(Error::FrameSize(_)) => CHIP8_FRAME_SIZE,
+    }
+}
+
+/// The CPU registers, as read and written by `chip8_get_registers` and
+/// `chip8_set_registers`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Registers {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+/// Keeps the screen one byte per pixel, so C can read it in place.
+struct PixelDisplay {
+    pixels: [u8; CHIP8_WIDTH * CHIP8_HEIGHT],
+    changed: bool,
+}
+
+impl Chip8Disp for PixelDisplay {
+    fn present(&mut self, frame: &Framebuffer, dirty: Option<Region>) {
+        if dirty.is_none() {
+            return;
+        }
+
+        for y in 0..CHIP8_HEIGHT {
+            for x in 0..CHIP8_WIDTH {
+                self.pixels[y * CHIP8_WIDTH + x] = frame.pixel(x, y) as u8;
+            }
+        }
+        self.changed = true;
+    }
+}
+
+/// Keys set by `chip8_set_key`.
+struct HostKeys {
+    keys: KeyQueue,
+}
+
+impl Chip8Input for HostKeys {
+    fn key_pressed(&self, key: Chip8Key) -> bool {
+        self.keys.key_pressed(key)
+    }
+
+    fn next_event(&mut self) -> Option<KeyEvent> {
+        self.keys.next_event()
+    }
+
+    fn poll(&mut self) -> bool {
+        false
+    }
+}
+
+/// A machine, `chip8_t` in C. Only ever handled through a pointer.
+pub struct Handle {
+    chip8: Chip8<PixelDisplay, HostKeys>,
+    /// Loaded again on reset.
+    program: Vec<u8>,
+}
+
+impl Handle {
+    fn display(&self) -> &PixelDisplay {
+        self.chip8.display().unwrap()
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn chip8_abi_version() -> u32 {
+    CHIP8_ABI_VERSION
+}
+
+/// A short description of `status`, valid for the life of the program.
+#[no_mangle]
+pub extern "C" fn chip8_status_message(status: Status) -> *const c_char {
+    let message: &'static [u8] = match status {
+        CHIP8_OK => b"OK\0",
+        CHIP8_NULL_POINTER => b"Null pointer\0",
+        CHIP8_BUFFER_TOO_SMALL => b"Buffer too small\0",
+        CHIP8_UNKNOWN_PRESET => b"Unknown quirks preset\0",
+        CHIP8_INVALID_KEY => b"Invalid key\0",
+        CHIP8_INVALID_OPCODE => b"Invalid instruction\0",
+        CHIP8_PROGRAM_TOO_LONG => b"Program too long\0",
+        CHIP8_WRITE_PAST_END => b"Write past the end of memory\0",
+        CHIP8_NOT_A_STATE => b"Not a Chip-8 save state\0",
+        CHIP8_STATE_VERSION => b"Unsupported save state version\0",
+        CHIP8_INVALID_STACK_POINTER => b"Invalid stack pointer in save state\0",
+        CHIP8_FRAME_SIZE => b"Invalid frame size\0",
+        _ => b"Unknown status\0",
+    };
+    message.as_ptr() as *const c_char
+}
+
+/// A new machine with an empty program. `seed` seeds `RND`, pass e.g. the
+/// time to get different numbers on every run. Free it with
+/// `chip8_destroy`.
+#[no_mangle]
+pub extern "C" fn chip8_create(seed: u32) -> *mut Handle {
+    let mut chip8 = Chip8::new();
+    chip8.seed_rng(seed);
+    chip8.connect_display(PixelDisplay {
+        pixels: [0; CHIP8_WIDTH * CHIP8_HEIGHT],
+        changed: true,
+    });
+    chip8.connect_keyboard(HostKeys { keys: KeyQueue::new() });
+
+    Box::into_raw(Box::new(Handle {
+        chip8: chip8,
+        program: Vec::new(),
+    }))
+}
+
+/// Free a machine. Does nothing when `chip8` is null.
+///
+/// # Safety
+///
+/// `chip8` must be null or a machine from `chip8_create`, and is invalid
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_destroy(chip8: *mut Handle) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+/// Reset the machine and load `len` bytes of `rom`. Quirks and speed are
+/// kept.
+///
+/// # Safety
+///
+/// `chip8` must be null or a machine from `chip8_create` that hasn't been
+/// destroyed. `rom` must be null or valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(chip8: *mut Handle, rom: *const u8, len: usize)
+                                        -> Status {
+    if chip8.is_null() || rom.is_null() {
+        return CHIP8_NULL_POINTER;
+    }
+
+    let handle = &mut *chip8;
+    let program = slice::from_raw_parts(rom, len);
+    handle.chip8.reset();
+    let result = handle.chip8.upload_rom(program);
+    handle.program = if result.is_ok() { program.to_vec() } else { Vec::new() };
+    handle.chip8.present();
+    status(result)
+}
+
+/// Return to the power-on state with the same program.
+///
+/// # Safety
+///
+/// `chip8` must be null or a machine from `chip8_create` that hasn't been
+/// destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_reset(chip8: *mut Handle) {
+    if let Some(handle) = chip8.as_mut() {
+        handle.chip8.reset();
+        // Can't fail, chip8_load_rom only keeps programs that fit
+        let _ = handle.chip8.upload_rom(&handle.program);
+        handle.chip8.present();
+    }
+}
+
+/// Switch to the quirks of `"chip8"`, `"schip"` or `"xochip"`.
+///
+/// # Safety
+///
+/// `chip8` must be null or a machine from `chip8_create` that hasn't been
+/// destroyed. `preset` must be null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_quirks(chip8: *mut Handle, preset: *const c_char) -> Status {
+    if chip8.is_null() || preset.is_null() {
+        return CHIP8_NULL_POINTER;
+    }
+
+    match CStr::from_ptr(preset).to_str().ok().and_then(Quirks::preset) {
+        Some(quirks) => {
+            (*chip8).chip8.set_quirks(quirks);
+            CHIP8_OK
+        }
+        None => CHIP8_UNKNOWN_PRESET,
+    }
+}
+
+/// # Safety
+///
+/// `chip8` must be null or a machine from `chip8_create` that hasn't been
+/// destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_cycles_per_frame(chip8: *mut Handle, cycles: usize) {
+    if let Some(handle) = chip8.as_mut() {
+        handle.chip8.set_cycles_per_frame(cycles);
+    }
+}
+
+/// Execute a single instruction.
+///
+/// # Safety
+///
+/// `chip8` must be null or a machine from `chip8_create` that hasn't been
+/// destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip8: *mut Handle) {
+    if let Some(handle) = chip8.as_mut() {
+        handle.chip8.step();
+        handle.chip8.present();
+    }
+}
+
+/// Emulate one 60 Hz frame. Returns whether the screen changed since the
+/// last call.
+///
+/// # Safety
+///
+/// `chip8` must be null or a machine from `chip8_create` that hasn't been
+/// destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_run_frame(chip8: *mut Handle) -> bool {
+    let handle = match chip8.as_mut() {
+        Some(handle) => handle,
+        None => return false,
+    };
+
+    handle.chip8.poll_input();
+    handle.chip8.run_frame();
+    handle.chip8.present();
+
+    let display = handle.chip8.display_mut().unwrap();
+    let changed = display.changed;
+    display.changed = false;
+    changed
+}
+
+/// Press or release a Chip-8 key, 0 to F.
+///
+/// # Safety
+///
+/// `chip8` must be null or a machine from `chip8_create` that hasn't been
+/// destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(chip8: *mut Handle, key: u8, pressed: bool) -> Status {
+    let handle = match chip8.as_mut() {
+        Some(handle) => handle,
+        None => return CHIP8_NULL_POINTER,
+    };
+
+    match Chip8Key::new(key) {
+        Ok(key) => {
+            handle.chip8.keyboard_mut().unwrap().keys.set_key(key, pressed);
+            CHIP8_OK
+        }
+        Err(error) => status(Err(error)),
+    }
+}
+
+/// The `CHIP8_WIDTH` by `CHIP8_HEIGHT` screen, one byte per pixel row by
+/// row, 1 when lit. The pointer stays valid until the machine is destroyed
+/// and the pixels are updated by the calls that run or load it.
+///
+/// # Safety
+///
+/// `chip8` must be null or a machine from `chip8_create` that hasn't been
+/// destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip8: *const Handle) -> *const u8 {
+    match chip8.as_ref() {
+        Some(handle) => handle.display().pixels.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Whether a tone should be playing.
+///
+/// # Safety
+///
+/// `chip8` must be null or a machine from `chip8_create` that hasn't been
+/// destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_sound_active(chip8: *const Handle) -> bool {
+    chip8.as_ref().is_some_and(|handle| handle.chip8.sound_active())
+}
+
+/// Whether the machine halted on a fault, such as an invalid instruction.
+///
+/// # Safety
+///
+/// `chip8` must be null or a machine from `chip8_create` that hasn't been
+/// destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_faulted(chip8: *const Handle) -> bool {
+    chip8.as_ref().is_some_and(|handle| handle.chip8.fault().is_some())
+}
+
+/// # Safety
+///
+/// `chip8` must be null or a machine from `chip8_create` that hasn't been
+/// destroyed. `registers` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_registers(chip8: *const Handle, registers: *mut Registers)
+                                             -> Status {
+    if chip8.is_null() || registers.is_null() {
+        return CHIP8_NULL_POINTER;
+    }
+
+    let chip8 = &(*chip8).chip8;
+    *registers = Registers {
+        v: chip8.registers(),
+        i: chip8.index(),
+        pc: chip8.pc(),
+        delay_timer: chip8.delay_timer(),
+        sound_timer: chip8.sound_timer(),
+    };
+    CHIP8_OK
+}
+
+/// Overwrite the registers. A `pc` outside memory faults on the next step.
+///
+/// # Safety
+///
+/// `chip8` must be null or a machine from `chip8_create` that hasn't been
+/// destroyed. `registers` must be null or valid for reads.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_registers(chip8: *mut Handle, registers: *const Registers)
+                                             -> Status {
+    if chip8.is_null() || registers.is_null() {
+        return CHIP8_NULL_POINTER;
+    }
+
+    let chip8 = &mut (*chip8).chip8;
+    let registers = &*registers;
+    chip8.set_registers(registers.v);
+    chip8.set_index(registers.i);
+    chip8.set_pc(registers.pc);
+    chip8.set_delay_timer(registers.delay_timer);
+    chip8.set_sound_timer(registers.sound_timer);
+    CHIP8_OK
+}
+
+/// The size of a save state, the same format as the SDL frontend's slots.
+#[no_mangle]
+pub extern "C" fn chip8_state_size() -> usize {
+    STATE_SIZE + FRAME_BYTES
+}
+
+/// Write a save state of `chip8_state_size()` bytes to `buffer`.
+///
+/// # Safety
+///
+/// `chip8` must be null or a machine from `chip8_create` that hasn't been
+/// destroyed. `buffer` must be null or valid for writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_save_state(chip8: *const Handle, buffer: *mut u8, len: usize)
+                                          -> Status {
+    if chip8.is_null() || buffer.is_null() {
+        return CHIP8_NULL_POINTER;
+    }
+    if len < chip8_state_size() {
+        return CHIP8_BUFFER_TOO_SMALL;
+    }
+
+    let chip8 = &(*chip8).chip8;
+    let mut state = chip8.save_state();
+    state.extend(chip8.save_frame());
+    ptr::copy_nonoverlapping(state.as_ptr(), buffer, state.len());
+    CHIP8_OK
+}
+
+/// Restore a state written by `chip8_save_state`.
+///
+/// # Safety
+///
+/// `chip8` must be null or a machine from `chip8_create` that hasn't been
+/// destroyed. `buffer` must be null or valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_state(chip8: *mut Handle, buffer: *const u8, len: usize)
+                                          -> Status {
+    if chip8.is_null() || buffer.is_null() {
+        return CHIP8_NULL_POINTER;
+    }
+    if len != chip8_state_size() {
+        return CHIP8_NOT_A_STATE;
+    }
+
+    let chip8 = &mut (*chip8).chip8;
+    let (machine, frame) = slice::from_raw_parts(buffer, len).split_at(STATE_SIZE);
+    let result = chip8.load_state(machine).and_then(|_| chip8.load_frame(frame));
+    chip8.present();
+    status(result)
+}
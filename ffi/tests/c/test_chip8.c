@@ -0,0 +1,106 @@
+/* Exercises the C API the way an embedding tool would. Built and run by
+ * tests/c_api.rs, exits non-zero on the first failed check. */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "chip8.h"
+
+#define CHECK(condition)                                                    \
+  do {                                                                      \
+    if (!(condition)) {                                                     \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__,      \
+              #condition);                                                  \
+      exit(1);                                                              \
+    }                                                                       \
+  } while (0)
+
+/* LD V5, K; LD F, V5; DRW V0, V0, 5; end: JP end */
+static const uint8_t SHOW_KEY[] = {0xF5, 0x0A, 0xF5, 0x29, 0xD0, 0x05, 0x12, 0x06};
+
+static int lit_pixels(const chip8_t *chip8) {
+  const uint8_t *pixels = chip8_framebuffer(chip8);
+  int lit = 0;
+  for (int i = 0; i < CHIP8_WIDTH * CHIP8_HEIGHT; i++) {
+    lit += pixels[i];
+  }
+  return lit;
+}
+
+static void test_errors(void) {
+  uint8_t too_long[4096] = {0};
+  chip8_t *chip8 = chip8_create(0);
+
+  CHECK(chip8_abi_version() == CHIP8_ABI_VERSION);
+  CHECK(chip8_load_rom(NULL, SHOW_KEY, sizeof(SHOW_KEY)) == CHIP8_NULL_POINTER);
+  CHECK(chip8_load_rom(chip8, too_long, sizeof(too_long)) == CHIP8_PROGRAM_TOO_LONG);
+  CHECK(strcmp(chip8_status_message(CHIP8_PROGRAM_TOO_LONG), "Program too long") == 0);
+  CHECK(strcmp(chip8_status_message(1000), "Unknown status") == 0);
+  CHECK(chip8_set_key(chip8, 0x10, true) == CHIP8_INVALID_KEY);
+  CHECK(chip8_set_quirks(chip8, "nes") == CHIP8_UNKNOWN_PRESET);
+  CHECK(chip8_set_quirks(chip8, "schip") == CHIP8_OK);
+  CHECK(chip8_framebuffer(NULL) == NULL);
+
+  chip8_destroy(chip8);
+  chip8_destroy(NULL);
+}
+
+static void test_game(void) {
+  chip8_t *chip8 = chip8_create(42);
+  CHECK(chip8_load_rom(chip8, SHOW_KEY, sizeof(SHOW_KEY)) == CHIP8_OK);
+
+  /* Redrawn at power on, then waiting for a key */
+  CHECK(chip8_run_frame(chip8));
+  CHECK(!chip8_run_frame(chip8));
+  CHECK(lit_pixels(chip8) == 0);
+
+  CHECK(chip8_set_key(chip8, 7, true) == CHIP8_OK);
+  chip8_run_frame(chip8);
+  CHECK(chip8_set_key(chip8, 7, false) == CHIP8_OK);
+  CHECK(chip8_run_frame(chip8));
+  CHECK(!chip8_faulted(chip8));
+
+  /* The top of the "7" glyph, F0 */
+  const uint8_t *pixels = chip8_framebuffer(chip8);
+  CHECK(pixels[0] && pixels[1] && pixels[2] && pixels[3] && !pixels[4]);
+
+  chip8_registers_t registers;
+  CHECK(chip8_get_registers(chip8, &registers) == CHIP8_OK);
+  CHECK(registers.v[5] == 7);
+  CHECK(registers.pc == 0x206);
+
+  /* Save, start over and load again */
+  size_t size = chip8_state_size();
+  uint8_t *state = malloc(size);
+  CHECK(chip8_save_state(chip8, state, size - 1) == CHIP8_BUFFER_TOO_SMALL);
+  CHECK(chip8_save_state(chip8, state, size) == CHIP8_OK);
+  int lit = lit_pixels(chip8);
+
+  chip8_reset(chip8);
+  CHECK(lit_pixels(chip8) == 0);
+  CHECK(chip8_load_state(chip8, state, 10) == CHIP8_NOT_A_STATE);
+  CHECK(chip8_load_state(chip8, state, size) == CHIP8_OK);
+  CHECK(lit_pixels(chip8) == lit);
+  free(state);
+
+  /* Draw the "A" glyph from a debugger: point I at it and rerun DRW */
+  registers.v[5] = 0xA;
+  registers.pc = 0x202;
+  CHECK(chip8_set_registers(chip8, &registers) == CHIP8_OK);
+  chip8_step(chip8);
+  chip8_step(chip8);
+  CHECK(chip8_get_registers(chip8, &registers) == CHIP8_OK);
+  CHECK(registers.pc == 0x206);
+  /* "A" is F0 90 F0 90 90 and "7" F0 10 20 40 40, XORed together */
+  CHECK(!pixels[0] && !pixels[3]);
+  CHECK(pixels[CHIP8_WIDTH] && !pixels[CHIP8_WIDTH + 3]);
+
+  chip8_destroy(chip8);
+}
+
+int main(void) {
+  test_errors();
+  test_game();
+  printf("ok\n");
+  return 0;
+}
@@ -0,0 +1,35 @@
+//! Compiles `c/test_chip8.c` against `include/chip8.h` and the static
+//! library, then runs it. `CC` picks the compiler, `cc` by default.
+
+#![cfg(unix)]
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn c_program() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // libchip8.a is built next to the test executable. Linking it rather
+    // than libchip8.so means no stale copy elsewhere is picked up at runtime.
+    let exe = env::current_exe().unwrap();
+    let libs = exe.parent().unwrap();
+    let program = libs.join("test_chip8");
+
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(&compiler)
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror"])
+        .arg("-I").arg(manifest.join("include"))
+        .arg(manifest.join("tests").join("c").join("test_chip8.c"))
+        .arg("-o").arg(&program)
+        .arg(libs.join("libchip8.a"))
+        // What the Rust standard library needs from the system
+        .args(["-lpthread", "-ldl", "-lm"])
+        .status()
+        .unwrap_or_else(|e| panic!("{}: {}", compiler, e));
+    assert!(status.success(), "test_chip8.c failed to build");
+
+    let output = Command::new(&program).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(output.stdout, b"ok\n");
+}
@@ -0,0 +1,19 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate chip8_emulator;
+
+use chip8_emulator::disasm::disassemble;
+use chip8_emulator::opcode::OpCode;
+
+fuzz_target!(|data: &[u8]| {
+    for bytes in data.chunks(2).filter(|bytes| bytes.len() == 2) {
+        let opcode = OpCode::new(&[bytes[0], bytes[1]]);
+        if let Ok(instruction) = opcode.to_instruction() {
+            let _ = instruction.to_string();
+        }
+    }
+
+    for addr in 0..data.len().min(0x1000) {
+        let _ = disassemble(data, addr as u16);
+    }
+});
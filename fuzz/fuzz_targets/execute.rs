@@ -0,0 +1,58 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate chip8_emulator;
+
+use chip8_emulator::headless::{headless, HeadlessConfig, InputEvent};
+use chip8_emulator::peripherals::Chip8Key;
+use chip8_emulator::quirks::Quirks;
+
+/// Frames each input runs for at most.
+const FRAMES: usize = 30;
+
+// The first byte picks the quirks, the second a key tapped every other frame
+// so programs waiting for input carry on. The rest is the program.
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+
+    let flags = data[0];
+    let quirks = Quirks {
+        shift_vy: flags & 0x01 != 0,
+        load_store_increment: flags & 0x02 != 0,
+        jump_vx: flags & 0x04 != 0,
+        vf_reset: flags & 0x08 != 0,
+        display_wait: flags & 0x10 != 0,
+        clip_sprites: flags & 0x20 != 0,
+        legacy_alu_flags: flags & 0x40 != 0,
+        key_on_press: flags & 0x80 != 0,
+    };
+    let key = Chip8Key::new(data[1] & 0xF).unwrap();
+    let input = (0..FRAMES)
+        .map(|frame| InputEvent {
+            frame: frame,
+            key: key,
+            pressed: frame % 2 == 0,
+        })
+        .collect();
+    let config = HeadlessConfig {
+        quirks: quirks,
+        cycles_per_frame: 100,
+        input: input,
+        ..HeadlessConfig::default()
+    };
+
+    let program = &data[2..];
+    let mut chip8 = match headless(program, &config) {
+        Ok(chip8) => chip8,
+        // Too long to fit in memory
+        Err(_) => return,
+    };
+    for _ in 0..FRAMES {
+        chip8.poll_input();
+        chip8.run_frame();
+        if chip8.fault().is_some() {
+            break;
+        }
+    }
+});
@@ -0,0 +1,497 @@
+//! A libretro core, to play through RetroArch and other libretro frontends.
+//!
+//! The frontend calls the `retro_*` functions from a single thread, so the
+//! machine and the frontend's callbacks live in thread locals. Quirks and
+//! speed default to what the ROM database recommends for the game, like the
+//! SDL frontend does unless `--no-autodetect` is given.
+
+// Struct literals spell out `field: field`
+#![allow(clippy::redundant_field_names)]
+
+extern crate chip8_emulator;
+
+pub mod retro;
+
+use std::cell::{Cell, RefCell};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_uint, c_void};
+use std::ptr;
+use std::slice;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chip8_emulator::chip8::{Chip8, DEFAULT_CYCLES_PER_FRAME, FRAME_BYTES, STATE_SIZE};
+use chip8_emulator::config::{self, AudioConfig, Palette, Rgb, PALETTE_NAMES};
+use chip8_emulator::peripherals::{Chip8Disp, Chip8Input, Chip8Key, Framebuffer, KeyEvent,
+                                  KeyQueue, Region};
+use chip8_emulator::quirks::{Quirks, PRESET_NAMES};
+use chip8_emulator::romdb::{RomDatabase, RomInfo};
+
+use retro::*;
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 32;
+const SAMPLE_RATE: f64 = 44100.0;
+/// Audio frames per 60 Hz video frame.
+const SAMPLES_PER_FRAME: usize = 735;
+
+const QUIRKS_OPTION: &str = "chip8_quirks";
+const SPEED_OPTION: &str = "chip8_speed";
+const PALETTE_OPTION: &str = "chip8_palette";
+/// Instructions per frame offered besides `auto`.
+const SPEEDS: [usize; 12] = [5, 7, 9, 12, 15, 20, 30, 50, 100, 200, 500, 1000];
+
+/// RetroPad buttons mapped like the SDL frontend's default gamepad profile,
+/// by position: the RetroPad's B is the bottom face button.
+const BUTTONS: [(c_uint, Chip8Key); 12] = [
+    (RETRO_DEVICE_ID_JOYPAD_UP, Chip8Key::Key2),
+    (RETRO_DEVICE_ID_JOYPAD_DOWN, Chip8Key::Key8),
+    (RETRO_DEVICE_ID_JOYPAD_LEFT, Chip8Key::Key4),
+    (RETRO_DEVICE_ID_JOYPAD_RIGHT, Chip8Key::Key6),
+    (RETRO_DEVICE_ID_JOYPAD_B, Chip8Key::Key5),
+    (RETRO_DEVICE_ID_JOYPAD_A, Chip8Key::Key0),
+    (RETRO_DEVICE_ID_JOYPAD_Y, Chip8Key::Key7),
+    (RETRO_DEVICE_ID_JOYPAD_X, Chip8Key::Key9),
+    (RETRO_DEVICE_ID_JOYPAD_L, Chip8Key::Key1),
+    (RETRO_DEVICE_ID_JOYPAD_R, Chip8Key::Key3),
+    (RETRO_DEVICE_ID_JOYPAD_SELECT, Chip8Key::KeyA),
+    (RETRO_DEVICE_ID_JOYPAD_START, Chip8Key::KeyB),
+];
+
+#[derive(Copy, Clone, Default)]
+struct Callbacks {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+}
+
+thread_local! {
+    static CALLBACKS: Cell<Callbacks> = Cell::new(Callbacks::default());
+    static CORE: RefCell<Option<Core>> = const { RefCell::new(None) };
+}
+
+fn callbacks() -> Callbacks {
+    CALLBACKS.with(|callbacks| callbacks.get())
+}
+
+fn set_callbacks<F: FnOnce(&mut Callbacks)>(set: F) {
+    CALLBACKS.with(|callbacks| {
+        let mut updated = callbacks.get();
+        set(&mut updated);
+        callbacks.set(updated);
+    });
+}
+
+/// Run `f` on the loaded game, if there is one.
+fn with_core<F: FnOnce(&mut Core) -> R, R>(f: F) -> Option<R> {
+    CORE.with(|core| core.borrow_mut().as_mut().map(f))
+}
+
+fn environment<T>(cmd: c_uint, data: &mut T) -> bool {
+    callbacks().environment.is_some_and(|environment| {
+        environment(cmd, data as *mut T as *mut c_void)
+    })
+}
+
+/// The frontend's value for a core option.
+fn option(key: &str) -> Option<String> {
+    let key = CString::new(key).unwrap();
+    let mut variable = Variable {
+        key: key.as_ptr(),
+        value: ptr::null(),
+    };
+    if !environment(RETRO_ENVIRONMENT_GET_VARIABLE, &mut variable) || variable.value.is_null() {
+        return None;
+    }
+
+    Some(unsafe { CStr::from_ptr(variable.value) }.to_string_lossy().into_owned())
+}
+
+fn declare_options() {
+    let speeds: Vec<String> = SPEEDS.iter().map(|speed| speed.to_string()).collect();
+    let options = [
+        (QUIRKS_OPTION, format!("Quirks preset; auto|{}", PRESET_NAMES.join("|"))),
+        (SPEED_OPTION, format!("Instructions per frame; auto|{}", speeds.join("|"))),
+        (PALETTE_OPTION, format!("Palette; {}", PALETTE_NAMES.join("|"))),
+    ];
+    let options: Vec<(CString, CString)> = options.iter()
+        .map(|&(key, ref value)| {
+            (CString::new(key).unwrap(), CString::new(value.as_str()).unwrap())
+        })
+        .collect();
+
+    let mut variables: Vec<Variable> = options.iter()
+        .map(|(key, value)| Variable { key: key.as_ptr(), value: value.as_ptr() })
+        .collect();
+    variables.push(Variable { key: ptr::null(), value: ptr::null() });
+    environment(RETRO_ENVIRONMENT_SET_VARIABLES, &mut variables[0]);
+}
+
+fn xrgb(color: Rgb) -> u32 {
+    let Rgb(r, g, b) = color;
+    (r as u32) << 16 | (g as u32) << 8 | b as u32
+}
+
+/// The screen as XRGB8888 pixels, the format handed to the frontend.
+struct Screen {
+    palette: Palette,
+    pixels: Vec<u32>,
+}
+
+impl Screen {
+    fn redraw(&mut self, frame: &Framebuffer, region: Region) {
+        let foreground = xrgb(self.palette.foreground);
+        let background = xrgb(self.palette.background);
+        for y in region.y..(region.y + region.height) {
+            for x in region.x..(region.x + region.width) {
+                let lit = frame.pixel(x, y);
+                self.pixels[y * WIDTH + x] = if lit { foreground } else { background };
+            }
+        }
+    }
+}
+
+impl Chip8Disp for Screen {
+    fn present(&mut self, frame: &Framebuffer, dirty: Option<Region>) {
+        if let Some(dirty) = dirty {
+            self.redraw(frame, dirty);
+        }
+    }
+}
+
+/// The Chip-8 keys held on the first RetroPad.
+struct RetroPad {
+    keys: KeyQueue,
+}
+
+impl Chip8Input for RetroPad {
+    fn key_pressed(&self, key: Chip8Key) -> bool {
+        self.keys.key_pressed(key)
+    }
+
+    fn next_event(&mut self) -> Option<KeyEvent> {
+        self.keys.next_event()
+    }
+
+    fn poll(&mut self) -> bool {
+        let callbacks = callbacks();
+        if let (Some(poll), Some(state)) = (callbacks.input_poll, callbacks.input_state) {
+            poll();
+            for &(button, key) in BUTTONS.iter() {
+                let pressed = state(0, RETRO_DEVICE_JOYPAD, 0, button) != 0;
+                self.keys.set_key(key, pressed);
+            }
+        }
+
+        false
+    }
+}
+
+struct Core {
+    chip8: Chip8<Screen, RetroPad>,
+    program: Vec<u8>,
+    info: RomInfo,
+    audio: AudioConfig,
+    // Position in the square wave period, from 0 to 1
+    phase: f32,
+}
+
+impl Core {
+    /// Apply the core options, `auto` picking the ROM's recommended
+    /// settings. ROMs without any keep the default quirks.
+    fn apply_options(&mut self) {
+        let preset = match option(QUIRKS_OPTION) {
+            Some(ref preset) if preset != "auto" => Some(preset.clone()),
+            _ => self.info.quirks_preset(),
+        };
+        let quirks = match preset {
+            Some(ref preset) => Quirks::preset(preset),
+            None => Some(Quirks::default()),
+        };
+        if let Some(quirks) = quirks {
+            if quirks != self.chip8.quirks() {
+                self.chip8.set_quirks(quirks);
+            }
+        }
+
+        let speed = option(SPEED_OPTION)
+            .and_then(|speed| speed.parse().ok())
+            .or_else(|| self.info.entry.as_ref().and_then(|entry| entry.speed))
+            .unwrap_or(DEFAULT_CYCLES_PER_FRAME);
+        self.chip8.set_cycles_per_frame(speed);
+
+        let palette = option(PALETTE_OPTION)
+            .and_then(|name| Palette::preset(&name))
+            .unwrap_or_else(|| Palette::preset(PALETTE_NAMES[0]).unwrap());
+        let frame = *self.chip8.framebuffer();
+        let screen = self.chip8.display_mut().unwrap();
+        if palette != screen.palette {
+            screen.palette = palette;
+            screen.redraw(&frame, Region::full());
+        }
+    }
+
+    /// Hand the frontend a frame of square wave while the sound timer runs,
+    /// and silence otherwise.
+    fn play_audio(&mut self, audio_sample_batch: AudioSampleBatchFn) {
+        let mut samples = [0i16; SAMPLES_PER_FRAME * 2];
+        if self.chip8.sound_active() {
+            let volume = self.audio.volume.clamp(0.0, 1.0);
+            let amplitude = (volume * i16::MAX as f32) as i16;
+            let phase_inc = self.audio.frequency / SAMPLE_RATE as f32;
+            for frame in samples.chunks_mut(2) {
+                let sample = if self.phase <= 0.5 { amplitude } else { -amplitude };
+                frame[0] = sample;
+                frame[1] = sample;
+                self.phase = (self.phase + phase_inc) % 1.0;
+            }
+        }
+
+        let mut sent = 0;
+        while sent < SAMPLES_PER_FRAME {
+            let used = audio_sample_batch(samples[sent * 2..].as_ptr(), SAMPLES_PER_FRAME - sent);
+            if used == 0 {
+                break;
+            }
+            sent += used;
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(environment: EnvironmentFn) {
+    set_callbacks(|callbacks| callbacks.environment = Some(environment));
+    declare_options();
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(video_refresh: VideoRefreshFn) {
+    set_callbacks(|callbacks| callbacks.video_refresh = Some(video_refresh));
+}
+
+/// Unused, audio is handed over a frame at a time.
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_audio_sample: AudioSampleFn) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(audio_sample_batch: AudioSampleBatchFn) {
+    set_callbacks(|callbacks| callbacks.audio_sample_batch = Some(audio_sample_batch));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(input_poll: InputPollFn) {
+    set_callbacks(|callbacks| callbacks.input_poll = Some(input_poll));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(input_state: InputStateFn) {
+    set_callbacks(|callbacks| callbacks.input_state = Some(input_state));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    CORE.with(|core| *core.borrow_mut() = None);
+}
+
+/// # Safety
+///
+/// `info` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    *info = SystemInfo {
+        library_name: b"chip8\0".as_ptr() as *const c_char,
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        // The ones the SDL frontend's "Load ROM" menu lists
+        valid_extensions: b"ch8|c8|rom\0".as_ptr() as *const c_char,
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+///
+/// `info` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    *info = SystemAvInfo {
+        geometry: GameGeometry {
+            base_width: WIDTH as c_uint,
+            base_height: HEIGHT as c_uint,
+            max_width: WIDTH as c_uint,
+            max_height: HEIGHT as c_uint,
+            aspect_ratio: 2.0,
+        },
+        timing: SystemTiming {
+            fps: 60.0,
+            sample_rate: SAMPLE_RATE,
+        },
+    };
+}
+
+/// Only the RetroPad is supported.
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    with_core(|core| {
+        core.chip8.reset();
+        // Can't fail, retro_load_game only keeps programs that fit
+        let _ = core.chip8.upload_rom(&core.program);
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let mut updated = false;
+    environment(RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE, &mut updated);
+
+    let callbacks = callbacks();
+    with_core(|core| {
+        if updated {
+            core.apply_options();
+        }
+
+        core.chip8.poll_input();
+        core.chip8.run_frame();
+        core.chip8.present();
+
+        if let Some(video_refresh) = callbacks.video_refresh {
+            let pixels = &core.chip8.display().unwrap().pixels;
+            video_refresh(pixels.as_ptr() as *const c_void, WIDTH as c_uint, HEIGHT as c_uint,
+                          WIDTH * 4);
+        }
+        if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+            core.play_audio(audio_sample_batch);
+        }
+    });
+}
+
+/// Save states are the machine state followed by the screen, the format
+/// of the SDL frontend's save slots.
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    STATE_SIZE + FRAME_BYTES
+}
+
+/// Fails if `size` is less than `retro_serialize_size()`.
+///
+/// # Safety
+///
+/// `data` must be valid for writes of `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    if size < STATE_SIZE + FRAME_BYTES {
+        return false;
+    }
+
+    with_core(|core| {
+        let mut state = core.chip8.save_state();
+        state.extend(core.chip8.save_frame());
+        slice::from_raw_parts_mut(data as *mut u8, state.len()).copy_from_slice(&state);
+    }).is_some()
+}
+
+/// Fails if `size` is less than `retro_serialize_size()` or the state is
+/// invalid.
+///
+/// # Safety
+///
+/// `data` must be valid for reads of `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    if size < STATE_SIZE + FRAME_BYTES {
+        return false;
+    }
+
+    let state = slice::from_raw_parts(data as *const u8, STATE_SIZE + FRAME_BYTES);
+    let (machine, frame) = state.split_at(STATE_SIZE);
+    with_core(|core| {
+        core.chip8.load_state(machine).and_then(|_| core.chip8.load_frame(frame)).is_ok()
+    }).unwrap_or(false)
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+/// # Safety
+///
+/// `game` must be null or point to a `retro_game_info` whose `data`, if not
+/// null, is valid for reads of `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const GameInfo) -> bool {
+    if game.is_null() || (*game).data.is_null() {
+        return false;
+    }
+    let program = slice::from_raw_parts((*game).data as *const u8, (*game).size).to_vec();
+
+    let mut pixel_format = RETRO_PIXEL_FORMAT_XRGB8888;
+    if !environment(RETRO_ENVIRONMENT_SET_PIXEL_FORMAT, &mut pixel_format) {
+        return false;
+    }
+
+    let mut chip8 = Chip8::new();
+    if chip8.upload_rom(&program).is_err() {
+        return false;
+    }
+    // Nothing in a libretro core to seed from but the clock
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.subsec_nanos());
+    chip8.seed_rng(seed);
+    let palette = Palette::preset(PALETTE_NAMES[0]).unwrap();
+    chip8.connect_display(Screen {
+        palette: palette,
+        pixels: vec![xrgb(palette.background); WIDTH * HEIGHT],
+    });
+    chip8.connect_keyboard(RetroPad { keys: KeyQueue::new() });
+
+    let info = RomInfo::identify(&RomDatabase::bundled(), &config::rom_id(&program), &program);
+    let mut core = Core {
+        chip8: chip8,
+        program: program,
+        info: info,
+        audio: AudioConfig::default(),
+        phase: 0.0,
+    };
+    core.apply_options();
+    CORE.with(|slot| *slot.borrow_mut() = Some(core));
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(_game_type: c_uint, _info: *const GameInfo,
+                                          _num_info: usize) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    CORE.with(|core| *core.borrow_mut() = None);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+/// Memory isn't exposed, so achievements and cheats don't work.
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_id: c_uint) -> *mut c_void {
+    ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_id: c_uint) -> usize {
+    0
+}
@@ -0,0 +1,244 @@
+//! Loads the built core with libloading and drives it the way a libretro
+//! frontend would, without a window or audio device.
+
+extern crate chip8_emulator;
+extern crate libloading;
+
+#[path = "../src/retro.rs"]
+#[allow(dead_code)]
+mod retro;
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::os::raw::{c_uint, c_void};
+use std::ptr;
+use std::slice;
+
+use libloading::Library;
+
+use chip8_emulator::config::Palette;
+use chip8_emulator::quirks::Quirks;
+
+use retro::*;
+
+// LD V5, K; LD F, V5; DRW V0, V0, 5; LD V1, 30; LD ST, V1; end: JP end
+const SHOW_KEY: &[u8] = &[0xF5, 0x0A, 0xF5, 0x29, 0xD0, 0x05, 0x61, 0x1E, 0xF1, 0x18, 0x12, 0x0A];
+
+thread_local! {
+    static PIXEL_FORMAT: Cell<Option<c_uint>> = const { Cell::new(None) };
+    static DECLARED: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+    static OPTIONS: RefCell<HashMap<String, CString>> = RefCell::new(HashMap::new());
+    static OPTIONS_UPDATED: Cell<bool> = const { Cell::new(false) };
+    static VIDEO: RefCell<Option<(Vec<u32>, c_uint, c_uint)>> = const { RefCell::new(None) };
+    static AUDIO: RefCell<Vec<i16>> = const { RefCell::new(Vec::new()) };
+    static BUTTONS: Cell<u16> = const { Cell::new(0) };
+}
+
+extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    unsafe {
+        match cmd {
+            RETRO_ENVIRONMENT_SET_PIXEL_FORMAT => {
+                PIXEL_FORMAT.with(|format| format.set(Some(*(data as *const c_uint))));
+                true
+            }
+            RETRO_ENVIRONMENT_SET_VARIABLES => {
+                let mut variable = data as *const Variable;
+                while !(*variable).key.is_null() {
+                    let key = CStr::from_ptr((*variable).key).to_string_lossy().into_owned();
+                    let value = CStr::from_ptr((*variable).value).to_string_lossy().into_owned();
+                    DECLARED.with(|declared| declared.borrow_mut().push((key, value)));
+                    variable = variable.offset(1);
+                }
+                true
+            }
+            RETRO_ENVIRONMENT_GET_VARIABLE => {
+                let variable = &mut *(data as *mut Variable);
+                let key = CStr::from_ptr(variable.key).to_string_lossy().into_owned();
+                OPTIONS.with(|options| match options.borrow().get(&key) {
+                    Some(value) => {
+                        variable.value = value.as_ptr();
+                        true
+                    }
+                    None => false,
+                })
+            }
+            RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE => {
+                *(data as *mut bool) = OPTIONS_UPDATED.with(|updated| updated.replace(false));
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+extern "C" fn video_refresh(data: *const c_void, width: c_uint, height: c_uint, pitch: usize) {
+    assert_eq!(pitch, width as usize * 4);
+    let pixels = unsafe { slice::from_raw_parts(data as *const u32, (width * height) as usize) };
+    VIDEO.with(|video| *video.borrow_mut() = Some((pixels.to_vec(), width, height)));
+}
+
+extern "C" fn audio_sample_batch(data: *const i16, frames: usize) -> usize {
+    let samples = unsafe { slice::from_raw_parts(data, frames * 2) };
+    AUDIO.with(|audio| audio.borrow_mut().extend_from_slice(samples));
+    frames
+}
+
+extern "C" fn input_poll() {}
+
+extern "C" fn input_state(port: c_uint, device: c_uint, _index: c_uint, id: c_uint) -> i16 {
+    let pressed = port == 0 && device == RETRO_DEVICE_JOYPAD &&
+                  BUTTONS.with(|buttons| buttons.get()) & (1 << id) != 0;
+    pressed as i16
+}
+
+fn set_option(key: &str, value: &str) {
+    let value = CString::new(value).unwrap();
+    OPTIONS.with(|options| options.borrow_mut().insert(key.to_string(), value));
+    OPTIONS_UPDATED.with(|updated| updated.set(true));
+}
+
+/// The core, which cargo builds next to the test executable.
+fn load_core() -> Library {
+    let exe = env::current_exe().unwrap();
+    let name = format!("{}chip8_emulator_libretro{}", env::consts::DLL_PREFIX,
+                       env::consts::DLL_SUFFIX);
+    let path = exe.parent().unwrap().join(name);
+    unsafe { Library::new(&path) }.unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+fn symbol<T: Copy>(core: &Library, name: &str) -> T {
+    unsafe { *core.get::<T>(name.as_bytes()).unwrap() }
+}
+
+/// Run a frame and return the screen.
+fn run(core: &Library) -> Vec<u32> {
+    symbol::<extern "C" fn()>(core, "retro_run")();
+    let (pixels, width, height) = VIDEO.with(|video| video.borrow_mut().take())
+        .expect("no video this frame");
+    assert_eq!((width, height), (64, 32));
+    pixels
+}
+
+/// Load `program` into the core.
+fn start(core: &Library, program: &[u8]) {
+    assert_eq!(symbol::<extern "C" fn() -> c_uint>(core, "retro_api_version")(), 1);
+    symbol::<extern "C" fn(EnvironmentFn)>(core, "retro_set_environment")(environment);
+    symbol::<extern "C" fn(VideoRefreshFn)>(core, "retro_set_video_refresh")(video_refresh);
+    symbol::<extern "C" fn(AudioSampleBatchFn)>(core, "retro_set_audio_sample_batch")(
+        audio_sample_batch);
+    symbol::<extern "C" fn(InputPollFn)>(core, "retro_set_input_poll")(input_poll);
+    symbol::<extern "C" fn(InputStateFn)>(core, "retro_set_input_state")(input_state);
+    symbol::<extern "C" fn()>(core, "retro_init")();
+
+    let game = GameInfo {
+        path: ptr::null(),
+        data: program.as_ptr() as *const c_void,
+        size: program.len(),
+        meta: ptr::null(),
+    };
+    let load_game = symbol::<unsafe extern "C" fn(*const GameInfo) -> bool>(core,
+                                                                           "retro_load_game");
+    assert!(unsafe { load_game(&game) });
+    assert_eq!(PIXEL_FORMAT.with(|format| format.get()), Some(RETRO_PIXEL_FORMAT_XRGB8888));
+}
+
+fn stop(core: &Library) {
+    symbol::<extern "C" fn()>(core, "retro_unload_game")();
+    symbol::<extern "C" fn()>(core, "retro_deinit")();
+}
+
+#[test]
+fn system_info() {
+    let core = load_core();
+    unsafe {
+        let mut info: SystemInfo = mem::zeroed();
+        symbol::<unsafe extern "C" fn(*mut SystemInfo)>(&core, "retro_get_system_info")(
+            &mut info);
+        assert_eq!(CStr::from_ptr(info.library_name).to_str(), Ok("chip8"));
+        assert_eq!(CStr::from_ptr(info.valid_extensions).to_str(), Ok("ch8|c8|rom"));
+        assert!(!info.need_fullpath);
+
+        let mut av_info: SystemAvInfo = mem::zeroed();
+        symbol::<unsafe extern "C" fn(*mut SystemAvInfo)>(&core, "retro_get_system_av_info")(
+            &mut av_info);
+        assert_eq!((av_info.geometry.base_width, av_info.geometry.base_height), (64, 32));
+        assert_eq!(av_info.timing.fps, 60.0);
+        assert_eq!(av_info.timing.sample_rate, 44100.0);
+    }
+}
+
+#[test]
+fn options() {
+    let core = load_core();
+    symbol::<extern "C" fn(EnvironmentFn)>(&core, "retro_set_environment")(environment);
+
+    let declared = DECLARED.with(|declared| declared.borrow().clone());
+    let keys: Vec<&str> = declared.iter().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(keys, ["chip8_quirks", "chip8_speed", "chip8_palette"]);
+    for (key, definition) in declared.iter() {
+        let values = definition.split_once("; ").unwrap().1;
+        for value in values.split('|').filter(|&value| value != "auto") {
+            let valid = match key.as_str() {
+                "chip8_quirks" => Quirks::preset(value).is_some(),
+                "chip8_speed" => value.parse::<usize>().is_ok(),
+                _ => Palette::preset(value).is_some(),
+            };
+            assert!(valid, "{} = {}", key, value);
+        }
+    }
+}
+
+#[test]
+fn plays_a_game() {
+    let core = load_core();
+    start(&core, SHOW_KEY);
+    let white = 0x00FF_FFFF;
+
+    // Waiting for a key, the screen stays blank and silent
+    for _ in 0..3 {
+        assert!(run(&core).iter().all(|&pixel| pixel == white));
+    }
+    assert!(AUDIO.with(|audio| audio.borrow().iter().all(|&sample| sample == 0)));
+    assert_eq!(AUDIO.with(|audio| audio.borrow().len()), 3 * 735 * 2);
+
+    // Y is the left face button, key 7
+    BUTTONS.with(|buttons| buttons.set(1 << RETRO_DEVICE_ID_JOYPAD_Y));
+    run(&core);
+    BUTTONS.with(|buttons| buttons.set(0));
+    AUDIO.with(|audio| audio.borrow_mut().clear());
+    let mut pixels = run(&core);
+    for _ in 0..3 {
+        pixels = run(&core);
+    }
+    // The top of the "7" glyph, F0, in black
+    assert_eq!(&pixels[..5], &[0, 0, 0, 0, white]);
+    assert!(AUDIO.with(|audio| audio.borrow().iter().any(|&sample| sample != 0)));
+
+    // Save, start over and load again
+    let size = symbol::<extern "C" fn() -> usize>(&core, "retro_serialize_size")();
+    let mut state = vec![0u8; size];
+    unsafe {
+        let serialize = symbol::<unsafe extern "C" fn(*mut c_void, usize) -> bool>(
+            &core, "retro_serialize");
+        assert!(serialize(state.as_mut_ptr() as *mut c_void, size));
+    }
+    symbol::<extern "C" fn()>(&core, "retro_reset")();
+    assert!(run(&core).iter().all(|&pixel| pixel == white));
+    unsafe {
+        let unserialize = symbol::<unsafe extern "C" fn(*const c_void, usize) -> bool>(
+            &core, "retro_unserialize");
+        assert!(!unserialize(state.as_ptr() as *const c_void, 10));
+        assert!(unserialize(state.as_ptr() as *const c_void, size));
+    }
+    assert_eq!(run(&core), pixels);
+
+    // Options apply on the next frame
+    set_option("chip8_palette", "green");
+    let pixels = run(&core);
+    assert_eq!(&pixels[..5], &[0x0033_FF66, 0x0033_FF66, 0x0033_FF66, 0x0033_FF66, 0x000A_140A]);
+
+    stop(&core);
+}
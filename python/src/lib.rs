@@ -0,0 +1,260 @@
+//! Python bindings, imported as `chip8`. Built with maturin, see the README.
+//! There is no window or audio: scripts drive the machine frame by frame,
+//! press keys and read the screen as a NumPy array.
+
+// Struct literals spell out `field: field`
+#![allow(clippy::redundant_field_names)]
+
+extern crate chip8_emulator;
+extern crate numpy;
+extern crate pyo3;
+
+use numpy::ndarray::Array2;
+use numpy::{IntoPyArray, PyArray2};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyList};
+
+use chip8_emulator::chip8::{Chip8, DEFAULT_CYCLES_PER_FRAME, FRAME_BYTES, STATE_SIZE};
+use chip8_emulator::error::Error;
+use chip8_emulator::headless::HeadlessDisplay;
+use chip8_emulator::peripherals::{Chip8Input, Chip8Key, KeyEvent, KeyQueue};
+use chip8_emulator::quirks::{Quirks, PRESET_NAMES};
+
+/// Keys pressed and released from Python.
+struct ScriptKeys {
+    keys: KeyQueue,
+}
+
+impl Chip8Input for ScriptKeys {
+    fn key_pressed(&self, key: Chip8Key) -> bool {
+        self.keys.key_pressed(key)
+    }
+
+    fn next_event(&mut self) -> Option<KeyEvent> {
+        self.keys.next_event()
+    }
+
+    fn poll(&mut self) -> bool {
+        false
+    }
+}
+
+fn value_error(error: Error) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+/// A Chip-8 machine.
+///
+/// `Chip8(rom=b"", seed=0)` runs `rom` with `RND` seeded by `seed`, so runs
+/// are repeatable unless a different seed is passed.
+#[pyclass(name = "Chip8", module = "chip8", unsendable)]
+pub struct PyChip8 {
+    // The screen is read from the core, no display is connected
+    chip8: Chip8<HeadlessDisplay, ScriptKeys>,
+    /// Loaded again on reset.
+    program: Vec<u8>,
+}
+
+#[pymethods]
+impl PyChip8 {
+    #[new]
+    #[pyo3(signature = (rom = b"".as_ref(), seed = 0))]
+    fn new(rom: &[u8], seed: u32) -> PyResult<PyChip8> {
+        let mut chip8 = Chip8::new();
+        chip8.seed_rng(seed);
+        chip8.connect_keyboard(ScriptKeys { keys: KeyQueue::new() });
+        chip8.upload_rom(rom).map_err(value_error)?;
+
+        Ok(PyChip8 {
+            chip8: chip8,
+            program: rom.to_vec(),
+        })
+    }
+
+    /// Reset the machine and load another ROM. Quirks and speed are kept.
+    fn load_rom(&mut self, rom: &[u8]) -> PyResult<()> {
+        self.chip8.reset();
+        self.program.clear();
+        self.chip8.upload_rom(rom).map_err(value_error)?;
+        self.program = rom.to_vec();
+        Ok(())
+    }
+
+    /// Return to the power-on state with the same ROM.
+    fn reset(&mut self) {
+        self.chip8.reset();
+        self.chip8.upload_rom(&self.program).unwrap();
+    }
+
+    /// Switch to the quirks of `"chip8"`, `"schip"` or `"xochip"`.
+    fn set_quirks(&mut self, preset: &str) -> PyResult<()> {
+        let quirks = Quirks::preset(preset).ok_or_else(|| {
+            PyValueError::new_err(format!("Unknown quirks preset: {} (expected one of {})",
+                                          preset, PRESET_NAMES.join(", ")))
+        })?;
+        self.chip8.set_quirks(quirks);
+        Ok(())
+    }
+
+    /// Instructions per 60 Hz frame, 9 unless changed.
+    #[pyo3(signature = (cycles = DEFAULT_CYCLES_PER_FRAME))]
+    fn set_cycles_per_frame(&mut self, cycles: usize) {
+        self.chip8.set_cycles_per_frame(cycles);
+    }
+
+    /// Execute `count` instructions.
+    #[pyo3(signature = (count = 1))]
+    fn step(&mut self, count: usize) {
+        for _ in 0..count {
+            self.chip8.step();
+        }
+    }
+
+    /// Emulate `count` 60 Hz frames.
+    #[pyo3(signature = (count = 1))]
+    fn run_frames(&mut self, count: usize) {
+        for _ in 0..count {
+            self.chip8.poll_input();
+            self.chip8.run_frame();
+        }
+    }
+
+    /// Press a Chip-8 key, 0 to 15. It stays down until released.
+    fn press_key(&mut self, key: u8) -> PyResult<()> {
+        self.set_key(key, true)
+    }
+
+    fn release_key(&mut self, key: u8) -> PyResult<()> {
+        self.set_key(key, false)
+    }
+
+    /// The screen as a new 32x64 `uint8` array, indexed `[y, x]`, 1 when lit.
+    fn framebuffer<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<u8>> {
+        let frame = self.chip8.framebuffer();
+        Array2::from_shape_fn((32, 64), |(y, x)| frame.pixel(x, y) as u8).into_pyarray(py)
+    }
+
+    /// `length` bytes of memory from `addr`.
+    fn read_memory<'py>(&self, py: Python<'py>, addr: usize, length: usize)
+                        -> PyResult<Bound<'py, PyBytes>> {
+        let memory = self.chip8.memory();
+        if addr.checked_add(length).is_none_or(|end| end > memory.len()) {
+            return Err(PyValueError::new_err(
+                format!("Read past the end of memory at {:#05X}", addr)));
+        }
+
+        Ok(PyBytes::new(py, &memory[addr..(addr + length)]))
+    }
+
+    /// Copy `data` into memory at `addr`.
+    fn write_memory(&mut self, addr: u16, data: &[u8]) -> PyResult<()> {
+        self.chip8.write_memory(addr, data).map_err(value_error)
+    }
+
+    /// V0 to VF, as a list.
+    #[getter]
+    fn v<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, self.chip8.registers())
+    }
+
+    #[setter]
+    fn set_v(&mut self, registers: [u8; 16]) {
+        self.chip8.set_registers(registers);
+    }
+
+    #[getter]
+    fn i(&self) -> u16 {
+        self.chip8.index()
+    }
+
+    #[setter]
+    fn set_i(&mut self, index: u16) {
+        self.chip8.set_index(index);
+    }
+
+    /// The program counter. An address outside memory faults on the next
+    /// step.
+    #[getter]
+    fn pc(&self) -> u16 {
+        self.chip8.pc()
+    }
+
+    #[setter]
+    fn set_pc(&mut self, pc: u16) {
+        self.chip8.set_pc(pc);
+    }
+
+    #[getter]
+    fn delay_timer(&self) -> u8 {
+        self.chip8.delay_timer()
+    }
+
+    #[setter]
+    fn set_delay_timer(&mut self, value: u8) {
+        self.chip8.set_delay_timer(value);
+    }
+
+    #[getter]
+    fn sound_timer(&self) -> u8 {
+        self.chip8.sound_timer()
+    }
+
+    #[setter]
+    fn set_sound_timer(&mut self, value: u8) {
+        self.chip8.set_sound_timer(value);
+    }
+
+    /// The return addresses in use, innermost last.
+    #[getter]
+    fn stack(&self) -> Vec<u16> {
+        self.chip8.stack()[..self.chip8.stack_ptr() as usize].to_vec()
+    }
+
+    /// Whether a tone should be playing.
+    #[getter]
+    fn sound_active(&self) -> bool {
+        self.chip8.sound_active()
+    }
+
+    /// The fault that halted the machine, or `None`.
+    #[getter]
+    fn fault(&self) -> Option<String> {
+        self.chip8.fault().map(|fault| fault.to_string())
+    }
+
+    /// The machine state followed by the screen, in the same format as the
+    /// save state slots of the SDL frontend.
+    fn save_state<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        let mut state = self.chip8.save_state();
+        state.extend(self.chip8.save_frame());
+        PyBytes::new(py, &state)
+    }
+
+    /// Restore a state written by `save_state`.
+    fn load_state(&mut self, state: &[u8]) -> PyResult<()> {
+        if state.len() != STATE_SIZE + FRAME_BYTES {
+            return Err(value_error(Error::NotAState));
+        }
+
+        let (machine, frame) = state.split_at(STATE_SIZE);
+        self.chip8.load_state(machine)
+            .and_then(|_| self.chip8.load_frame(frame))
+            .map_err(value_error)
+    }
+}
+
+impl PyChip8 {
+    fn set_key(&mut self, key: u8, pressed: bool) -> PyResult<()> {
+        let key = Chip8Key::new(key).map_err(value_error)?;
+        self.chip8.keyboard_mut().unwrap().keys.set_key(key, pressed);
+        Ok(())
+    }
+}
+
+#[pymodule]
+fn chip8(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyChip8>()?;
+    module.add("STATE_SIZE", STATE_SIZE + FRAME_BYTES)?;
+    Ok(())
+}
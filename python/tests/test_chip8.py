@@ -0,0 +1,110 @@
+"""Run with `python -m unittest discover python/tests` after `maturin develop`."""
+
+import unittest
+
+import chip8
+
+# LD V5, K; LD F, V5; DRW V0, V0, 5; end: JP end
+SHOW_KEY = bytes([0xF5, 0x0A, 0xF5, 0x29, 0xD0, 0x05, 0x12, 0x06])
+
+
+def show_seven():
+    machine = chip8.Chip8(SHOW_KEY)
+    machine.run_frames(2)
+    machine.press_key(7)
+    machine.run_frames()
+    machine.release_key(7)
+    machine.run_frames(2)
+    return machine
+
+
+class Chip8Test(unittest.TestCase):
+    def test_framebuffer(self):
+        machine = chip8.Chip8(SHOW_KEY)
+        machine.run_frames(3)
+        frame = machine.framebuffer()
+        self.assertEqual(frame.shape, (32, 64))
+        self.assertEqual(frame.dtype, "uint8")
+        self.assertEqual(frame.sum(), 0)
+
+        # The top of the "7" glyph, F0
+        frame = show_seven().framebuffer()
+        self.assertEqual(frame[0, :5].tolist(), [1, 1, 1, 1, 0])
+        self.assertEqual(frame[:, 8:].sum(), 0)
+
+    def test_registers(self):
+        machine = show_seven()
+        self.assertEqual(machine.v[5], 7)
+        self.assertEqual(machine.pc, 0x206)
+        self.assertEqual(machine.i, 7 * 5)
+        self.assertEqual(machine.stack, [])
+        self.assertIsNone(machine.fault)
+
+        # LD F, V5 again with V5 set by hand
+        registers = machine.v
+        registers[5] = 0xA
+        machine.v = registers
+        machine.pc = 0x202
+        machine.step()
+        self.assertEqual(machine.v[5], 0xA)
+        self.assertEqual(machine.i, 0xA * 5)
+        self.assertEqual(machine.pc, 0x204)
+
+        machine.sound_timer = 2
+        self.assertTrue(machine.sound_active)
+        machine.run_frames()
+        self.assertEqual(machine.sound_timer, 1)
+
+        machine.pc = 0xFFF
+        machine.step()
+        self.assertIn("out of memory", machine.fault)
+
+    def test_memory(self):
+        machine = chip8.Chip8(SHOW_KEY)
+        self.assertEqual(machine.read_memory(0x200, len(SHOW_KEY)), SHOW_KEY)
+        # The "0" glyph at the start of memory
+        self.assertEqual(machine.read_memory(0, 5), bytes([0xF0, 0x90, 0x90, 0x90, 0xF0]))
+
+        machine.write_memory(0x300, b"\x12\x34")
+        self.assertEqual(machine.read_memory(0x300, 2), b"\x12\x34")
+        with self.assertRaises(ValueError):
+            machine.read_memory(0xFFF, 2)
+        with self.assertRaises(ValueError):
+            machine.write_memory(0xFFF, b"\x00\x00")
+
+    def test_keys(self):
+        machine = chip8.Chip8(SHOW_KEY)
+        with self.assertRaises(ValueError):
+            machine.press_key(16)
+        with self.assertRaises(OverflowError):
+            machine.press_key(-1)
+
+    def test_save_state(self):
+        machine = show_seven()
+        state = machine.save_state()
+        self.assertEqual(len(state), chip8.STATE_SIZE)
+
+        machine.reset()
+        self.assertEqual(machine.pc, 0x200)
+        self.assertEqual(machine.read_memory(0x200, len(SHOW_KEY)), SHOW_KEY)
+        with self.assertRaises(ValueError):
+            machine.load_state(state[:10])
+        machine.load_state(state)
+        self.assertEqual(machine.save_state(), state)
+        self.assertEqual(machine.v[5], 7)
+
+    def test_load_rom(self):
+        machine = chip8.Chip8()
+        with self.assertRaises(ValueError):
+            machine.load_rom(bytes(4096))
+        machine.load_rom(SHOW_KEY)
+        machine.set_quirks("schip")
+        machine.set_cycles_per_frame(100)
+        with self.assertRaises(ValueError):
+            machine.set_quirks("nes")
+        machine.run_frames()
+        self.assertEqual(machine.pc, 0x200)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,131 @@
+use quirks::Quirks;
+
+/// The arithmetic and logic instructions, 8XY0 to 8XYE.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AluOp {
+    Load,
+    Or,
+    And,
+    Xor,
+    Add,
+    Sub,
+    ShiftR,
+    SubN,
+    ShiftL,
+}
+
+pub const ALU_OPS: [AluOp; 9] = [AluOp::Load, AluOp::Or, AluOp::And, AluOp::Xor, AluOp::Add,
+                                 AluOp::Sub, AluOp::ShiftR, AluOp::SubN, AluOp::ShiftL];
+
+impl AluOp {
+    /// The operation of an 8XYN opcode, by its last nibble.
+    pub fn from_nibble(nibble: u8) -> Option<AluOp> {
+        match nibble {
+            0x0 => Some(AluOp::Load),
+            0x1 => Some(AluOp::Or),
+            0x2 => Some(AluOp::And),
+            0x3 => Some(AluOp::Xor),
+            0x4 => Some(AluOp::Add),
+            0x5 => Some(AluOp::Sub),
+            0x6 => Some(AluOp::ShiftR),
+            0x7 => Some(AluOp::SubN),
+            0xE => Some(AluOp::ShiftL),
+            _ => None,
+        }
+    }
+
+    pub fn nibble(&self) -> u8 {
+        match *self {
+            AluOp::Load => 0x0,
+            AluOp::Or => 0x1,
+            AluOp::And => 0x2,
+            AluOp::Xor => 0x3,
+            AluOp::Add => 0x4,
+            AluOp::Sub => 0x5,
+            AluOp::ShiftR => 0x6,
+            AluOp::SubN => 0x7,
+            AluOp::ShiftL => 0xE,
+        }
+    }
+}
+
+/// What an operation writes: `value` to VX and then `flag`, if any, to VF.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AluResult {
+    pub value: u8,
+    pub flag: Option<u8>,
+}
+
+/// Compute `op` on the values of VX and VY.
+///
+/// The flags follow the COSMAC VIP: VF is 1 after `Add` on a carry and after
+/// `Sub` and `SubN` when there is no borrow, so equal operands give 1, and
+/// it is 0 otherwise. Shifts put the bit shifted out in VF. With the
+/// `legacy_alu_flags` quirk `Add` only ever sets VF and the subtractions
+/// compare with `>`, as this emulator used to.
+pub fn execute(op: AluOp, x: u8, y: u8, quirks: &Quirks) -> AluResult {
+    let logic_flag = if quirks.vf_reset { Some(0) } else { None };
+    let shifted = if quirks.shift_vy { y } else { x };
+
+    let (value, flag) = match op {
+        AluOp::Load => (y, None),
+        AluOp::Or => (x | y, logic_flag),
+        AluOp::And => (x & y, logic_flag),
+        AluOp::Xor => (x ^ y, logic_flag),
+        AluOp::Add => {
+            let (value, carry) = x.overflowing_add(y);
+            if quirks.legacy_alu_flags && !carry {
+                (value, None)
+            } else {
+                (value, Some(carry as u8))
+            }
+        }
+        AluOp::Sub => (x.wrapping_sub(y), Some(no_borrow(x, y, quirks) as u8)),
+        AluOp::SubN => (y.wrapping_sub(x), Some(no_borrow(y, x, quirks) as u8)),
+        AluOp::ShiftR => (shifted >> 1, Some(shifted & 1)),
+        AluOp::ShiftL => (shifted << 1, Some(shifted >> 7)),
+    };
+
+    AluResult {
+        value: value,
+        flag: flag,
+    }
+}
+
+fn no_borrow(minuend: u8, subtrahend: u8, quirks: &Quirks) -> bool {
+    if quirks.legacy_alu_flags {
+        minuend > subtrahend
+    } else {
+        minuend >= subtrahend
+    }
+}
+
+/// Execute `op` on the registers VX and VY.
+///
+/// The operands are read before anything is written, and VF is written
+/// after VX, so when X is F the register ends up holding the flag. With the
+/// `legacy_alu_flags` quirk the flag of the arithmetic instructions is
+/// written first and the result overwrites it instead. The shifts then read
+/// their operand again, as the old core did, so 8FF6 and 8FFE shift the flag.
+pub fn apply(op: AluOp, registers: &mut [u8; 16], x: usize, y: usize, quirks: &Quirks) {
+    let result = execute(op, registers[x], registers[y], quirks);
+    let flag_first = quirks.legacy_alu_flags &&
+        op != AluOp::Or && op != AluOp::And && op != AluOp::Xor;
+
+    match result.flag {
+        Some(flag) if flag_first => {
+            registers[0xF] = flag;
+            registers[x] = match op {
+                AluOp::ShiftR | AluOp::ShiftL => {
+                    execute(op, registers[x], registers[y], quirks).value
+                }
+                _ => result.value,
+            };
+        }
+        Some(flag) => {
+            registers[x] = result.value;
+            registers[0xF] = flag;
+        }
+        None => registers[x] = result.value,
+    }
+}
@@ -0,0 +1,148 @@
+//! Block translation for long headless runs.
+//!
+//! A block is the run of straight-line instructions starting at an address:
+//! everything up to the first jump, skip, call, draw, memory access, key or
+//! random instruction. Only registers, I and the timers change inside a
+//! block, so each instruction is turned into a closure once and the chain
+//! runs without decoding or dispatching again. The instruction that ends
+//! the block is left to the interpreter.
+
+use alu::{self, AluOp};
+use opcode::{Instruction, OpCode};
+use quirks::Quirks;
+
+/// Most instructions translated into one block.
+pub const MAX_BLOCK_LEN: usize = 64;
+
+/// How the core executes instructions.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Backend {
+    /// Decode and execute one instruction at a time.
+    Interpreter,
+    /// Run straight-line code as translated blocks.
+    Blocks,
+}
+
+impl Backend {
+    pub fn parse(value: &str) -> Result<Backend, String> {
+        match value.to_lowercase().as_str() {
+            "interpreter" => Ok(Backend::Interpreter),
+            "blocks" => Ok(Backend::Blocks),
+            _ => Err(format!("Invalid CPU backend: {}", value)),
+        }
+    }
+}
+
+/// The part of the machine a block can change.
+pub struct BlockCpu<'a> {
+    pub registers: &'a mut [u8; 16],
+    pub index: &'a mut u16,
+    pub delay_timer: &'a mut u8,
+    pub sound_timer: &'a mut u8,
+}
+
+type Step = Box<dyn Fn(&mut BlockCpu)>;
+
+pub struct Block {
+    steps: Vec<Step>,
+    // Bytes of memory read to translate the block, the instruction after
+    // it included
+    size: usize,
+}
+
+impl Block {
+    /// Translate the instructions starting at `start`. The ALU instructions
+    /// are translated with `quirks`, so the block has to be translated again
+    /// when they change.
+    pub fn translate(memory: &[u8], start: usize, quirks: &Quirks) -> Block {
+        let mut steps = Vec::new();
+        let mut addr = start;
+        while steps.len() < MAX_BLOCK_LEN && addr + 1 < memory.len() {
+            let opcode = OpCode::new(&[memory[addr], memory[addr + 1]]);
+            match opcode.to_instruction().ok().and_then(|instruction| step(instruction, quirks)) {
+                Some(step) => steps.push(step),
+                None => break,
+            }
+            addr += 2;
+        }
+
+        Block {
+            steps: steps,
+            size: (addr + 2).min(memory.len()) - start,
+        }
+    }
+
+    /// The number of instructions in the block.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Whether translating the block at `start` read any of the bytes from
+    /// `first` up to `end`.
+    pub fn overlaps(&self, start: usize, first: usize, end: usize) -> bool {
+        start < end && first < start + self.size
+    }
+
+    /// Run the first `count` instructions of the block.
+    pub fn run(&self, cpu: &mut BlockCpu, count: usize) {
+        for step in self.steps[..count].iter() {
+            step(cpu);
+        }
+    }
+}
+
+/// The closure executing a straight-line instruction, `None` for the ones
+/// that end a block.
+fn step(instruction: Instruction, quirks: &Quirks) -> Option<Step> {
+    let alu_op = |op: AluOp, x: u8, y: u8| -> Option<Step> {
+        let quirks = *quirks;
+        let (x, y) = (x as usize, y as usize);
+        Some(Box::new(move |cpu: &mut BlockCpu| alu::apply(op, cpu.registers, x, y, &quirks)))
+    };
+
+    match instruction {
+        Instruction::SYS(_) => Some(Box::new(|_: &mut BlockCpu| ())),
+        Instruction::LoadI(x, byte) => {
+            Some(Box::new(move |cpu: &mut BlockCpu| cpu.registers[x as usize] = byte))
+        }
+        Instruction::AddI(x, byte) => {
+            Some(Box::new(move |cpu: &mut BlockCpu| {
+                cpu.registers[x as usize] = cpu.registers[x as usize].wrapping_add(byte);
+            }))
+        }
+        Instruction::LoadR(x, y) => alu_op(AluOp::Load, x, y),
+        Instruction::Or(x, y) => alu_op(AluOp::Or, x, y),
+        Instruction::And(x, y) => alu_op(AluOp::And, x, y),
+        Instruction::Xor(x, y) => alu_op(AluOp::Xor, x, y),
+        Instruction::Add(x, y) => alu_op(AluOp::Add, x, y),
+        Instruction::Sub(x, y) => alu_op(AluOp::Sub, x, y),
+        Instruction::ShiftR(x, y) => alu_op(AluOp::ShiftR, x, y),
+        Instruction::SubN(x, y) => alu_op(AluOp::SubN, x, y),
+        Instruction::ShiftL(x, y) => alu_op(AluOp::ShiftL, x, y),
+        Instruction::LoadIdx(addr) => Some(Box::new(move |cpu: &mut BlockCpu| *cpu.index = addr)),
+        Instruction::AddIdx(x) => {
+            Some(Box::new(move |cpu: &mut BlockCpu| {
+                *cpu.index = cpu.index.wrapping_add(cpu.registers[x as usize] as u16);
+            }))
+        }
+        Instruction::LoadSprite(x) => {
+            Some(Box::new(move |cpu: &mut BlockCpu| {
+                *cpu.index = 5 * cpu.registers[x as usize] as u16;
+            }))
+        }
+        Instruction::LoadFromDT(x) => {
+            Some(Box::new(move |cpu: &mut BlockCpu| cpu.registers[x as usize] = *cpu.delay_timer))
+        }
+        Instruction::SetDT(x) => {
+            Some(Box::new(move |cpu: &mut BlockCpu| *cpu.delay_timer = cpu.registers[x as usize]))
+        }
+        Instruction::SetST(x) => {
+            Some(Box::new(move |cpu: &mut BlockCpu| *cpu.sound_timer = cpu.registers[x as usize]))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,571 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use sha1;
+use toml;
+
+use blocks::Backend;
+use core::DEFAULT_CYCLES_PER_FRAME;
+use quirks::{Quirks, PRESET_NAMES};
+use romdb::{Platform, RomDatabase, RomInfo};
+
+pub const DEFAULT_DEADZONE: u16 = 8000;
+
+/// Settings read from the TOML configuration file.
+///
+/// Besides the global sections the file may contain a `[roms.<sha1>]` table
+/// per ROM, keyed by the SHA-1 of the ROM file. Its contents are laid out
+/// like the global sections and override them when that ROM is loaded:
+///
+/// ```toml
+/// [quirks]
+/// preset = "chip8"
+///
+/// [roms.0123456789abcdef0123456789abcdef01234567]
+/// name = "Some game"
+/// cpu.speed = 30
+/// quirks.preset = "schip"
+/// ```
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Config {
+    pub cpu: CpuConfig,
+    pub quirks: QuirksConfig,
+    pub display: DisplayConfig,
+    /// Chip-8 key (`0`-`F`) to SDL key name, e.g. `"5" = "Space"`.
+    pub keymap: HashMap<String, String>,
+    pub gamepad: GamepadConfig,
+    pub audio: AudioConfig,
+    pub logging: LoggingConfig,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/chip8/config.toml`, falling back to
+    /// `~/.config/chip8/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("config.toml"))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P,
+                                rom_id: Option<&str>,
+                                recommended: Option<toml::Value>) -> Result<Config, String> {
+        let path = path.as_ref();
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|e| format!("Unable to read config {}: {}", path.display(), e))?;
+
+        Config::parse(&contents, rom_id, recommended)
+    }
+
+    /// Parse a config, layering its global settings over the `recommended`
+    /// settings for the ROM and its `[roms.<rom_id>]` section over both.
+    ///
+    /// `recommended` is laid out like the config file, see
+    /// `RomInfo::recommended_config`.
+    pub fn parse(contents: &str,
+                 rom_id: Option<&str>,
+                 recommended: Option<toml::Value>) -> Result<Config, String> {
+        let mut user: toml::Value = contents.parse()
+            .map_err(|e| format!("Invalid config: {}", e))?;
+
+        let rom_section = match user {
+            toml::Value::Table(ref mut table) => {
+                let roms = table.remove("roms");
+                match (roms, rom_id) {
+                    (Some(toml::Value::Table(roms)), Some(id)) => {
+                        roms.into_iter()
+                            .find(|(key, _)| key.eq_ignore_ascii_case(id))
+                            .map(|(_, section)| section)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        let mut value = recommended.unwrap_or_else(|| toml::Value::Table(Default::default()));
+        merge(&mut value, user);
+
+        if let Some(section) = rom_section {
+            if let Some(name) = section.get("name").and_then(|name| name.as_str()) {
+                info!("Using config profile for {}", name);
+            }
+            merge(&mut value, section);
+        }
+
+        value.try_into().map_err(|e| format!("Invalid config: {}", e))
+    }
+}
+
+/// Where the settings of a ROM come from, kept so that ROMs loaded later are
+/// configured the same way as the first one.
+pub struct SettingsSource {
+    romdb: RomDatabase,
+    path: Option<PathBuf>,
+    autodetect: bool,
+    overrides: Box<dyn Fn(&mut Config)>,
+}
+
+/// The settings of a ROM, see `SettingsSource::resolve`.
+pub struct RomSettings {
+    pub rom_id: String,
+    pub info: RomInfo,
+    pub config: Config,
+}
+
+impl SettingsSource {
+    /// `path` is the config file, `None` for the defaults. `overrides` runs
+    /// last, to apply the command line flags. Without `autodetect` the ROM
+    /// database only gives the window title.
+    pub fn new(romdb: RomDatabase,
+               path: Option<PathBuf>,
+               autodetect: bool,
+               overrides: Box<dyn Fn(&mut Config)>) -> SettingsSource {
+        SettingsSource {
+            romdb: romdb,
+            path: path,
+            autodetect: autodetect,
+            overrides: overrides,
+        }
+    }
+
+    /// Settings for `program`: the ROM's recommended settings, the config
+    /// file, the ROM's section in the config file and then the overrides,
+    /// each overriding the ones before.
+    pub fn resolve(&self, program: &[u8]) -> Result<RomSettings, String> {
+        let rom_id = rom_id(program);
+        let info = RomInfo::identify(&self.romdb, &rom_id, program);
+        let recommended = if self.autodetect { Some(info.recommended_config()) } else { None };
+
+        let mut config = match self.path {
+            Some(ref path) => Config::load(path, Some(&rom_id), recommended),
+            None => Config::parse("", Some(&rom_id), recommended),
+        }?;
+        (self.overrides)(&mut config);
+
+        Ok(RomSettings {
+            rom_id: rom_id,
+            info: info,
+            config: config,
+        })
+    }
+}
+
+impl RomSettings {
+    /// Log what is known about the ROM.
+    pub fn log(&self) {
+        info!("ROM sha1: {}", self.rom_id);
+        match self.info.entry {
+            Some(ref entry) => {
+                info!("ROM: {} by {} ({})", entry.title,
+                      entry.author.as_deref().unwrap_or("unknown"), entry.platform.name());
+                if let Some(ref keys) = entry.keys {
+                    info!("Keys: {}", keys);
+                }
+            }
+            None => info!("Unknown ROM, detected platform {}", self.info.platform.name()),
+        }
+        if self.info.platform != Platform::Chip8 {
+            warn!("ROM targets {}, only CHIP-8 instructions are emulated",
+                  self.info.platform.name());
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/chip8`, falling back to `~/.config/chip8`.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(ref dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(base.join("chip8"))
+}
+
+/// `$XDG_DATA_HOME/chip8`, falling back to `~/.local/share/chip8`. Save
+/// states are kept here.
+pub fn data_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_DATA_HOME") {
+        Some(ref dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local").join("share"),
+    };
+
+    Some(base.join("chip8"))
+}
+
+/// Recursively merge `overlay` into `base`, with values from `overlay` winning.
+fn merge(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (&mut toml::Value::Table(ref mut base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                if let Some(existing) = base.get_mut(&key) {
+                    merge(existing, value);
+                    continue;
+                }
+                base.insert(key, value);
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Identifier used for `[roms.<id>]` sections: the lowercase hex SHA-1 of the
+/// ROM, as printed by `sha1sum`.
+pub fn rom_id(program: &[u8]) -> String {
+    sha1::Sha1::from(program).digest().to_string()
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct CpuConfig {
+    /// Instructions executed per 60 Hz frame.
+    pub speed: usize,
+    /// Cache decoded instructions.
+    pub decode_cache: bool,
+    /// `interpreter` or `blocks`.
+    pub backend: String,
+}
+
+impl Default for CpuConfig {
+    fn default() -> CpuConfig {
+        CpuConfig {
+            speed: DEFAULT_CYCLES_PER_FRAME,
+            decode_cache: false,
+            backend: "interpreter".to_string(),
+        }
+    }
+}
+
+impl CpuConfig {
+    pub fn backend(&self) -> Result<Backend, String> {
+        Backend::parse(&self.backend)
+    }
+}
+
+/// The `[quirks]` section: an optional preset with individual quirks
+/// overriding it.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct QuirksConfig {
+    pub preset: Option<String>,
+    pub shift_vy: Option<bool>,
+    pub load_store_increment: Option<bool>,
+    pub jump_vx: Option<bool>,
+    pub vf_reset: Option<bool>,
+    pub display_wait: Option<bool>,
+    pub clip_sprites: Option<bool>,
+    pub legacy_alu_flags: Option<bool>,
+    pub key_on_press: Option<bool>,
+}
+
+impl QuirksConfig {
+    pub fn quirks(&self) -> Result<Quirks, String> {
+        let mut quirks = match self.preset {
+            Some(ref name) => Quirks::preset(name)
+                .ok_or_else(|| format!("Unknown quirks preset: {} (expected one of {})",
+                                       name, PRESET_NAMES.join(", ")))?,
+            None => Quirks::default(),
+        };
+
+        quirks.shift_vy = self.shift_vy.unwrap_or(quirks.shift_vy);
+        quirks.load_store_increment = self.load_store_increment.unwrap_or(quirks.load_store_increment);
+        quirks.jump_vx = self.jump_vx.unwrap_or(quirks.jump_vx);
+        quirks.vf_reset = self.vf_reset.unwrap_or(quirks.vf_reset);
+        quirks.display_wait = self.display_wait.unwrap_or(quirks.display_wait);
+        quirks.clip_sprites = self.clip_sprites.unwrap_or(quirks.clip_sprites);
+        quirks.legacy_alu_flags = self.legacy_alu_flags.unwrap_or(quirks.legacy_alu_flags);
+        quirks.key_on_press = self.key_on_press.unwrap_or(quirks.key_on_press);
+
+        Ok(quirks)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct GamepadConfig {
+    pub enabled: bool,
+    /// Name of a built-in profile, see `GamepadProfile::builtin`.
+    pub profile: Option<String>,
+    /// Custom mapping, see `GamepadProfile::parse`.
+    pub mapping: Option<String>,
+    pub deadzone: u16,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> GamepadConfig {
+        GamepadConfig {
+            enabled: true,
+            profile: None,
+            mapping: None,
+            deadzone: DEFAULT_DEADZONE,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct AudioConfig {
+    pub enabled: bool,
+    /// Tone pitch in Hz.
+    pub frequency: f32,
+    /// Volume from 0.0 to 1.0.
+    pub volume: f32,
+}
+
+impl Default for AudioConfig {
+    fn default() -> AudioConfig {
+        AudioConfig {
+            enabled: true,
+            frequency: 440.0,
+            volume: 0.25,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// One of `off`, `error`, `warn`, `info`, `debug` or `trace`.
+    pub level: String,
+    /// Also write the log to this file.
+    pub file: Option<String>,
+    /// A log4rs YAML file to use instead of the settings above.
+    pub log4rs: Option<String>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> LoggingConfig {
+        LoggingConfig {
+            level: "warn".to_string(),
+            file: None,
+            log4rs: None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Parse a colour written as `#RRGGBB` or `RRGGBB`.
+    pub fn parse(value: &str) -> Result<Rgb, String> {
+        let hex = value.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(format!("Invalid colour: {}", value));
+        }
+
+        let channel = |idx: usize| {
+            u8::from_str_radix(&hex[idx..(idx + 2)], 16)
+                .map_err(|_| format!("Invalid colour: {}", value))
+        };
+
+        Ok(Rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub foreground: Rgb,
+    pub background: Rgb,
+}
+
+pub const PALETTE_NAMES: [&str; 7] = ["classic", "inverted", "green", "amber", "lcd", "octo", "cyan"];
+
+impl Palette {
+    pub fn preset(name: &str) -> Option<Palette> {
+        let palette = |fg, bg| Some(Palette { foreground: fg, background: bg });
+
+        match name.to_lowercase().as_str() {
+            "classic" => palette(Rgb(0x00, 0x00, 0x00), Rgb(0xFF, 0xFF, 0xFF)),
+            "inverted" => palette(Rgb(0xFF, 0xFF, 0xFF), Rgb(0x00, 0x00, 0x00)),
+            "green" => palette(Rgb(0x33, 0xFF, 0x66), Rgb(0x0A, 0x14, 0x0A)),
+            "amber" => palette(Rgb(0xFF, 0xB0, 0x00), Rgb(0x1A, 0x10, 0x00)),
+            "lcd" => palette(Rgb(0x0F, 0x38, 0x0F), Rgb(0x9B, 0xBC, 0x0F)),
+            "octo" => palette(Rgb(0xFF, 0xCC, 0x00), Rgb(0x99, 0x66, 0x00)),
+            "cyan" => palette(Rgb(0x00, 0xE0, 0xFF), Rgb(0x00, 0x20, 0x30)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ScaleMode {
+    /// Largest whole-number multiple of 64x32 that fits the window.
+    Integer,
+    /// Largest 2:1 rectangle that fits the window.
+    Aspect,
+    /// Fill the whole window.
+    Stretch,
+}
+
+impl ScaleMode {
+    pub fn parse(value: &str) -> Result<ScaleMode, String> {
+        match value.to_lowercase().as_str() {
+            "integer" => Ok(ScaleMode::Integer),
+            "aspect" => Ok(ScaleMode::Aspect),
+            "stretch" => Ok(ScaleMode::Stretch),
+            _ => Err(format!("Invalid scaling mode: {}", value)),
+        }
+    }
+}
+
+/// How previous frames bleed into the current one to hide sprite flicker.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Persistence {
+    /// Show only the current framebuffer.
+    Off,
+    /// Pixels fade out, keeping `decay` of their brightness every 1/60 s.
+    Phosphor { decay: f32 },
+    /// A pixel stays lit while it was set in any of the last `frames` frames.
+    Or { frames: u32 },
+}
+
+impl Persistence {
+    pub fn parse(mode: &str, decay: f32, frames: u32) -> Result<Persistence, String> {
+        match mode.to_lowercase().as_str() {
+            "off" => Ok(Persistence::Off),
+            "phosphor" => {
+                if !(0.0..1.0).contains(&decay) {
+                    return Err(format!("Phosphor decay must be in [0, 1): {}", decay));
+                }
+                Ok(Persistence::Phosphor { decay: decay })
+            }
+            "or" => Ok(Persistence::Or { frames: frames.max(1) }),
+            _ => Err(format!("Invalid persistence mode: {}", mode)),
+        }
+    }
+}
+
+/// The `[display]` section of the config file.
+///
+/// Values are kept as written so that command line flags can override them
+/// before `palette` and `scale_mode` validate the result.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct DisplayConfig {
+    pub palette: String,
+    pub foreground: Option<String>,
+    pub background: Option<String>,
+    pub scale: u32,
+    pub scaling: String,
+    pub resizable: bool,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    pub grid: bool,
+    pub scanlines: bool,
+    pub persistence: String,
+    pub decay: f32,
+    pub persistence_frames: u32,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> DisplayConfig {
+        DisplayConfig {
+            palette: "classic".to_string(),
+            foreground: None,
+            background: None,
+            scale: 10,
+            scaling: "aspect".to_string(),
+            resizable: false,
+            fullscreen: false,
+            vsync: true,
+            grid: false,
+            scanlines: false,
+            persistence: "off".to_string(),
+            decay: 0.5,
+            persistence_frames: 2,
+        }
+    }
+}
+
+impl DisplayConfig {
+    /// The preset palette with any explicit colours applied on top.
+    pub fn palette(&self) -> Result<Palette, String> {
+        let mut palette = Palette::preset(&self.palette)
+            .ok_or_else(|| format!("Unknown palette: {} (expected one of {})",
+                                   self.palette, PALETTE_NAMES.join(", ")))?;
+
+        if let Some(ref fg) = self.foreground {
+            palette.foreground = Rgb::parse(fg)?;
+        }
+
+        if let Some(ref bg) = self.background {
+            palette.background = Rgb::parse(bg)?;
+        }
+
+        Ok(palette)
+    }
+
+    pub fn scale_mode(&self) -> Result<ScaleMode, String> {
+        ScaleMode::parse(&self.scaling)
+    }
+
+    pub fn persistence(&self) -> Result<Persistence, String> {
+        Persistence::parse(&self.persistence, self.decay, self.persistence_frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    fn recommended() -> Option<toml::Value> {
+        Some("quirks.preset = \"chip8\"\ncpu.speed = 20".parse().unwrap())
+    }
+
+    #[test]
+    fn recommended_settings_apply_without_user_settings() {
+        let config = Config::parse("", Some(ROM), recommended()).unwrap();
+        assert_eq!(config.quirks.preset, Some("chip8".to_string()));
+        assert_eq!(config.cpu.speed, 20);
+    }
+
+    #[test]
+    fn user_settings_override_recommended_settings() {
+        let contents = "[quirks]\npreset = \"schip\"\n[cpu]\nspeed = 30\n";
+        let config = Config::parse(contents, Some(ROM), recommended()).unwrap();
+        assert_eq!(config.quirks.preset, Some("schip".to_string()));
+        assert_eq!(config.cpu.speed, 30);
+    }
+
+    #[test]
+    fn rom_section_overrides_user_settings() {
+        let contents = format!("[quirks]\npreset = \"schip\"\n\n\
+                                [roms.{}]\nquirks.preset = \"xochip\"\n",
+                               ROM.to_uppercase());
+        let config = Config::parse(&contents, Some(ROM), recommended()).unwrap();
+        assert_eq!(config.quirks.preset, Some("xochip".to_string()));
+        assert_eq!(config.cpu.speed, 20);
+
+        let config = Config::parse(&contents, Some("other"), recommended()).unwrap();
+        assert_eq!(config.quirks.preset, Some("schip".to_string()));
+    }
+
+    #[test]
+    fn settings_source_layers_overrides_last() {
+        // PLANE 1, an XO-CHIP instruction
+        let program = [0xF1, 0x01];
+        let overrides = Box::new(|config: &mut Config| config.cpu.speed = 50);
+        let source = SettingsSource::new(RomDatabase::default(), None, true, overrides);
+        let settings = source.resolve(&program).unwrap();
+        assert_eq!(settings.rom_id, rom_id(&program));
+        assert_eq!(settings.config.quirks.preset, Some("xochip".to_string()));
+        assert_eq!(settings.config.cpu.speed, 50);
+
+        let source = SettingsSource::new(RomDatabase::default(), None, false, Box::new(|_| {}));
+        assert_eq!(source.resolve(&program).unwrap().config.quirks.preset, None);
+    }
+
+    #[test]
+    fn negative_deadzone_is_rejected() {
+        assert!(Config::parse("[gamepad]\ndeadzone = -32768\n", None, None).is_err());
+        let config = Config::parse("[gamepad]\ndeadzone = 32767\n", None, None).unwrap();
+        assert_eq!(config.gamepad.deadzone, 32767);
+    }
+}
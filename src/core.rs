@@ -1,10 +1,39 @@
+#[cfg(feature = "std")]
+use std::cmp;
+use std::fmt;
+use std::ops::Range;
+
+#[cfg(feature = "rand")]
 use rand;
-use std::{thread, time};
-use std::time::Duration;
-use std::sync::{Mutex, Arc};
 
+use alu::{self, AluOp};
+#[cfg(feature = "std")]
+use blocks::{Backend, Block, BlockCpu, MAX_BLOCK_LEN};
+use error::Error;
 use opcode::{OpCode, Instruction, Register};
-use peripherals::{Chip8Disp, Chip8Input, PixelData, Chip8Key};
+use peripherals::{Chip8Disp, Chip8Input, Chip8Key, Framebuffer, KeyEvent, Region};
+use quirks::Quirks;
+#[cfg(feature = "std")]
+use timing::FramePacer;
+
+/// Instructions executed per 60 Hz frame by default, roughly 500 Hz.
+pub const DEFAULT_CYCLES_PER_FRAME: usize = 9;
+
+const STATE_MAGIC: &[u8; 4] = b"C8ST";
+const STATE_VERSION: u8 = 1;
+
+/// Size of a state written by `Chip8::save_state`.
+pub const STATE_SIZE: usize = 4 + 1 + 4096 + 16 + 2 + 2 + 16 * 2 + 1 + 1 + 1;
+
+/// Size of a screen written by `Chip8::save_frame`.
+pub const FRAME_BYTES: usize = 64 * 32 / 8;
+
+/// Longest program `Chip8::upload_rom` accepts, from 0x200 to the end of
+/// memory.
+pub const MAX_PROGRAM_SIZE: usize = 4096 - 0x200;
+
+/// Most breakpoints set at once.
+pub const MAX_BREAKPOINTS: usize = 16;
 
 fn populate_builtin_sprites(memory: &mut [u8; 4096]) {
     memory[..(5*16)].copy_from_slice(&[
@@ -27,6 +56,115 @@ fn populate_builtin_sprites(memory: &mut [u8; 4096]) {
         ]);
 }
 
+/// Arguments of a `Draw` instruction: the sprite read from memory at
+/// `index` and where it was drawn.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DrawCall {
+    pub index: u16,
+    pub height: u8,
+    pub x: u8,
+    pub y: u8,
+}
+
+/// Something a program did that the machine can't carry out. The machine
+/// halts on a fault until it is reset or a state is loaded.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Fault {
+    /// An opcode that isn't a Chip-8 instruction.
+    InvalidInstruction { pc: u16, opcode: u16 },
+    /// `Call` with all 16 stack entries in use.
+    StackOverflow { pc: u16 },
+    /// `Return` with an empty stack.
+    StackUnderflow { pc: u16 },
+    /// An instruction read or wrote memory past the end through I.
+    MemoryOutOfBounds { pc: u16, index: u16 },
+    /// The program counter left memory.
+    PcOutOfBounds { pc: u16 },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Fault::InvalidInstruction { pc, opcode } => {
+                write!(f, "Invalid instruction {:04X} at {:03X}", opcode, pc)
+            }
+            Fault::StackOverflow { pc } => write!(f, "Stack overflow at {:03X}", pc),
+            Fault::StackUnderflow { pc } => write!(f, "Return with an empty stack at {:03X}", pc),
+            Fault::MemoryOutOfBounds { pc, index } => {
+                write!(f, "Memory access past the end through I = {:04X} at {:03X}", index, pc)
+            }
+            Fault::PcOutOfBounds { pc } => write!(f, "Program counter out of memory at {:04X}", pc),
+        }
+    }
+}
+
+/// How far a `LoadKey` instruction got waiting for a key.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum KeyWait {
+    /// Waiting for a key to go down. Keys already held don't count.
+    Press,
+    /// Waiting for the key that went down to go up again.
+    Release(Chip8Key),
+    /// The instruction stores the key the next time it executes.
+    Done(Chip8Key),
+}
+
+impl KeyWait {
+    fn next(self, event: KeyEvent, on_press: bool) -> KeyWait {
+        match (self, event) {
+            (KeyWait::Press, KeyEvent::Pressed(key)) if on_press => KeyWait::Done(key),
+            (KeyWait::Press, KeyEvent::Pressed(key)) => KeyWait::Release(key),
+            (KeyWait::Release(waiting), KeyEvent::Released(key)) if key == waiting => {
+                KeyWait::Done(key)
+            }
+            (wait, _) => wait,
+        }
+    }
+}
+
+/// Marsaglia's xorshift128, the generator behind `Rand`.
+#[derive(Copy, Clone, Debug)]
+struct XorShift {
+    x: u32,
+    y: u32,
+    z: u32,
+    w: u32,
+}
+
+impl XorShift {
+    fn new(seed: u32) -> XorShift {
+        // Xorshift needs a seed that isn't all zeros
+        XorShift {
+            x: seed,
+            y: 0x9E37_79B9,
+            z: 0x7F4A_7C15,
+            w: 0x2545_F491,
+        }
+    }
+
+    fn next_u8(&mut self) -> u8 {
+        let t = self.x ^ (self.x << 11);
+        self.x = self.y;
+        self.y = self.z;
+        self.z = self.w;
+        self.w = self.w ^ (self.w >> 19) ^ (t ^ (t >> 8));
+        self.w as u8
+    }
+}
+
+/// A different seed every run.
+#[cfg(feature = "rand")]
+fn initial_seed() -> u32 {
+    rand::random()
+}
+
+/// Without `rand` there is no entropy to seed from, e.g. without std or in a
+/// browser, and the host calls `seed_rng`.
+#[cfg(not(feature = "rand"))]
+fn initial_seed() -> u32 {
+    0
+}
+
 #[derive(Debug)]
 struct Registers {
     registers: [u8; 16],
@@ -61,8 +199,45 @@ pub struct Chip8<T: Chip8Disp, U: Chip8Input>  {
     stack_ptr: u8,
 
     // Timers
-    delay_timer: Arc<Mutex<u8>>,
-    sound_timer: Arc<Mutex<u8>>,
+    delay_timer: u8,
+    sound_timer: u8,
+
+    framebuffer: Framebuffer,
+    // The part of the framebuffer changed since it was last presented
+    dirty: Option<Region>,
+
+    quirks: Quirks,
+    cycles_per_frame: usize,
+    rng: XorShift,
+    // Set at the start of every frame, cleared by the first instruction
+    vblank: bool,
+    // Set when the current frame should stop executing instructions
+    stalled: bool,
+    // Execution stopped, until a reset or a state is loaded
+    fault: Option<Fault>,
+    // A LoadKey instruction is waiting for a key
+    key_wait: Option<KeyWait>,
+    // The instruction decoded at each address, when caching is enabled
+    #[cfg(feature = "std")]
+    decode_cache: Option<Vec<Option<Instruction>>>,
+    #[cfg(feature = "std")]
+    backend: Backend,
+    // The block translated at each address, empty for the interpreter
+    #[cfg(feature = "std")]
+    blocks: Vec<Option<Block>>,
+    // Instructions executed since the last reset
+    executed: u64,
+
+    // Debugging
+    breakpoints: [u16; MAX_BREAKPOINTS],
+    breakpoint_count: usize,
+    // The last frame stopped at a breakpoint
+    breakpoint_hit: bool,
+    // Breakpoint ignored until the pc moves elsewhere, so execution can
+    // resume from it
+    skip_breakpoint: Option<u16>,
+    last_draw: Option<DrawCall>,
+    log_draws: bool,
 
     // Peripherals
     screen: Option<T>,
@@ -75,31 +250,6 @@ impl<T, U> Chip8<T, U>
         let mut memory = [0; 4096];
         populate_builtin_sprites(&mut memory);
 
-        let delay_timer = Arc::new(Mutex::new(0));
-        let sound_timer = Arc::new(Mutex::new(0));
-
-        let delay_clone = Arc::clone(&delay_timer);
-        let sound_clone = Arc::clone(&sound_timer);
-
-        thread::spawn(move || {
-            let rate = Duration::from_millis(16);
-            loop {
-                // Delay here
-                thread::sleep(rate);
-
-                let mut delay = delay_clone.lock().unwrap();
-                let mut sound = sound_clone.lock().unwrap();
-
-                if *delay > 0 {
-                    *delay -= 1;
-                }
-
-                if *sound > 0 {
-                    *sound -= 1;
-                }
-            }
-        });
-
         Chip8 {
             memory: memory,
             registers: Registers::new(),
@@ -108,8 +258,33 @@ impl<T, U> Chip8<T, U>
             stack: [0; 16],
             stack_ptr: 0,
 
-            delay_timer: delay_timer,
-            sound_timer: sound_timer,
+            delay_timer: 0,
+            sound_timer: 0,
+
+            framebuffer: Framebuffer::new(),
+            dirty: Some(Region::full()),
+
+            quirks: Quirks::default(),
+            cycles_per_frame: DEFAULT_CYCLES_PER_FRAME,
+            rng: XorShift::new(initial_seed()),
+            vblank: false,
+            stalled: false,
+            fault: None,
+            key_wait: None,
+            #[cfg(feature = "std")]
+            decode_cache: None,
+            #[cfg(feature = "std")]
+            backend: Backend::Interpreter,
+            #[cfg(feature = "std")]
+            blocks: Vec::new(),
+            executed: 0,
+
+            breakpoints: [0; MAX_BREAKPOINTS],
+            breakpoint_count: 0,
+            breakpoint_hit: false,
+            skip_breakpoint: None,
+            last_draw: None,
+            log_draws: false,
 
             screen: None,
             keyboard: None,
@@ -124,86 +299,617 @@ impl<T, U> Chip8<T, U>
         self.keyboard = Some(keyboard);
     }
 
-    pub fn upload_rom(&mut self, program: &[u8]) -> Result<(), String> {
-        if program.len() > (4096 - 0x200) {
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+        // Blocks are translated for the quirks they run with
+        self.invalidate(0..4096);
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_cycles_per_frame(&mut self, cycles: usize) {
+        self.cycles_per_frame = cycles;
+    }
+
+    pub fn cycles_per_frame(&self) -> usize {
+        self.cycles_per_frame
+    }
+
+    /// Remember every instruction decoded, by address, so it isn't decoded
+    /// again the next time it runs. Instructions are decoded again once the
+    /// program writes over them.
+    #[cfg(feature = "std")]
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decode_cache = if enabled { Some(vec![None; 4096]) } else { None };
+    }
+
+    #[cfg(feature = "std")]
+    pub fn decode_cache(&self) -> bool {
+        self.decode_cache.is_some()
+    }
+
+    /// Choose how instructions are executed. With `Backend::Blocks`
+    /// straight-line code is translated into blocks the first time it runs,
+    /// see `blocks`, and translated again once the program writes over it.
+    #[cfg(feature = "std")]
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+        self.blocks = match backend {
+            Backend::Interpreter => Vec::new(),
+            Backend::Blocks => (0..4096).map(|_| None).collect(),
+        };
+    }
+
+    #[cfg(feature = "std")]
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Instructions executed since the last reset, faulting ones excluded.
+    pub fn instructions(&self) -> u64 {
+        self.executed
+    }
+
+    /// Make `Rand` repeatable: the same seed always gives the same numbers.
+    /// Without std every machine starts with the same seed, so the host
+    /// should seed it from its own source of randomness.
+    pub fn seed_rng(&mut self, seed: u32) {
+        self.rng = XorShift::new(seed);
+    }
+
+    pub fn display(&self) -> Option<&T> {
+        self.screen.as_ref()
+    }
+
+    pub fn keyboard(&self) -> Option<&U> {
+        self.keyboard.as_ref()
+    }
+
+    pub fn display_mut(&mut self) -> Option<&mut T> {
+        self.screen.as_mut()
+    }
+
+    pub fn keyboard_mut(&mut self) -> Option<&mut U> {
+        self.keyboard.as_mut()
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn registers(&self) -> [u8; 16] {
+        self.registers.registers
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    pub fn stack_ptr(&self) -> u8 {
+        self.stack_ptr
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// Overwrite V0 to VF, for debuggers and scripts poking at a program.
+    pub fn set_registers(&mut self, registers: [u8; 16]) {
+        self.registers.registers = registers;
+    }
+
+    /// Jump to `pc`. An address outside memory faults on the next step.
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    pub fn set_index(&mut self, index: u16) {
+        self.index = index;
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    /// Replace the screen contents.
+    pub fn set_framebuffer(&mut self, frame: &Framebuffer) {
+        self.framebuffer = *frame;
+        self.mark_dirty(Region::full());
+    }
+
+    /// The screen contents packed into `FRAME_BYTES` bytes, one bit per
+    /// pixel with the leftmost pixel of each byte in the high bit.
+    #[cfg(feature = "std")]
+    pub fn save_frame(&self) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(FRAME_BYTES);
+        for y in 0..32 {
+            let row = self.framebuffer.row(y);
+            frame.extend((0..8).rev().map(|byte| (row >> (8 * byte)) as u8));
+        }
+        frame
+    }
+
+    /// Restore screen contents written by `save_frame`.
+    pub fn load_frame(&mut self, frame: &[u8]) -> Result<(), Error> {
+        if frame.len() != FRAME_BYTES {
+            return Err(Error::FrameSize(frame.len()));
+        }
+
+        for (y, bytes) in frame.chunks(8).enumerate() {
+            let row = bytes.iter().fold(0, |row, &byte| (row << 8) | byte as u64);
+            self.framebuffer.set_row(y, row);
+        }
+        self.mark_dirty(Region::full());
+
+        Ok(())
+    }
+
+    fn mark_dirty(&mut self, region: Region) {
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(&region),
+            None => region,
+        });
+    }
+
+    /// The most recent `Draw` instruction.
+    pub fn last_draw(&self) -> Option<DrawCall> {
+        self.last_draw
+    }
+
+    /// Log every `Draw` instruction with its sprite address, height and
+    /// position, at info level.
+    pub fn set_log_draws(&mut self, log_draws: bool) {
+        self.log_draws = log_draws;
+    }
+
+    pub fn breakpoints(&self) -> &[u16] {
+        &self.breakpoints[..self.breakpoint_count]
+    }
+
+    /// Add or remove a breakpoint. `run_frame` stops before executing the
+    /// instruction at a breakpoint. Up to `MAX_BREAKPOINTS` can be set,
+    /// more are ignored.
+    pub fn set_breakpoint(&mut self, addr: u16, enabled: bool) {
+        let count = self.breakpoint_count;
+        if let Some(pos) = self.breakpoints().iter().position(|&bp| bp == addr) {
+            self.breakpoints.copy_within((pos + 1)..count, pos);
+            self.breakpoint_count -= 1;
+        }
+
+        if enabled {
+            if self.breakpoint_count == MAX_BREAKPOINTS {
+                warn!("Only {} breakpoints can be set", MAX_BREAKPOINTS);
+                return;
+            }
+            self.breakpoints[self.breakpoint_count] = addr;
+            self.breakpoint_count += 1;
+        }
+    }
+
+    /// Whether the last frame stopped at a breakpoint. The next frame
+    /// resumes with the instruction at the breakpoint.
+    pub fn breakpoint_hit(&self) -> bool {
+        self.breakpoint_hit
+    }
+
+    /// The fault that halted the machine, if any.
+    pub fn fault(&self) -> Option<Fault> {
+        self.fault
+    }
+
+    /// Execute a single instruction, ignoring breakpoints. Timers don't
+    /// tick, and with the display wait quirk every step may draw.
+    pub fn step(&mut self) {
+        self.vblank = true;
+        self.cycle_once();
+        self.breakpoint_hit = false;
+        self.skip_breakpoint = Some(self.pc);
+    }
+
+    /// Whether the sound timer is running and a tone should be playing.
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// Return to the power-on state, ready for a new ROM. Peripherals,
+    /// quirks and speed are kept.
+    pub fn reset(&mut self) {
+        self.memory = [0; 4096];
+        populate_builtin_sprites(&mut self.memory);
+        self.registers = Registers::new();
+        self.pc = 0x200;
+        self.index = 0;
+        self.stack = [0; 16];
+        self.stack_ptr = 0;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.vblank = false;
+        self.stalled = false;
+        self.fault = None;
+        self.key_wait = None;
+        self.executed = 0;
+        self.invalidate(0..4096);
+        self.breakpoint_hit = false;
+        self.skip_breakpoint = None;
+        self.last_draw = None;
+        self.framebuffer.clear();
+        self.mark_dirty(Region::full());
+    }
+
+    /// Snapshot of memory, registers, stack and timers, `STATE_SIZE` bytes
+    /// long. The screen is saved separately by `save_frame`, and a
+    /// `LoadKey` instruction waiting for a key starts over once loaded.
+    #[cfg(feature = "std")]
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::with_capacity(STATE_SIZE);
+        state.extend_from_slice(STATE_MAGIC);
+        state.push(STATE_VERSION);
+        state.extend_from_slice(&self.memory);
+        state.extend_from_slice(&self.registers.registers);
+        for value in [self.pc, self.index].iter().chain(self.stack.iter()) {
+            state.push((*value >> 8) as u8);
+            state.push(*value as u8);
+        }
+        state.push(self.stack_ptr);
+        state.push(self.delay_timer);
+        state.push(self.sound_timer);
+
+        state
+    }
+
+    /// Restore a snapshot written by `save_state`.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), Error> {
+        if state.len() != STATE_SIZE || &state[..4] != STATE_MAGIC {
+            return Err(Error::NotAState);
+        }
+        if state[4] != STATE_VERSION {
+            return Err(Error::StateVersion(state[4]));
+        }
+
+        let state = &state[5..];
+        let (memory, state) = state.split_at(4096);
+        let (registers, state) = state.split_at(16);
+        let (words, state) = state.split_at(2 * 18);
+        let word = |idx: usize| ((words[2 * idx] as u16) << 8) | words[2 * idx + 1] as u16;
+        if state[0] as usize > self.stack.len() {
+            return Err(Error::InvalidStackPointer);
+        }
+
+        self.memory.copy_from_slice(memory);
+        self.registers.registers.copy_from_slice(registers);
+        self.pc = word(0);
+        self.index = word(1);
+        for (idx, entry) in self.stack.iter_mut().enumerate() {
+            *entry = word(2 + idx);
+        }
+        self.stack_ptr = state[0];
+        self.delay_timer = state[1];
+        self.sound_timer = state[2];
+        self.vblank = false;
+        self.stalled = false;
+        self.fault = None;
+        self.key_wait = None;
+        self.invalidate(0..4096);
+        self.breakpoint_hit = false;
+        self.skip_breakpoint = None;
+        self.mark_dirty(Region::full());
+
+        Ok(())
+    }
+
+    /// Copy `data` into memory at `addr`.
+    pub fn write_memory(&mut self, addr: u16, data: &[u8]) -> Result<(), Error> {
+        let start = addr as usize;
+        if start + data.len() > self.memory.len() {
+            return Err(Error::WritePastEnd(addr));
+        }
+
+        self.memory[start..(start + data.len())].copy_from_slice(data);
+        self.invalidate(start..(start + data.len()));
+        Ok(())
+    }
+
+    pub fn upload_rom(&mut self, program: &[u8]) -> Result<(), Error> {
+        if program.len() > MAX_PROGRAM_SIZE {
             error!("Invalid program length");
-            return Err("Invalid program length!".to_string());
+            return Err(Error::ProgramTooLong(program.len()));
         }
 
         self.memory[0x200..(0x200 + program.len())].copy_from_slice(program);
+        self.invalidate(0x200..(0x200 + program.len()));
 
         Ok(())
     }
     
-    fn cycle_once(&mut self) -> bool {
-        // Poll keyboard to allow it to update inputs
+    fn cycle_once(&mut self) {
+        if self.fault.is_some() {
+            return;
+        }
+
+        match self.execute() {
+            Ok(()) => self.executed += 1,
+            Err(fault) => self.fault = Some(fault),
+        }
+        self.vblank = false;
+    }
+
+    fn execute(&mut self) -> Result<(), Fault> {
+        // Convert raw assembly at pc into parsed Opcode
+        let pc = self.pc as usize;
+        if pc + 1 >= self.memory.len() {
+            return Err(Fault::PcOutOfBounds { pc: self.pc });
+        }
+        let instruction = self.fetch(pc)?;
+        debug!("pc: {}, instruction: {:?}", self.pc, instruction);
+        self.handle_instruction(instruction)?;
+        debug!("Registers:
+               reg: {:?}, index: {},
+               stack: {:?}, stack_ptr: {},
+               delay: {:?}, sound: {:?}\n", 
+               self.registers, self.index, 
+               self.stack, self.stack_ptr, 
+               self.delay_timer, self.sound_timer);
+
+        Ok(())
+    }
+
+    /// The instruction at `pc`, from the decode cache if it is enabled.
+    #[cfg(feature = "std")]
+    fn fetch(&mut self, pc: usize) -> Result<Instruction, Fault> {
+        if let Some(instruction) = self.decode_cache.as_ref().and_then(|cache| cache[pc]) {
+            return Ok(instruction);
+        }
+
+        let instruction = self.decode(pc)?;
+        if let Some(ref mut cache) = self.decode_cache {
+            cache[pc] = Some(instruction);
+        }
+        Ok(instruction)
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn fetch(&mut self, pc: usize) -> Result<Instruction, Fault> {
+        self.decode(pc)
+    }
+
+    fn decode(&self, pc: usize) -> Result<Instruction, Fault> {
+        let bytes: [u8; 2] = [ self.memory[pc], self.memory[pc + 1] ];
+        let opcode = OpCode::new(&bytes);
+
+        opcode.to_instruction()
+            .map_err(|_| Fault::InvalidInstruction { pc: self.pc, opcode: opcode.raw() })
+    }
+
+    /// Forget the cached instructions and translated blocks that overlap
+    /// the bytes in `range`.
+    #[cfg(feature = "std")]
+    fn invalidate(&mut self, range: Range<usize>) {
+        if let Some(ref mut cache) = self.decode_cache {
+            let start = if range.start > 0 { range.start - 1 } else { 0 };
+            for entry in cache[start..range.end].iter_mut() {
+                *entry = None;
+            }
+        }
+
+        if !self.blocks.is_empty() {
+            // A block reads at most one instruction past its last
+            let start = range.start.saturating_sub(2 * MAX_BLOCK_LEN + 1);
+            for addr in start..range.end {
+                let overlaps = match self.blocks[addr] {
+                    Some(ref block) => block.overlaps(addr, range.start, range.end),
+                    None => false,
+                };
+                if overlaps {
+                    self.blocks[addr] = None;
+                }
+            }
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn invalidate(&mut self, _range: Range<usize>) {}
+
+    /// The `len` bytes of memory starting at I.
+    fn index_range(&self, len: usize) -> Result<Range<usize>, Fault> {
+        let start = self.index as usize;
+        if start + len > self.memory.len() {
+            return Err(Fault::MemoryOutOfBounds { pc: self.pc, index: self.index });
+        }
+
+        Ok(start..(start + len))
+    }
+
+    /// Poll the keyboard so it can update its inputs, and hand its key
+    /// events to a waiting `LoadKey` instruction.
+    ///
+    /// Returns true when the keyboard asked to quit.
+    pub fn poll_input(&mut self) -> bool {
         if let Some(ref mut keyboard) = self.keyboard {
             let quit = keyboard.poll();
             if quit {
                 info!("Keyboard quit");
                 return quit;
             }
+
+            while let Some(event) = keyboard.next_event() {
+                if let Some(wait) = self.key_wait {
+                    self.key_wait = Some(wait.next(event, self.quirks.key_on_press));
+                }
+            }
         }
 
-        // Convert raw assembly at pc into parsed Opcode
-        let bytes: [u8; 2] = [ self.memory[self.pc as usize],
-                               self.memory[(self.pc + 1) as usize] ];
-        let opcode = OpCode::new(&bytes);
+        false
+    }
 
-        // Ignore error instructions for now
-        if let Ok(instruction) = opcode.to_instruction() {
-            debug!("pc: {}, instruction: {:?}", self.pc, instruction);
-            self.handle_instruction(instruction);
-            debug!("Registers:
-                   reg: {:?}, index: {},
-                   stack: {:?}, stack_ptr: {},
-                   delay: {:?}, sound: {:?}\n", 
-                   self.registers, self.index, 
-                   self.stack, self.stack_ptr, 
-                   self.delay_timer, self.sound_timer);
-        } else {
-            warn!("Invalid instruction: {:?}", opcode);
+    /// Emulate one 60 Hz frame: execute the frame's instructions and tick
+    /// the timers. Nothing happens after a fault.
+    pub fn run_frame(&mut self) {
+        if self.fault.is_some() {
+            return;
         }
 
-        false
+        self.vblank = true;
+        self.stalled = false;
+        self.breakpoint_hit = false;
+        let mut cycles = 0;
+        while cycles < self.cycles_per_frame {
+            if self.skip_breakpoint != Some(self.pc) {
+                self.skip_breakpoint = None;
+                if self.breakpoints().contains(&self.pc) {
+                    // The rest of the frame, timers included, is skipped
+                    info!("Breakpoint at {:#05X}", self.pc);
+                    self.breakpoint_hit = true;
+                    self.skip_breakpoint = Some(self.pc);
+                    return;
+                }
+            }
+
+            let ran = self.run_block(self.cycles_per_frame - cycles);
+            if ran > 0 {
+                cycles += ran;
+                continue;
+            }
+
+            self.cycle_once();
+            cycles += 1;
+            if self.stalled || self.fault.is_some() {
+                break;
+            }
+        }
+
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
     }
 
+    /// Run up to `budget` instructions of the block at the pc, stopping
+    /// before any breakpoint, and return how many ran. None run with the
+    /// interpreter or when the instruction at the pc ends a block.
+    #[cfg(feature = "std")]
+    fn run_block(&mut self, budget: usize) -> usize {
+        let pc = self.pc as usize;
+        if self.backend != Backend::Blocks || pc >= self.memory.len() {
+            return 0;
+        }
+        if self.blocks[pc].is_none() {
+            self.blocks[pc] = Some(Block::translate(&self.memory, pc, &self.quirks));
+        }
+
+        let block = match self.blocks[pc] {
+            Some(ref block) => block,
+            None => return 0,
+        };
+        let mut count = cmp::min(block.len(), budget);
+        for &bp in self.breakpoints().iter() {
+            if bp > self.pc && (bp - self.pc).is_multiple_of(2) {
+                count = cmp::min(count, ((bp - self.pc) / 2) as usize);
+            }
+        }
+        if count == 0 {
+            return 0;
+        }
+
+        block.run(&mut BlockCpu {
+            registers: &mut self.registers.registers,
+            index: &mut self.index,
+            delay_timer: &mut self.delay_timer,
+            sound_timer: &mut self.sound_timer,
+        }, count);
+        self.pc += 2 * count as u16;
+        self.executed += count as u64;
+        self.vblank = false;
+        count
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn run_block(&mut self, _budget: usize) -> usize {
+        0
+    }
+
+    /// Present the display if anything changed since it was last presented.
+    pub fn present(&mut self) {
+        if let Some(ref mut screen) = self.screen {
+            if self.dirty.is_some() || screen.animating() {
+                screen.present(&self.framebuffer, self.dirty);
+            }
+        }
+        self.dirty = None;
+    }
+
+    #[cfg(feature = "std")]
     pub fn run(&mut self) {
-        let rate = Duration::from_millis(2); // 1/s
+        let mut pacer = FramePacer::new();
 
         loop {
-            let now = time::Instant::now();
-
-            let quit = self.cycle_once();
+            let quit = self.poll_input();
             if quit {
                 break;
             }
 
-            let elapsed = now.elapsed();
-            if elapsed < rate {
-                debug!("Slept CPU");
-                thread::sleep(rate - elapsed);
+            self.run_frame();
+            if let Some(fault) = self.fault {
+                error!("{}", fault);
+                break;
             }
+            self.present();
+            pacer.wait();
         }
     }
 
-    fn handle_instruction(&mut self, instruction: Instruction) {
+    fn alu(&mut self, op: AluOp, regx: Register, regy: Register) {
+        let (x, y) = (regx as usize, regy as usize);
+        alu::apply(op, &mut self.registers.registers, x, y, &self.quirks);
+        self.pc += 2;
+    }
+
+    fn handle_instruction(&mut self, instruction: Instruction) -> Result<(), Fault> {
         match instruction {
-            Instruction::SYS(_) => return,
+            // Machine code routines can't run here, 0NNN is ignored
+            Instruction::SYS(_) => self.pc += 2,
             Instruction::Clear => {
-                if let Some(ref mut screen) = self.screen {
-                    screen.clear();
-                }
-
+                self.framebuffer.clear();
+                self.mark_dirty(Region::full());
                 self.pc += 2;
             }
             Instruction::Return => {
+                if self.stack_ptr == 0 {
+                    return Err(Fault::StackUnderflow { pc: self.pc });
+                }
                 self.stack_ptr -= 1;
-                self.pc = self.stack[self.stack_ptr as usize] + 2;
+                self.pc = self.stack[self.stack_ptr as usize].wrapping_add(2);
                 self.stack[self.stack_ptr as usize] = 0;
             }
             Instruction::Jump(addr) => self.pc = addr,
             Instruction::Call(addr) => {
+                if self.stack_ptr as usize >= self.stack.len() {
+                    return Err(Fault::StackOverflow { pc: self.pc });
+                }
                 // Set stack to save current location
                 self.stack[self.stack_ptr as usize] = self.pc;
                 self.stack_ptr += 1;
@@ -239,85 +945,15 @@ impl<T, U> Chip8<T, U>
                 self.registers.set(reg, val);
                 self.pc += 2;
             }
-            Instruction::LoadR(regx, regy) => {
-                let val = self.registers.get(regy);
-                self.registers.set(regx, val);
-                self.pc += 2;
-            }
-            Instruction::Or(regx, regy) => {
-                let val = self.registers.get(regx) | self.registers.get(regy);
-                self.registers.set(regx, val);
-                self.pc += 2;
-            }
-            Instruction::And(regx, regy) => {
-                let val = self.registers.get(regx) & self.registers.get(regy);
-                self.registers.set(regx, val);
-                self.pc += 2;
-            }
-            Instruction::Xor(regx, regy) => {
-                let val = self.registers.get(regx) ^ self.registers.get(regy);
-                self.registers.set(regx, val);
-                self.pc += 2;
-            }
-            Instruction::Add(regx, regy) => {
-                let x_val = self.registers.get(regx);
-                let y_val = self.registers.get(regy);
-
-                let mut temp = (x_val as u16) + (y_val as u16);
-                if temp > 0xFF {
-                    self.registers.set_0xf(1);
-                    temp = temp & 0xFF;
-                }
-
-                self.registers.set(regx, temp as u8);
-                self.pc += 2;
-            }
-            Instruction::Sub(regx, regy) => {
-                let x_val = self.registers.get(regx);
-                let y_val = self.registers.get(regy);
-
-                if x_val > y_val {
-                    self.registers.set_0xf(1);
-                } else {
-                    self.registers.set_0xf(0);
-                }
-    
-                // Apply subtraction
-                let result = x_val.wrapping_sub(y_val);
-                self.registers.set(regx, result);
-                self.pc += 2;
-            }
-            Instruction::ShiftR(reg) => {
-                let high_1 = self.registers.get(reg) & 0b1;
-                self.registers.set_0xf(high_1);
-
-                let val = self.registers.get(reg) >> 1;
-                self.registers.set(reg, val);
-                self.pc += 2;
-            }
-            Instruction::SubN(regx, regy) => {
-                let x_val = self.registers.get(regx);
-                let y_val = self.registers.get(regy);
-
-                if y_val > x_val {
-                    self.registers.set_0xf(1);
-                } else {
-                    self.registers.set_0xf(0);
-                }
-    
-                // Apply subtraction
-                let result = y_val.wrapping_sub(x_val);
-                self.registers.set(regx, result);
-                self.pc += 2;
-            }
-            Instruction::ShiftL(reg) => {
-                let val = self.registers.get(reg) >> 7;
-                self.registers.set_0xf(val);
-
-                let val = self.registers.get(reg) << 1;
-                self.registers.set(reg, val);
-                self.pc += 2;
-            }
+            Instruction::LoadR(regx, regy) => self.alu(AluOp::Load, regx, regy),
+            Instruction::Or(regx, regy) => self.alu(AluOp::Or, regx, regy),
+            Instruction::And(regx, regy) => self.alu(AluOp::And, regx, regy),
+            Instruction::Xor(regx, regy) => self.alu(AluOp::Xor, regx, regy),
+            Instruction::Add(regx, regy) => self.alu(AluOp::Add, regx, regy),
+            Instruction::Sub(regx, regy) => self.alu(AluOp::Sub, regx, regy),
+            Instruction::ShiftR(regx, regy) => self.alu(AluOp::ShiftR, regx, regy),
+            Instruction::SubN(regx, regy) => self.alu(AluOp::SubN, regx, regy),
+            Instruction::ShiftL(regx, regy) => self.alu(AluOp::ShiftL, regx, regy),
             Instruction::SkipNeq(regx, regy) => {
                 if self.registers.get(regx) != self.registers.get(regy) {
                     self.pc += 4;
@@ -330,58 +966,78 @@ impl<T, U> Chip8<T, U>
                 self.pc += 2;
             }
             Instruction::JumpAddV0(addr) => {
-                self.pc = addr + (self.registers.get(0x0) as u16);
+                let reg = if self.quirks.jump_vx { ((addr >> 8) & 0xF) as Register } else { 0x0 };
+                self.pc = addr + (self.registers.get(reg) as u16);
             }
             Instruction::Rand(reg, byte) => {
-                let val = rand::random::<u8>() & byte;
+                let val = self.rng.next_u8() & byte;
                 self.registers.set(reg, val);
                 self.pc += 2;
             }
             Instruction::Draw(regx, regy, nib) => {
-                let mut pixel_data = Vec::new();
-                let start = self.index as usize;
-                let end = (self.index + (nib as u16)) as usize;
-
-                // Iterate over our sprite data
-                for (idx, line) in self.memory[start..end].iter().enumerate() {
-                    // Get array of bits
-                    let mut bits = [0u8; 8];
-                    for i in 0..8 {
-                        bits[i] = (line >> i) & 1;
-                    }
+                if self.quirks.display_wait && !self.vblank {
+                    // Retry at the start of the next frame
+                    self.stalled = true;
+                    return Ok(());
+                }
 
-                    for (bit_pos, bit) in bits.iter().rev().enumerate() {
-                        // Get positions
-                        let x_pos = self.registers.get(regx) + (bit_pos as u8);
-                        let y_pos = self.registers.get(regy) + (idx as u8);
+                let sprite = self.index_range(nib as usize)?;
 
-                        // Get value
-                        let val = *bit == 1;
+                // The starting position always wraps, the sprite itself is
+                // clipped or wrapped depending on the quirks
+                let x_start = (self.registers.get(regx) % 64) as usize;
+                let y_start = (self.registers.get(regy) % 32) as usize;
+                let draw = DrawCall {
+                    index: self.index,
+                    height: nib,
+                    x: x_start as u8,
+                    y: y_start as u8,
+                };
+                if self.log_draws {
+                    info!("Draw at {:#05X}: index {:#05X}, height {}, x {}, y {}",
+                          self.pc, draw.index, draw.height, draw.x, draw.y);
+                }
+                self.last_draw = Some(draw);
 
-                        let pixel = PixelData{ x: x_pos as usize, 
-                                               y: y_pos as usize, 
-                                               val: val };
-                        
-                        pixel_data.push(pixel);
+                // XOR each sprite row onto the screen as a whole, VF reports
+                // whether a lit pixel was turned off
+                let mut collision = false;
+                let mut changed: Option<Region> = None;
+                for (idx, line) in self.memory[sprite].iter().enumerate() {
+                    let mut y_pos = y_start + idx;
+                    if y_pos >= 32 {
+                        if self.quirks.clip_sprites {
+                            break;
+                        }
+                        y_pos %= 32;
                     }
-                }
 
-                if let Some(ref mut screen) = self.screen {
-                    let collision = screen.set_pixel_data(&pixel_data[..]);
-                    if collision {
-                        self.registers.set_0xf(1);
+                    // Shifting drops the columns past the right edge,
+                    // rotating brings them back on the left
+                    let line = (*line as u64) << 56;
+                    let bits = if self.quirks.clip_sprites {
+                        line >> x_start
                     } else {
-                        self.registers.set_0xf(0);
+                        line.rotate_right(x_start as u32)
+                    };
+                    collision |= self.framebuffer.xor_row(y_pos, bits);
+
+                    if let Some(row) = Region::from_row(y_pos, bits) {
+                        changed = Some(changed.map_or(row, |region| region.union(&row)));
                     }
+                }
 
-                    screen.draw();
+                self.registers.set_0xf(collision as u8);
+                if let Some(region) = changed {
+                    self.mark_dirty(region);
                 }
 
                 self.pc += 2;
             }
+            // Only the low nibble of VX selects the key, like on the VIP
             Instruction::SkipEqKey(reg) => {
                 let mut skip = false;
-                if let Ok(key) = Chip8Key::new(self.registers.get(reg)) {
+                if let Ok(key) = Chip8Key::new(self.registers.get(reg) & 0xF) {
                     if let Some(ref mut keyboard) = self.keyboard {
                         skip = keyboard.key_pressed(key);
                     }
@@ -395,7 +1051,7 @@ impl<T, U> Chip8<T, U>
             }
             Instruction::SkipNeqKey(reg) => {
                 let mut skip = false;
-                if let Ok(key) = Chip8Key::new(self.registers.get(reg)) {
+                if let Ok(key) = Chip8Key::new(self.registers.get(reg) & 0xF) {
                     if let Some(ref mut keyboard) = self.keyboard {
                         skip = !keyboard.key_pressed(key);
                     }
@@ -408,26 +1064,33 @@ impl<T, U> Chip8<T, U>
                 }
             }
             Instruction::LoadFromDT(reg) => {
-                let val = self.delay_timer.lock().unwrap();
-                self.registers.set(reg, *val);
+                let val = self.delay_timer;
+                self.registers.set(reg, val);
                 self.pc += 2;
             }
             Instruction::LoadKey(reg) => {
-                if let Some(ref mut keyboard) = self.keyboard {
-                    if let Some(key_pressed) = keyboard.last_key_pressed() {
-                        self.registers.set(reg, key_pressed as u8);
+                match self.key_wait {
+                    Some(KeyWait::Done(key)) => {
+                        self.key_wait = None;
+                        self.registers.set(reg, key as u8);
                         self.pc += 2;
                     }
+                    Some(_) => self.stalled = true,
+                    None => {
+                        // Key events before this point are ignored. The
+                        // rest of the frame is skipped while waiting, the
+                        // timers keep running
+                        self.key_wait = Some(KeyWait::Press);
+                        self.stalled = true;
+                    }
                 }
             }
             Instruction::SetDT(reg) => {
-                let mut delay = self.delay_timer.lock().unwrap();
-                *delay = self.registers.get(reg);
+                self.delay_timer = self.registers.get(reg);
                 self.pc += 2;
             }
             Instruction::SetST(reg) => {
-                let mut sound = self.sound_timer.lock().unwrap();
-                *sound = self.registers.get(reg);
+                self.sound_timer = self.registers.get(reg);
                 self.pc += 2;
             }
             Instruction::AddIdx(reg) => {
@@ -449,28 +1112,47 @@ impl<T, U> Chip8<T, U>
                 }
                 */
 
-                self.memory[(self.index + 0) as usize] = val / 100;
-                self.memory[(self.index + 1) as usize] = (val / 10) % 10;
-                self.memory[(self.index + 2) as usize] = (val % 100) % 10;
+                let digits = self.index_range(3)?;
+                self.memory[digits.clone()]
+                    .copy_from_slice(&[val / 100, (val / 10) % 10, val % 10]);
+                self.invalidate(digits);
 
                 self.pc += 2;
             }
             Instruction::StoreRegs(reg) => {
+                let range = self.index_range(reg as usize + 1)?;
                 for idx in 0..(reg+1) {
-                    self.memory[(self.index + idx as u16) as usize] = self.registers.get(idx);
+                    self.memory[range.start + idx as usize] = self.registers.get(idx);
                 }
+                self.invalidate(range);
 
+                if self.quirks.load_store_increment {
+                    self.index += reg as u16 + 1;
+                }
                 self.pc += 2;
             }
             Instruction::ReadRegs(reg) => {
+                let start = self.index_range(reg as usize + 1)?.start;
                 for idx in 0..(reg+1) {
-                    let val = self.memory[(self.index + idx as u16) as usize];
+                    let val = self.memory[start + idx as usize];
                     self.registers.set(idx, val);
                 }
 
+                if self.quirks.load_store_increment {
+                    self.index += reg as u16 + 1;
+                }
                 self.pc += 2;
             }
         }
+
+        Ok(())
+    }
+}
+
+impl<T, U> Default for Chip8<T, U>
+    where T: Chip8Disp, U: Chip8Input {
+    fn default() -> Chip8<T, U> {
+        Chip8::new()
     }
 }
 
@@ -0,0 +1,16 @@
+use opcode::OpCode;
+
+/// Disassemble the instruction stored at `addr` using Cowgod's mnemonics,
+/// e.g. `LD V1, #22`. Words that don't decode are shown as data.
+pub fn disassemble(memory: &[u8], addr: u16) -> String {
+    let addr = addr as usize;
+    if addr + 1 >= memory.len() {
+        return "??".to_string();
+    }
+
+    let opcode = OpCode::new(&[memory[addr], memory[addr + 1]]);
+    match opcode.to_instruction() {
+        Ok(instruction) => instruction.to_string(),
+        Err(_) => format!("DW #{:04X}", opcode.raw()),
+    }
+}
@@ -0,0 +1,40 @@
+use std::fmt;
+
+/// Errors of the modules that also build without std, where there is no
+/// `String` to describe them. With std they convert into the `String`
+/// errors used everywhere else.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Error {
+    InvalidKey(u8),
+    InvalidOpcode(u16),
+    ProgramTooLong(usize),
+    WritePastEnd(u16),
+    NotAState,
+    StateVersion(u8),
+    InvalidStackPointer,
+    FrameSize(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidKey(value) => write!(f, "Invalid key {:#X}", value),
+            Error::InvalidOpcode(opcode) => write!(f, "Invalid instruction {:#06X}", opcode),
+            Error::ProgramTooLong(len) => write!(f, "Invalid program length: {} bytes", len),
+            Error::WritePastEnd(addr) => write!(f, "Write past the end of memory at {:#05X}", addr),
+            Error::NotAState => write!(f, "Not a Chip-8 save state"),
+            Error::StateVersion(version) => {
+                write!(f, "Unsupported save state version {}", version)
+            }
+            Error::InvalidStackPointer => write!(f, "Invalid stack pointer in save state"),
+            Error::FrameSize(len) => write!(f, "Invalid frame size: {} bytes", len),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Error> for String {
+    fn from(error: Error) -> String {
+        error.to_string()
+    }
+}
@@ -0,0 +1,269 @@
+use std::time::{Duration, Instant};
+
+use blocks::Backend;
+use core::{Chip8, DEFAULT_CYCLES_PER_FRAME};
+use peripherals::{Chip8Disp, Chip8Input, Chip8Key, KeyEvent, KeyQueue, Region};
+use quirks::Quirks;
+
+pub use peripherals::Framebuffer;
+
+/// A display that keeps a copy of the presented frame in memory, for running
+/// ROMs without a window. Only the dirty region is copied, so the copy shows
+/// whether the core reported every change.
+pub struct HeadlessDisplay {
+    frame: Framebuffer,
+}
+
+impl HeadlessDisplay {
+    pub fn new() -> HeadlessDisplay {
+        HeadlessDisplay {
+            frame: Framebuffer::new(),
+        }
+    }
+
+    /// The frame as of the last `present`.
+    pub fn frame(&self) -> &Framebuffer {
+        &self.frame
+    }
+}
+
+impl Default for HeadlessDisplay {
+    fn default() -> HeadlessDisplay {
+        HeadlessDisplay::new()
+    }
+}
+
+impl Chip8Disp for HeadlessDisplay {
+    fn present(&mut self, frame: &Framebuffer, dirty: Option<Region>) {
+        if let Some(dirty) = dirty {
+            let mask = dirty.row_mask();
+            for y in dirty.y..(dirty.y + dirty.height) {
+                let row = (self.frame.row(y) & !mask) | (frame.row(y) & mask);
+                self.frame.set_row(y, row);
+            }
+        }
+    }
+}
+
+/// A key press or release at the start of an emulated frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InputEvent {
+    pub frame: usize,
+    pub key: Chip8Key,
+    pub pressed: bool,
+}
+
+/// Input replaying a fixed sequence of key events. Frames are counted by
+/// `poll`, which `run_headless` calls once per frame.
+pub struct ScriptedInput {
+    script: Vec<InputEvent>,
+    frame: usize,
+    keys: KeyQueue,
+}
+
+impl ScriptedInput {
+    pub fn new(script: &[InputEvent]) -> ScriptedInput {
+        let mut script = script.to_vec();
+        script.sort_by_key(|event| event.frame);
+
+        ScriptedInput {
+            script: script,
+            frame: 0,
+            keys: KeyQueue::new(),
+        }
+    }
+
+    /// Parse a script of whitespace separated events, `<frame>:<key>` to
+    /// press a key and `<frame>:-<key>` to release it, e.g. `10:5 14:-5`.
+    /// Keys are hex digits.
+    pub fn parse(script: &str) -> Result<Vec<InputEvent>, String> {
+        script.split_whitespace()
+            .map(|event| {
+                let invalid = || format!("Invalid input event: {}", event);
+                let mut parts = event.splitn(2, ':');
+                let frame = parts.next()
+                    .and_then(|frame| frame.parse().ok())
+                    .ok_or_else(invalid)?;
+                let key = parts.next().ok_or_else(invalid)?;
+                let (pressed, key) = match key.strip_prefix('-') {
+                    Some(key) => (false, key),
+                    None => (true, key),
+                };
+                let key = u8::from_str_radix(key, 16)
+                    .map_err(|_| invalid())
+                    .and_then(|key| Chip8Key::new(key).map_err(String::from))?;
+
+                Ok(InputEvent {
+                    frame: frame,
+                    key: key,
+                    pressed: pressed,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Chip8Input for ScriptedInput {
+    fn key_pressed(&self, key: Chip8Key) -> bool {
+        self.keys.key_pressed(key)
+    }
+
+    fn next_event(&mut self) -> Option<KeyEvent> {
+        self.keys.next_event()
+    }
+
+    fn poll(&mut self) -> bool {
+        let frame = self.frame;
+        for event in self.script.iter().filter(|event| event.frame == frame) {
+            self.keys.set_key(event.key, event.pressed);
+        }
+
+        self.frame += 1;
+        false
+    }
+}
+
+/// Settings for `run_headless`.
+#[derive(Clone, Debug)]
+pub struct HeadlessConfig {
+    pub quirks: Quirks,
+    pub cycles_per_frame: usize,
+    /// Seed for `Rand`, so runs are repeatable.
+    pub seed: u32,
+    /// Bytes written to memory after the ROM is loaded, e.g. to select a
+    /// test in a test ROM.
+    pub pokes: Vec<(u16, u8)>,
+    pub input: Vec<InputEvent>,
+    /// Cache decoded instructions, see `Chip8::set_decode_cache`.
+    pub decode_cache: bool,
+    pub backend: Backend,
+}
+
+impl Default for HeadlessConfig {
+    fn default() -> HeadlessConfig {
+        HeadlessConfig {
+            quirks: Quirks::default(),
+            cycles_per_frame: DEFAULT_CYCLES_PER_FRAME,
+            seed: 0,
+            pokes: Vec::new(),
+            input: Vec::new(),
+            decode_cache: false,
+            backend: Backend::Interpreter,
+        }
+    }
+}
+
+pub type HeadlessChip8 = Chip8<HeadlessDisplay, ScriptedInput>;
+
+/// Load `program` into a machine without a window.
+pub fn headless(program: &[u8], config: &HeadlessConfig) -> Result<HeadlessChip8, String> {
+    let mut chip8 = Chip8::new();
+    chip8.set_quirks(config.quirks);
+    chip8.set_cycles_per_frame(config.cycles_per_frame);
+    chip8.seed_rng(config.seed);
+    chip8.set_decode_cache(config.decode_cache);
+    chip8.set_backend(config.backend);
+    chip8.upload_rom(program)?;
+    for &(addr, value) in config.pokes.iter() {
+        chip8.write_memory(addr, &[value])?;
+    }
+
+    chip8.connect_display(HeadlessDisplay::new());
+    chip8.connect_keyboard(ScriptedInput::new(&config.input));
+    Ok(chip8)
+}
+
+/// Run `program` for `frames` frames and return the screen at the end, or
+/// the fault that stopped it.
+pub fn run_headless(program: &[u8], config: &HeadlessConfig, frames: usize)
+                    -> Result<Framebuffer, String> {
+    let mut chip8 = headless(program, config)?;
+    for _ in 0..frames {
+        chip8.poll_input();
+        chip8.run_frame();
+        if let Some(fault) = chip8.fault() {
+            return Err(fault.to_string());
+        }
+    }
+
+    Ok(*chip8.framebuffer())
+}
+
+/// How fast `benchmark` ran a ROM.
+#[derive(Copy, Clone, Debug)]
+pub struct BenchReport {
+    pub frames: usize,
+    pub instructions: u64,
+    pub elapsed: Duration,
+}
+
+impl BenchReport {
+    /// Emulated instructions per second of real time, in millions.
+    pub fn mips(&self) -> f64 {
+        let secs = self.elapsed.as_secs() as f64 + self.elapsed.subsec_nanos() as f64 * 1e-9;
+        self.instructions as f64 / secs / 1e6
+    }
+}
+
+/// Run `program` for `frames` frames as fast as possible, without the 60 Hz
+/// limit, and measure how many instructions it executed. Stops early on a
+/// fault.
+pub fn benchmark(program: &[u8], config: &HeadlessConfig, frames: usize)
+                 -> Result<BenchReport, String> {
+    let mut chip8 = headless(program, config)?;
+    let start = Instant::now();
+    let mut frame = 0;
+    while frame < frames && chip8.fault().is_none() {
+        chip8.poll_input();
+        chip8.run_frame();
+        chip8.present();
+        frame += 1;
+    }
+
+    Ok(BenchReport {
+        frames: frame,
+        instructions: chip8.instructions(),
+        elapsed: start.elapsed(),
+    })
+}
+
+/// A framebuffer as text: 32 lines of 64 characters, `#` for lit pixels and
+/// `.` for dark ones.
+pub fn frame_to_string(frame: &Framebuffer) -> String {
+    let mut text = String::with_capacity(65 * 32);
+    for y in 0..32 {
+        for x in 0..64 {
+            text.push(if frame.pixel(x, y) { '#' } else { '.' });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Parse a framebuffer written by `frame_to_string`.
+pub fn frame_from_str(text: &str) -> Result<Framebuffer, String> {
+    let mut frame = Framebuffer::new();
+    let lines: Vec<&str> = text.lines()
+        .map(|line| line.trim_end())
+        .filter(|line| !line.is_empty())
+        .collect();
+    if lines.len() != 32 {
+        return Err(format!("Expected 32 lines, found {}", lines.len()));
+    }
+
+    for (y, line) in lines.iter().enumerate() {
+        if line.chars().count() != 64 {
+            return Err(format!("Line {} is not 64 pixels wide", y + 1));
+        }
+        for (x, c) in line.chars().enumerate() {
+            let lit = match c {
+                '#' => true,
+                '.' => false,
+                _ => return Err(format!("Invalid pixel {:?} on line {}", c, y + 1)),
+            };
+            frame.set_pixel(x, y, lit);
+        }
+    }
+
+    Ok(frame)
+}
@@ -1,12 +1,77 @@
+//! The emulator core and its frontends.
+//!
+//! The default `sdl` feature adds the SDL frontend to everything `std`
+//! builds. With `std` disabled as well only `alu`, `chip8`, `error`,
+//! `opcode`, `peripherals` and `quirks` are built, under `#![no_std]` and
+//! without an allocator.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+// Struct literals spell out `field: field`
+#![allow(clippy::redundant_field_names)]
+
+// Lets the modules shared with no_std builds keep their `std::` paths
+#[cfg(not(feature = "std"))]
+extern crate core as std;
+
+#[cfg(feature = "rand")]
 extern crate rand;
+#[cfg(feature = "sdl")]
 extern crate sdl2;
+#[cfg(feature = "std")]
+extern crate toml;
+#[cfg(feature = "std")]
+extern crate sha1;
+
+#[cfg(feature = "std")]
+#[macro_use]
+extern crate serde_derive;
 
 #[macro_use]
 extern crate log;
 
-mod opcode;
+pub mod alu;
+pub mod error;
+pub mod opcode;
 pub mod peripherals;
-pub mod sdl2_peripherals;
-pub mod core;
+pub mod quirks;
 
+#[cfg(feature = "std")]
+pub mod core;
+/// `core` under a name that is free without std, where `core` is the core
+/// library itself.
+#[cfg(feature = "std")]
+pub use core as chip8;
+#[cfg(not(feature = "std"))]
+#[path = "core.rs"]
+pub mod chip8;
 
+#[cfg(feature = "sdl")]
+mod font;
+#[cfg(feature = "std")]
+pub mod disasm;
+#[cfg(feature = "sdl")]
+pub mod sdl2_peripherals;
+#[cfg(feature = "sdl")]
+pub mod sdl2_gamepad;
+#[cfg(feature = "sdl")]
+pub mod sdl2_frontend;
+#[cfg(feature = "sdl")]
+pub mod sdl2_overlay;
+#[cfg(feature = "sdl")]
+pub mod sdl2_debugger;
+#[cfg(feature = "sdl")]
+pub mod sdl2_sprite_viewer;
+#[cfg(feature = "std")]
+pub mod blocks;
+#[cfg(feature = "std")]
+pub mod headless;
+#[cfg(feature = "std")]
+pub mod timing;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod romdb;
+#[cfg(feature = "std")]
+pub mod sprites;
+#[cfg(feature = "std")]
+pub mod snapshot;
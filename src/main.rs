@@ -1,3 +1,6 @@
+// Struct literals spell out `field: field`
+#![allow(clippy::redundant_field_names)]
+
 extern crate chip8_emulator;
 extern crate structopt;
 extern crate sdl2;
@@ -9,41 +12,494 @@ extern crate log4rs;
 #[macro_use]
 extern crate structopt_derive;
 
+use std::env;
 use std::io::Read;
 use std::fs::File;
+use std::path::Path;
+use std::process;
 
+use log::LevelFilter;
+use log4rs::append::console::ConsoleAppender;
+use log4rs::append::file::FileAppender;
+use log4rs::config::{Appender, Root};
 use structopt::StructOpt;
+use chip8_emulator::blocks::Backend;
 use chip8_emulator::core::Chip8;
-use chip8_emulator::sdl2_peripherals::{Display, Keyboard};
+use chip8_emulator::config::{self, Config, LoggingConfig, SettingsSource};
+use chip8_emulator::romdb::RomDatabase;
+use chip8_emulator::sdl2_peripherals::{Beeper, Display, Keyboard};
+use chip8_emulator::sdl2_frontend::Frontend;
+use chip8_emulator::sdl2_gamepad::{Gamepad, GamepadProfile};
+use chip8_emulator::headless::{self, HeadlessConfig};
+use chip8_emulator::quirks::Quirks;
+use chip8_emulator::snapshot::{self, SnapshotSpec};
 
-#[derive(StructOpt, Debug)]
+#[derive(StructOpt, Clone, Debug)]
 #[structopt(name = "fancify")]
 struct Cli {
     source: String,
+
+    #[structopt(long = "config",
+                help = "Path to a TOML config file [default: $XDG_CONFIG_HOME/chip8/config.toml]")]
+    config: Option<String>,
+
+    #[structopt(long = "no-autodetect",
+                help = "Don't apply settings from the ROM database or platform detection")]
+    no_autodetect: bool,
+
+    #[structopt(long = "speed", help = "Instructions executed per 60 Hz frame")]
+    speed: Option<usize>,
+
+    #[structopt(long = "quirks", help = "Quirks preset: chip8, schip or xochip")]
+    quirks: Option<String>,
+
+    #[structopt(long = "mute", help = "Disable sound")]
+    mute: bool,
+
+    #[structopt(long = "log-level", help = "One of off, error, warn, info, debug or trace")]
+    log_level: Option<String>,
+
+    #[structopt(long = "palette",
+                help = "Colour preset: classic, inverted, green, amber, lcd, octo or cyan")]
+    palette: Option<String>,
+
+    #[structopt(long = "fg", help = "Foreground colour as #RRGGBB")]
+    foreground: Option<String>,
+
+    #[structopt(long = "bg", help = "Background colour as #RRGGBB")]
+    background: Option<String>,
+
+    #[structopt(long = "scale", help = "Initial window size as a multiple of 64x32")]
+    scale: Option<u32>,
+
+    #[structopt(long = "scaling", help = "How the screen fills the window: integer, aspect or stretch")]
+    scaling: Option<String>,
+
+    #[structopt(long = "resizable", help = "Allow the window to be resized")]
+    resizable: bool,
+
+    #[structopt(long = "fullscreen", help = "Start in fullscreen")]
+    fullscreen: bool,
+
+    #[structopt(long = "no-vsync", help = "Present frames without waiting for vertical blank")]
+    no_vsync: bool,
+
+    #[structopt(long = "display-wait",
+                help = "Draw at most one sprite per frame, waiting for vblank like the COSMAC VIP")]
+    display_wait: bool,
+
+    #[structopt(long = "grid", help = "Draw a grid between pixels")]
+    grid: bool,
+
+    #[structopt(long = "scanlines", help = "Darken every other line of the window")]
+    scanlines: bool,
+
+    #[structopt(long = "persistence",
+                help = "Flicker reduction: off, phosphor (fading pixels) or or (merge recent frames)")]
+    persistence: Option<String>,
+
+    #[structopt(long = "decay", help = "Brightness kept per frame in phosphor mode, 0.0-1.0")]
+    decay: Option<f32>,
+
+    #[structopt(long = "persistence-frames", help = "Number of frames merged in or mode")]
+    persistence_frames: Option<u32>,
+
+    #[structopt(long = "no-gamepad", help = "Disable game controller input")]
+    no_gamepad: bool,
+
+    #[structopt(long = "gamepad-profile",
+                help = "Built-in gamepad profile to use instead of the one matching the ROM name")]
+    gamepad_profile: Option<String>,
+
+    #[structopt(long = "gamepad-map",
+                help = "Custom gamepad mapping, e.g. \"up=2,down=8,left=4,right=6,a=5\"")]
+    gamepad_map: Option<String>,
+
+    #[structopt(long = "deadzone", help = "Analog stick deadzone (0-32767)")]
+    deadzone: Option<u16>,
+
+    #[structopt(long = "debug", help = "Start paused with the debugger window open")]
+    debug: bool,
+
+    #[structopt(long = "sprites", help = "Open the sprite viewer window")]
+    sprites: bool,
+
+    #[structopt(long = "log-draws",
+                help = "Log every sprite drawn with its address, height and position")]
+    log_draws: bool,
+}
+
+impl Cli {
+    /// Apply command line flags on top of the config file settings.
+    fn apply(&self, config: &mut Config) {
+        if let Some(speed) = self.speed {
+            config.cpu.speed = speed;
+        }
+        if let Some(ref preset) = self.quirks {
+            // A preset chosen on the command line replaces configured quirks
+            config.quirks = Default::default();
+            config.quirks.preset = Some(preset.clone());
+        }
+        if self.display_wait {
+            config.quirks.display_wait = Some(true);
+        }
+
+        config.audio.enabled &= !self.mute;
+        if let Some(ref level) = self.log_level {
+            config.logging.level = level.clone();
+        }
+        if self.log_draws {
+            // Draw calls are logged at info level
+            let level = config.logging.level.parse().unwrap_or(LevelFilter::Warn);
+            if level < LevelFilter::Info {
+                config.logging.level = "info".to_string();
+            }
+        }
+
+        config.gamepad.enabled &= !self.no_gamepad;
+        if self.gamepad_profile.is_some() {
+            config.gamepad.profile = self.gamepad_profile.clone();
+            config.gamepad.mapping = None;
+        }
+        if self.gamepad_map.is_some() {
+            config.gamepad.mapping = self.gamepad_map.clone();
+        }
+        if let Some(deadzone) = self.deadzone {
+            config.gamepad.deadzone = deadzone;
+        }
+
+        let config = &mut config.display;
+        if let Some(ref palette) = self.palette {
+            config.palette = palette.clone();
+            // A preset chosen on the command line replaces configured colours
+            config.foreground = None;
+            config.background = None;
+        }
+        if self.foreground.is_some() {
+            config.foreground = self.foreground.clone();
+        }
+        if self.background.is_some() {
+            config.background = self.background.clone();
+        }
+        if let Some(scale) = self.scale {
+            config.scale = scale;
+        }
+        if let Some(ref scaling) = self.scaling {
+            config.scaling = scaling.clone();
+        }
+        config.resizable |= self.resizable;
+        config.fullscreen |= self.fullscreen;
+        config.vsync &= !self.no_vsync;
+        config.grid |= self.grid;
+        config.scanlines |= self.scanlines;
+        if let Some(ref persistence) = self.persistence {
+            config.persistence = persistence.clone();
+        }
+        if let Some(decay) = self.decay {
+            config.decay = decay;
+        }
+        if let Some(frames) = self.persistence_frames {
+            config.persistence_frames = frames;
+        }
+    }
+}
+
+/// `chip8 snapshot`, runs a ROM without a window to record its screen at
+/// chosen frames or check it still matches an earlier recording.
+#[derive(StructOpt, Debug)]
+#[structopt(name = "snapshot")]
+struct SnapshotCli {
+    #[structopt(help = "record or verify")]
+    action: String,
+
+    rom: String,
+
+    #[structopt(help = "Directory holding the snapshots")]
+    dir: String,
+
+    #[structopt(long = "frames", help = "Frames to record, e.g. 60,300,600")]
+    frames: Option<String>,
+
+    #[structopt(long = "input",
+                help = "Key events, e.g. \"10:5 14:-5\" presses 5 on frame 10 and releases it on 14")]
+    input: Option<String>,
+
+    #[structopt(long = "speed", help = "Instructions executed per 60 Hz frame")]
+    speed: Option<usize>,
+
+    #[structopt(long = "quirks", help = "Quirks preset: chip8, schip or xochip")]
+    quirks: Option<String>,
+
+    #[structopt(long = "seed", help = "Seed for the random number generator")]
+    seed: Option<u32>,
+}
+
+/// Run `chip8 snapshot`, returning the exit code.
+fn snapshot(cli: SnapshotCli) -> i32 {
+    let mut program = Vec::new();
+    if let Err(e) = File::open(&cli.rom).and_then(|mut file| file.read_to_end(&mut program)) {
+        eprintln!("Unable to read {}: {}", cli.rom, e);
+        return 2;
+    }
+
+    match cli.action.as_str() {
+        "record" => {
+            let frames = cli.frames.as_ref()
+                .ok_or_else(|| "--frames is required to record".to_string())
+                .and_then(|frames| {
+                    frames.split(',')
+                        .map(|frame| frame.trim().parse()
+                            .map_err(|_| format!("Invalid frame number: {}", frame)))
+                        .collect::<Result<Vec<usize>, String>>()
+                });
+            let mut spec = match frames {
+                Ok(frames) => SnapshotSpec::new(&program, frames),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return 2;
+                }
+            };
+            spec.quirks = cli.quirks.clone();
+            spec.speed = cli.speed.unwrap_or(spec.speed);
+            spec.seed = cli.seed.unwrap_or(spec.seed);
+            spec.input = cli.input.clone().unwrap_or_default();
+
+            match snapshot::record(&cli.dir, &program, &spec) {
+                Ok(()) => {
+                    println!("Recorded {} snapshots in {}", spec.frames.len(), cli.dir);
+                    0
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    2
+                }
+            }
+        }
+        "verify" => {
+            match snapshot::verify(&cli.dir, &program) {
+                Ok(ref mismatches) if mismatches.is_empty() => {
+                    println!("All snapshots in {} match", cli.dir);
+                    0
+                }
+                Ok(mismatches) => {
+                    for mismatch in mismatches.iter() {
+                        println!("{}", mismatch.diff());
+                    }
+                    println!("{} snapshots don't match, + is lit only now and - only before",
+                             mismatches.len());
+                    1
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    2
+                }
+            }
+        }
+        action => {
+            eprintln!("Unknown snapshot action {}, expected record or verify", action);
+            2
+        }
+    }
+}
+
+/// `chip8 bench`, runs ROMs without a window or frame limit and reports how
+/// many instructions per second were emulated.
+#[derive(StructOpt, Debug)]
+#[structopt(name = "bench")]
+struct BenchCli {
+    roms: Vec<String>,
+
+    #[structopt(long = "frames", help = "Frames to run each ROM for", default_value = "600")]
+    frames: usize,
+
+    #[structopt(long = "speed", help = "Instructions executed per 60 Hz frame",
+                default_value = "1000")]
+    speed: usize,
+
+    #[structopt(long = "quirks", help = "Quirks preset: chip8, schip or xochip")]
+    quirks: Option<String>,
+
+    #[structopt(long = "no-decode-cache", help = "Decode every instruction each time it runs")]
+    no_decode_cache: bool,
+
+    #[structopt(long = "backend", help = "interpreter or blocks", default_value = "interpreter")]
+    backend: String,
+}
+
+/// Run `chip8 bench`, returning the exit code.
+fn bench(cli: BenchCli) -> i32 {
+    let quirks = match cli.quirks {
+        Some(ref name) => match Quirks::preset(name) {
+            Some(quirks) => quirks,
+            None => {
+                eprintln!("Unknown quirks preset: {}", name);
+                return 2;
+            }
+        },
+        None => Quirks::default(),
+    };
+    let backend = match Backend::parse(&cli.backend) {
+        Ok(backend) => backend,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    let config = HeadlessConfig {
+        quirks: quirks,
+        cycles_per_frame: cli.speed,
+        decode_cache: !cli.no_decode_cache,
+        backend: backend,
+        ..HeadlessConfig::default()
+    };
+
+    let (mut instructions, mut seconds) = (0, 0.0);
+    for rom in cli.roms.iter() {
+        let mut program = Vec::new();
+        if let Err(e) = File::open(rom).and_then(|mut file| file.read_to_end(&mut program)) {
+            eprintln!("Unable to read {}: {}", rom, e);
+            return 2;
+        }
+
+        match headless::benchmark(&program, &config, cli.frames) {
+            Ok(report) => {
+                println!("{}: {} frames, {} instructions, {:.2} MIPS",
+                         rom, report.frames, report.instructions, report.mips());
+                instructions += report.instructions;
+                seconds += report.elapsed.as_secs() as f64 +
+                    report.elapsed.subsec_nanos() as f64 * 1e-9;
+            }
+            Err(e) => {
+                eprintln!("{}: {}", rom, e);
+                return 2;
+            }
+        }
+    }
+
+    if cli.roms.len() > 1 {
+        println!("Total: {} instructions, {:.2} MIPS",
+                 instructions, instructions as f64 / seconds / 1e6);
+    }
+    0
+}
+
+fn init_logging(config: &LoggingConfig) {
+    if let Some(ref path) = config.log4rs {
+        log4rs::init_file(path, Default::default()).expect("Invalid log4rs config");
+        return;
+    }
+
+    let level: LevelFilter = config.level.parse().expect("Invalid log level");
+    let stdout = ConsoleAppender::builder().build();
+    let mut builder = log4rs::config::Config::builder()
+        .appender(Appender::builder().build("stdout", Box::new(stdout)));
+    let mut root = Root::builder().appender("stdout");
+
+    if let Some(ref path) = config.file {
+        let file = FileAppender::builder().build(path).expect("Unable to open log file");
+        builder = builder.appender(Appender::builder().build("file", Box::new(file)));
+        root = root.appender("file");
+    }
+
+    let log_config = builder.build(root.build(level)).expect("Invalid logging config");
+    log4rs::init_config(log_config).expect("Unable to start logging");
 }
 
 fn main() {
-    log4rs::init_file("log4rs.yaml", Default::default()).unwrap();
+    if env::args().nth(1).is_some_and(|arg| arg == "snapshot") {
+        let matches = SnapshotCli::clap().get_matches_from(env::args().skip(1));
+        process::exit(snapshot(SnapshotCli::from_clap(matches)));
+    }
+    if env::args().nth(1).is_some_and(|arg| arg == "bench") {
+        let matches = BenchCli::clap().get_matches_from(env::args().skip(1));
+        process::exit(bench(BenchCli::from_clap(matches)));
+    }
 
-    info!("Prog Start");
     let cli = Cli::from_args();
-    let mut chip8 = Chip8::new();
 
-    // Load program from file & upload to core
-    let mut file = File::open(cli.source).expect("Invalid filename");
+    // Load program from file
+    let mut file = File::open(&cli.source).expect("Invalid filename");
     let mut program = Vec::new();
     file.read_to_end(&mut program).expect("Invalid file");
+
+    // Look the ROM up in the bundled and user databases, or guess its platform
+    let mut romdb = RomDatabase::bundled();
+    let user_romdb = config::config_dir().map(|dir| dir.join("romdb.toml"));
+    let mut romdb_error = None;
+    if let Some(ref path) = user_romdb {
+        if path.exists() {
+            match RomDatabase::load(path) {
+                Ok(db) => romdb.extend(db),
+                Err(e) => romdb_error = Some(e),
+            }
+        }
+    }
+
+    // Settings come from the ROM's recommended settings, the config file,
+    // the ROM's section in the config file and then the command line, each
+    // overriding the ones before. ROMs loaded from the pause menu are set up
+    // the same way. A missing default config file is not an error.
+    let config_path = match cli.config {
+        Some(ref path) => Some(path.into()),
+        None => Config::default_path().and_then(|path| if path.exists() { Some(path) } else { None }),
+    };
+    let overrides = cli.clone();
+    let source = SettingsSource::new(romdb, config_path, !cli.no_autodetect,
+                                     Box::new(move |config| overrides.apply(config)));
+    let settings = source.resolve(&program).expect("Invalid config file");
+    let config = &settings.config;
+
+    init_logging(&config.logging);
+    info!("Prog Start");
+    if let Some(e) = romdb_error {
+        warn!("{}", e);
+    }
+    settings.log();
+
+    let title = settings.info.title()
+        .map_or_else(|| "chip8".to_string(), |title| format!("chip8 - {}", title));
+
+    // Upload program to core
+    let mut chip8 = Chip8::new();
+    chip8.set_log_draws(cli.log_draws);
     chip8.upload_rom(&program).expect("Invalid program length");
 
-    // Set up chip8 core with peripherals
+    // Set up chip8 core with peripherals, the frontend applies the settings
     let sdl_context = sdl2::init().unwrap();
-    let display = Display::new(&sdl_context);
-    let keyboard = Keyboard::new(&sdl_context);
+    let display = Display::new(&sdl_context, &config.display).expect("Unable to open display");
+    let mut keyboard = Keyboard::new(&sdl_context);
+    if config.gamepad.enabled {
+        let gamepad = Gamepad::new(&sdl_context, GamepadProfile::default(),
+                                   config.gamepad.deadzone);
+        keyboard.connect_gamepad(gamepad);
+    }
     chip8.connect_keyboard(keyboard);
     chip8.connect_display(display);
 
+    let beeper = if config.audio.enabled {
+        match Beeper::new(&sdl_context, &config.audio) {
+            Ok(beeper) => Some(beeper),
+            Err(e) => {
+                warn!("Sound disabled: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
     // Run indefinitely
     info!("Run");
-    chip8.run();
+    let rom = Path::new(&cli.source);
+    let mut frontend = Frontend::new(chip8, beeper, source, &title, rom, &settings.rom_id);
+    frontend.configure(config, rom).expect("Invalid config file");
+    if cli.debug {
+        frontend.toggle_debugger();
+        frontend.set_paused(true);
+    }
+    if cli.sprites {
+        frontend.toggle_sprite_viewer();
+    }
+    frontend.run();
 }
 
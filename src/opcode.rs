@@ -1,9 +1,13 @@
+use std::fmt;
+
+use error::Error;
+
 pub type Register = u8;
 pub type Address = u16;
 pub type Immediate = u8;
 pub type Nibble = u8;
 
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Instruction {
     SYS(Address),
     Clear,
@@ -21,9 +25,9 @@ pub enum Instruction {
     Xor(Register, Register),
     Add(Register, Register),
     Sub(Register, Register),
-    ShiftR(Register),
+    ShiftR(Register, Register),
     SubN(Register, Register),
-    ShiftL(Register),
+    ShiftL(Register, Register),
     SkipNeq(Register, Register),
     LoadIdx(Address),
     JumpAddV0(Address),
@@ -42,6 +46,49 @@ pub enum Instruction {
     ReadRegs(Register),
 }
 
+impl fmt::Display for Instruction {
+    /// Cowgod's mnemonics, e.g. `LD V1, #22`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::SYS(addr) => write!(f, "SYS #{:03X}", addr),
+            Instruction::Clear => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::Jump(addr) => write!(f, "JP #{:03X}", addr),
+            Instruction::Call(addr) => write!(f, "CALL #{:03X}", addr),
+            Instruction::SkipEqI(x, byte) => write!(f, "SE V{:X}, #{:02X}", x, byte),
+            Instruction::SkipNeqI(x, byte) => write!(f, "SNE V{:X}, #{:02X}", x, byte),
+            Instruction::SkipEq(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::LoadI(x, byte) => write!(f, "LD V{:X}, #{:02X}", x, byte),
+            Instruction::AddI(x, byte) => write!(f, "ADD V{:X}, #{:02X}", x, byte),
+            Instruction::LoadR(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::Add(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftR(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubN(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftL(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipNeq(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadIdx(addr) => write!(f, "LD I, #{:03X}", addr),
+            Instruction::JumpAddV0(addr) => write!(f, "JP V0, #{:03X}", addr),
+            Instruction::Rand(x, byte) => write!(f, "RND V{:X}, #{:02X}", x, byte),
+            Instruction::Draw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipEqKey(x) => write!(f, "SKP V{:X}", x),
+            Instruction::SkipNeqKey(x) => write!(f, "SKNP V{:X}", x),
+            Instruction::LoadFromDT(x) => write!(f, "LD V{:X}, DT", x),
+            Instruction::LoadKey(x) => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDT(x) => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetST(x) => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddIdx(x) => write!(f, "ADD I, V{:X}", x),
+            Instruction::LoadSprite(x) => write!(f, "LD F, V{:X}", x),
+            Instruction::LoadBCD(x) => write!(f, "LD B, V{:X}", x),
+            Instruction::StoreRegs(x) => write!(f, "LD [I], V{:X}", x),
+            Instruction::ReadRegs(x) => write!(f, "LD V{:X}, [I]", x),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct OpCode {
     opcode: u16,
@@ -53,6 +100,10 @@ impl OpCode {
         OpCode { opcode: opcode }
     }
 
+    pub fn raw(&self) -> u16 {
+        self.opcode
+    }
+
     fn addr(&self) -> Address {
         self.opcode & 0x0FFF
     }
@@ -73,7 +124,7 @@ impl OpCode {
         (self.opcode & 0x000F) as Nibble
     }
 
-    pub fn to_instruction(&self) -> Result<Instruction, String> {
+    pub fn to_instruction(&self) -> Result<Instruction, Error> {
         match self.opcode & 0xF000 {
             0x0000 => match self.opcode {
                 0x00E0 => Ok(Instruction::Clear),
@@ -84,7 +135,10 @@ impl OpCode {
             0x2000 => Ok(Instruction::Call(self.addr())),
             0x3000 => Ok(Instruction::SkipEqI(self.x_register(), self.immediate())),
             0x4000 => Ok(Instruction::SkipNeqI(self.x_register(), self.immediate())),
-            0x5000 => Ok(Instruction::SkipEq(self.x_register(), self.y_register())),
+            0x5000 => match self.opcode & 0x000F {
+                0x0 => Ok(Instruction::SkipEq(self.x_register(), self.y_register())),
+                _ => Err(Error::InvalidOpcode(self.opcode)),
+            }
             0x6000 => Ok(Instruction::LoadI(self.x_register(), self.immediate())),
             0x7000 => Ok(Instruction::AddI(self.x_register(), self.immediate())),
             0x8000 => match self.opcode & 0x000F {
@@ -94,14 +148,14 @@ impl OpCode {
                 0x3 => Ok(Instruction::Xor(self.x_register(), self.y_register())),
                 0x4 => Ok(Instruction::Add(self.x_register(), self.y_register())),
                 0x5 => Ok(Instruction::Sub(self.x_register(), self.y_register())),
-                0x6 => Ok(Instruction::ShiftR(self.x_register())),
+                0x6 => Ok(Instruction::ShiftR(self.x_register(), self.y_register())),
                 0x7 => Ok(Instruction::SubN(self.x_register(), self.y_register())),
-                0xE => Ok(Instruction::ShiftL(self.x_register())),
-                _ => Err("Invalid instruction!".to_string()),
+                0xE => Ok(Instruction::ShiftL(self.x_register(), self.y_register())),
+                _ => Err(Error::InvalidOpcode(self.opcode)),
             },
             0x9000 => match self.opcode & 0x000F {
                 0x0 => Ok(Instruction::SkipNeq(self.x_register(), self.y_register())),
-                _ => Err("Invalid instruction!".to_string()),
+                _ => Err(Error::InvalidOpcode(self.opcode)),
             }
             0xA000 => Ok(Instruction::LoadIdx(self.addr())),
             0xB000 => Ok(Instruction::JumpAddV0(self.addr())),
@@ -110,7 +164,7 @@ impl OpCode {
             0xE000 => match self.opcode & 0x00FF {
                 0x9E => Ok(Instruction::SkipEqKey(self.x_register())),
                 0xA1 => Ok(Instruction::SkipNeqKey(self.x_register())),
-                _ => Err("Invalid instruction!".to_string()),
+                _ => Err(Error::InvalidOpcode(self.opcode)),
             }
             0xF000 => match self.opcode & 0x00FF {
                 0x07 => Ok(Instruction::LoadFromDT(self.x_register())),
@@ -122,7 +176,7 @@ impl OpCode {
                 0x33 => Ok(Instruction::LoadBCD(self.x_register())),
                 0x55 => Ok(Instruction::StoreRegs(self.x_register())),
                 0x65 => Ok(Instruction::ReadRegs(self.x_register())),
-                _ => Err("Invalid instruction!".to_string()),
+                _ => Err(Error::InvalidOpcode(self.opcode)),
             }
             _ => {
                 unreachable!()
@@ -1,19 +1,225 @@
-#[derive(Debug)]
-pub struct PixelData { pub x: usize, pub y: usize, pub val: bool }
+use error::Error;
 
+/// The 64x32 monochrome screen, one `u64` per row with the leftmost pixel
+/// in the high bit, so a sprite row is drawn with a single XOR.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Framebuffer {
+    rows: [u64; 32],
+}
+
+impl Framebuffer {
+    pub fn new() -> Framebuffer {
+        Framebuffer {
+            rows: [0; 32],
+        }
+    }
+
+    pub fn row(&self, y: usize) -> u64 {
+        self.rows[y]
+    }
+
+    pub fn set_row(&mut self, y: usize, bits: u64) {
+        self.rows[y] = bits;
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.rows[y] & column_bit(x) != 0
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, lit: bool) {
+        if lit {
+            self.rows[y] |= column_bit(x);
+        } else {
+            self.rows[y] &= !column_bit(x);
+        }
+    }
+
+    /// Flip the pixels of row `y` that are set in `bits`, returning whether
+    /// any of them was lit.
+    pub fn xor_row(&mut self, y: usize, bits: u64) -> bool {
+        let collision = self.rows[y] & bits != 0;
+        self.rows[y] ^= bits;
+        collision
+    }
+
+    pub fn clear(&mut self) {
+        self.rows = [0; 32];
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Framebuffer {
+        Framebuffer::new()
+    }
+}
+
+fn column_bit(x: usize) -> u64 {
+    0x8000_0000_0000_0000 >> x
+}
+
+/// A rectangle of the screen, in pixels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    /// The whole screen.
+    pub fn full() -> Region {
+        Region {
+            x: 0,
+            y: 0,
+            width: 64,
+            height: 32,
+        }
+    }
+
+    /// The smallest region covering both.
+    pub fn union(&self, other: &Region) -> Region {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Region {
+            x: x,
+            y: y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+
+    /// The columns `bits` covers in row `y`, `None` if it's empty.
+    pub fn from_row(y: usize, bits: u64) -> Option<Region> {
+        if bits == 0 {
+            return None;
+        }
+
+        let x = bits.leading_zeros() as usize;
+        Some(Region {
+            x: x,
+            y: y,
+            width: 64 - bits.trailing_zeros() as usize - x,
+            height: 1,
+        })
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    /// The columns covered, as a mask for a `Framebuffer` row.
+    pub fn row_mask(&self) -> u64 {
+        if self.width == 0 {
+            return 0;
+        }
+        (!0u64 >> self.x) & (!0u64 << (64 - self.x - self.width))
+    }
+}
+
+/// Shows the screen. The core draws sprites and detects collisions itself,
+/// displays only present the result.
 pub trait Chip8Disp {
-    fn set_pixel_data(&mut self, data: &[PixelData]) -> bool;
-    fn draw(&mut self);
-    fn clear(&mut self);
+    /// Present `frame`, called at most once per 60 Hz frame. `dirty` covers
+    /// every pixel that changed since the last call, it is `None` when
+    /// nothing did and the display is only redrawn because it is animating.
+    fn present(&mut self, frame: &Framebuffer, dirty: Option<Region>);
+
+    /// Whether the display wants `present` every frame even when nothing
+    /// changed, e.g. to animate fading pixels.
+    fn animating(&self) -> bool {
+        false
+    }
 }
 
 pub trait Chip8Input {
-    fn last_key_pressed(&self) -> Option<Chip8Key>;
     fn key_pressed(&self, key: Chip8Key) -> bool;
+    /// The oldest key event that wasn't taken yet.
+    fn next_event(&mut self) -> Option<KeyEvent>;
     fn poll(&mut self) -> bool;
 }
 
-#[derive(Copy, Clone, PartialEq)]
+/// A keypad key going down or up.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum KeyEvent {
+    Pressed(Chip8Key),
+    Released(Chip8Key),
+}
+
+/// Key events a `KeyQueue` holds before it drops the oldest.
+pub const KEY_QUEUE_LEN: usize = 32;
+
+/// The state of the 16 keys and the events that led to it, in the order
+/// they happened, for implementations of `Chip8Input`.
+pub struct KeyQueue {
+    keys_pressed: [bool; 16],
+    // A ring buffer of `len` events starting at `first`
+    events: [KeyEvent; KEY_QUEUE_LEN],
+    first: usize,
+    len: usize,
+}
+
+impl KeyQueue {
+    pub fn new() -> KeyQueue {
+        KeyQueue {
+            keys_pressed: [false; 16],
+            events: [KeyEvent::Released(Chip8Key::Key0); KEY_QUEUE_LEN],
+            first: 0,
+            len: 0,
+        }
+    }
+
+    /// Press or release a key, queueing an event if that changes it.
+    pub fn set_key(&mut self, key: Chip8Key, pressed: bool) {
+        if self.keys_pressed[key as usize] != pressed {
+            self.keys_pressed[key as usize] = pressed;
+            if self.len == KEY_QUEUE_LEN {
+                warn!("Key event queue full, dropping {:?}", self.events[self.first]);
+                self.first = (self.first + 1) % KEY_QUEUE_LEN;
+                self.len -= 1;
+            }
+            self.events[(self.first + self.len) % KEY_QUEUE_LEN] = if pressed {
+                KeyEvent::Pressed(key)
+            } else {
+                KeyEvent::Released(key)
+            };
+            self.len += 1;
+        }
+    }
+
+    /// Set all keys at once. Events for keys that changed are queued in
+    /// the order of the keys.
+    pub fn set_keys(&mut self, keys: &[bool; 16]) {
+        for (idx, &pressed) in keys.iter().enumerate() {
+            if let Ok(key) = Chip8Key::new(idx as u8) {
+                self.set_key(key, pressed);
+            }
+        }
+    }
+
+    pub fn key_pressed(&self, key: Chip8Key) -> bool {
+        self.keys_pressed[key as usize]
+    }
+
+    pub fn next_event(&mut self) -> Option<KeyEvent> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let event = self.events[self.first];
+        self.first = (self.first + 1) % KEY_QUEUE_LEN;
+        self.len -= 1;
+        Some(event)
+    }
+}
+
+impl Default for KeyQueue {
+    fn default() -> KeyQueue {
+        KeyQueue::new()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub enum Chip8Key {
     Key0,
     Key1,
@@ -34,7 +240,7 @@ pub enum Chip8Key {
 }
 
 impl Chip8Key {
-    pub fn new(value: u8) -> Result<Chip8Key, String>  {
+    pub fn new(value: u8) -> Result<Chip8Key, Error>  {
         use self::Chip8Key::*;
         match value {
             0 => Ok(Key0),
@@ -53,7 +259,7 @@ impl Chip8Key {
             13 => Ok(KeyD),
             14 => Ok(KeyE),
             15 => Ok(KeyF),
-            _ => Err("Invalid Key token".to_string()),
+            _ => Err(Error::InvalidKey(value)),
         }
     }
 }
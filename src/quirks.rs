@@ -0,0 +1,101 @@
+/// Behaviour that differs between Chip-8 implementations and that ROMs
+/// written for one of them may depend on.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quirks {
+    /// `ShiftR`/`ShiftL` (8XY6/8XYE) shift VY and store the result in VX,
+    /// instead of shifting VX in place.
+    pub shift_vy: bool,
+    /// `StoreRegs`/`ReadRegs` (FX55/FX65) leave I pointing past the last
+    /// register transferred.
+    pub load_store_increment: bool,
+    /// `JumpAddV0` (BNNN) is read as BXNN and jumps to XNN + VX.
+    pub jump_vx: bool,
+    /// `Or`, `And` and `Xor` (8XY1/8XY2/8XY3) reset VF to 0.
+    pub vf_reset: bool,
+    /// `Draw` waits for the next 60 Hz vblank before drawing, so at most one
+    /// sprite is drawn per frame like on the original COSMAC VIP.
+    pub display_wait: bool,
+    /// Sprites crossing the edge of the screen are cut off instead of
+    /// wrapping around to the other side.
+    pub clip_sprites: bool,
+    /// ALU flags as earlier versions of this emulator set them: `Add` never
+    /// clears VF, `Sub` and `SubN` clear it for equal operands and VF is
+    /// written before the result. Only useful to compare runs with those
+    /// versions.
+    pub legacy_alu_flags: bool,
+    /// `LoadKey` (FX0A) stores a key as soon as it is pressed, instead of
+    /// waiting for it to be released again like the COSMAC VIP.
+    pub key_on_press: bool,
+}
+
+pub const PRESET_NAMES: [&str; 3] = ["chip8", "schip", "xochip"];
+
+impl Quirks {
+    /// Quirks for one of the well known platforms.
+    ///
+    /// `chip8` is the original COSMAC VIP interpreter, `schip` is SUPER-CHIP
+    /// 1.1 on the HP48 and `xochip` is Octo's XO-CHIP.
+    pub fn preset(name: &str) -> Option<Quirks> {
+        // Compared in place, there is no `to_lowercase` without std
+        let is = |aliases: &[&str]| aliases.iter().any(|alias| alias.eq_ignore_ascii_case(name));
+
+        if is(&["chip8", "chip-8", "vip"]) {
+            Some(Quirks {
+                shift_vy: true,
+                load_store_increment: true,
+                jump_vx: false,
+                vf_reset: true,
+                display_wait: true,
+                clip_sprites: true,
+                legacy_alu_flags: false,
+                key_on_press: false,
+            })
+        } else if is(&["schip", "superchip"]) {
+            Some(Quirks {
+                shift_vy: false,
+                load_store_increment: false,
+                jump_vx: true,
+                vf_reset: false,
+                display_wait: false,
+                clip_sprites: true,
+                legacy_alu_flags: false,
+                key_on_press: false,
+            })
+        } else if is(&["xochip", "xo-chip"]) {
+            Some(Quirks {
+                shift_vy: true,
+                load_store_increment: true,
+                jump_vx: false,
+                vf_reset: false,
+                display_wait: false,
+                clip_sprites: false,
+                legacy_alu_flags: false,
+                key_on_press: false,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The name of the preset these quirks match, if any.
+    pub fn preset_name(&self) -> Option<&'static str> {
+        PRESET_NAMES.iter()
+            .find(|name| Quirks::preset(name) == Some(*self))
+            .cloned()
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks {
+            shift_vy: false,
+            load_store_increment: false,
+            jump_vx: false,
+            vf_reset: false,
+            display_wait: false,
+            clip_sprites: true,
+            legacy_alu_flags: false,
+            key_on_press: false,
+        }
+    }
+}
@@ -0,0 +1,417 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use toml;
+
+use opcode::{Address, Instruction, OpCode};
+
+/// Database bundled with the emulator, see `data/romdb.toml` for the format.
+const BUNDLED_DB: &str = include_str!("../data/romdb.toml");
+
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub enum Platform {
+    #[serde(rename = "chip8")]
+    Chip8,
+    #[serde(rename = "schip")]
+    SuperChip,
+    #[serde(rename = "xochip")]
+    XoChip,
+}
+
+impl Platform {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SuperChip => "SUPER-CHIP",
+            Platform::XoChip => "XO-CHIP",
+        }
+    }
+
+    /// Name of the quirks preset matching the platform.
+    pub fn quirks_preset(&self) -> &'static str {
+        match *self {
+            Platform::Chip8 => "chip8",
+            Platform::SuperChip => "schip",
+            Platform::XoChip => "xochip",
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RomEntry {
+    /// Lowercase hex SHA-1 of the ROM file.
+    pub sha1: String,
+    pub title: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    pub platform: Platform,
+    /// Quirks preset, defaults to the platform's preset.
+    #[serde(default)]
+    pub quirks: Option<String>,
+    /// Recommended instructions per frame.
+    #[serde(default)]
+    pub speed: Option<usize>,
+    /// Short description of the controls.
+    #[serde(default)]
+    pub keys: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct RomDatabase {
+    #[serde(default)]
+    rom: Vec<RomEntry>,
+}
+
+impl RomDatabase {
+    pub fn bundled() -> RomDatabase {
+        RomDatabase::parse(BUNDLED_DB).expect("Invalid bundled ROM database")
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<RomDatabase, String> {
+        let path = path.as_ref();
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|e| format!("Unable to read ROM database {}: {}", path.display(), e))?;
+
+        RomDatabase::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<RomDatabase, String> {
+        toml::from_str(contents).map_err(|e| format!("Invalid ROM database: {}", e))
+    }
+
+    /// Add the entries of `other`, replacing entries for the same ROM.
+    pub fn extend(&mut self, other: RomDatabase) {
+        for entry in other.rom {
+            self.rom.retain(|e| !e.sha1.eq_ignore_ascii_case(&entry.sha1));
+            self.rom.push(entry);
+        }
+    }
+
+    pub fn lookup(&self, rom_id: &str) -> Option<&RomEntry> {
+        self.rom.iter().find(|e| e.sha1.eq_ignore_ascii_case(rom_id))
+    }
+
+    pub fn len(&self) -> usize {
+        self.rom.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rom.is_empty()
+    }
+}
+
+/// What is known about a ROM, either from the database or from scanning it.
+#[derive(Clone, Debug)]
+pub struct RomInfo {
+    pub entry: Option<RomEntry>,
+    pub platform: Platform,
+}
+
+impl RomInfo {
+    pub fn identify(db: &RomDatabase, rom_id: &str, program: &[u8]) -> RomInfo {
+        match db.lookup(rom_id) {
+            Some(entry) => RomInfo {
+                platform: entry.platform,
+                entry: Some(entry.clone()),
+            },
+            None => RomInfo {
+                entry: None,
+                platform: detect_platform(program),
+            },
+        }
+    }
+
+    /// The game's title followed by its controls, if it is in the database.
+    pub fn title(&self) -> Option<String> {
+        self.entry.as_ref().map(|entry| match entry.keys {
+            Some(ref keys) => format!("{} ({})", entry.title, keys),
+            None => entry.title.clone(),
+        })
+    }
+
+    /// The quirks preset to use, if the ROM is in the database or uses
+    /// SUPER-CHIP or XO-CHIP instructions. Other ROMs keep the default
+    /// quirks, which most CHIP-8 games of the CHIP-48 era rely on.
+    pub fn quirks_preset(&self) -> Option<String> {
+        match self.entry {
+            Some(ref entry) => Some(entry.quirks.clone()
+                .unwrap_or_else(|| entry.platform.quirks_preset().to_string())),
+            None if self.platform != Platform::Chip8 => {
+                Some(self.platform.quirks_preset().to_string())
+            }
+            None => None,
+        }
+    }
+
+    /// Recommended settings laid out like the config file, so they can be
+    /// layered underneath the user's own configuration.
+    pub fn recommended_config(&self) -> toml::Value {
+        let mut config = toml::value::Table::new();
+
+        if let Some(preset) = self.quirks_preset() {
+            let mut quirks = toml::value::Table::new();
+            quirks.insert("preset".to_string(), toml::Value::String(preset));
+            config.insert("quirks".to_string(), toml::Value::Table(quirks));
+        }
+
+        if let Some(speed) = self.entry.as_ref().and_then(|e| e.speed) {
+            let mut cpu = toml::value::Table::new();
+            cpu.insert("speed".to_string(), toml::Value::Integer(speed as i64));
+            config.insert("cpu".to_string(), toml::Value::Table(cpu));
+        }
+
+        toml::Value::Table(config)
+    }
+}
+
+/// The platform an instruction needs, for instructions that aren't part of
+/// plain CHIP-8.
+fn extension_platform(opcode: u16) -> Option<Platform> {
+    let decoded = OpCode::new(&[(opcode >> 8) as u8, opcode as u8]).to_instruction();
+
+    match decoded {
+        Ok(Instruction::SYS(addr)) => match addr {
+            // 00Cn scroll down, 00FB-00FF scroll, exit, lores/hires
+            0x0C0..=0x0CF | 0x0FB..=0x0FF => Some(Platform::SuperChip),
+            // 00Dn scroll up
+            0x0D0..=0x0DF => Some(Platform::XoChip),
+            _ => None,
+        },
+        // DXY0 draws a 16x16 sprite
+        Ok(Instruction::Draw(_, _, 0)) => Some(Platform::SuperChip),
+        Ok(_) => None,
+        Err(_) => match (opcode & 0xF000, opcode & 0x00FF) {
+            // FX30 big font, FX75/FX85 RPL flags
+            (0xF000, 0x30) | (0xF000, 0x75) | (0xF000, 0x85) => Some(Platform::SuperChip),
+            // F000 long load, FN01 planes, F002 audio, FX3A pitch
+            (0xF000, 0x00) | (0xF000, 0x01) | (0xF000, 0x02) | (0xF000, 0x3A) => Some(Platform::XoChip),
+            // 5XY2/5XY3 save/load register ranges
+            (0x5000, _) if opcode & 0xF == 0x2 || opcode & 0xF == 0x3 => Some(Platform::XoChip),
+            _ => None,
+        },
+    }
+}
+
+/// Guess the platform a ROM was written for by following its code from the
+/// entry point and looking for SUPER-CHIP or XO-CHIP only instructions.
+///
+/// Only reachable code is scanned so sprite data can't be mistaken for
+/// instructions. Code reached through computed jumps (`BNNN`) is missed.
+pub fn detect_platform(program: &[u8]) -> Platform {
+    let read = |addr: Address| -> Option<u16> {
+        let offset = addr.checked_sub(0x200)? as usize;
+        if offset + 1 < program.len() {
+            Some(((program[offset] as u16) << 8) | program[offset + 1] as u16)
+        } else {
+            None
+        }
+    };
+
+    let mut platform = Platform::Chip8;
+    let mut visited = HashSet::new();
+    let mut pending = vec![0x200];
+
+    while let Some(addr) = pending.pop() {
+        if !visited.insert(addr) {
+            continue;
+        }
+
+        let opcode = match read(addr) {
+            Some(opcode) => opcode,
+            None => continue,
+        };
+
+        if let Some(found) = extension_platform(opcode) {
+            if found > platform {
+                debug!("{} instruction {:04X} at {:03X}", found.name(), opcode, addr);
+                platform = found;
+            }
+        }
+
+        // XO-CHIP's F000 NNNN is four bytes long
+        let next = if opcode == 0xF000 { addr + 4 } else { addr + 2 };
+
+        let instruction = OpCode::new(&[(opcode >> 8) as u8, opcode as u8]).to_instruction();
+        match instruction {
+            Ok(Instruction::Jump(target)) => pending.push(target),
+            Ok(Instruction::Call(target)) => {
+                pending.push(target);
+                pending.push(next);
+            }
+            Ok(Instruction::SkipEqI(..)) |
+            Ok(Instruction::SkipNeqI(..)) |
+            Ok(Instruction::SkipEq(..)) |
+            Ok(Instruction::SkipNeq(..)) |
+            Ok(Instruction::SkipEqKey(..)) |
+            Ok(Instruction::SkipNeqKey(..)) => {
+                pending.push(next);
+                // XO-CHIP skips over the whole of an F000 NNNN
+                if read(next) == Some(0xF000) {
+                    pending.push(next + 4);
+                } else {
+                    pending.push(next + 2);
+                }
+            }
+            Ok(Instruction::Return) | Ok(Instruction::JumpAddV0(_)) => {}
+            // 00FD exits the interpreter
+            Ok(Instruction::SYS(0x0FD)) => {}
+            Ok(_) => pending.push(next),
+            Err(_) => {
+                if extension_platform(opcode).is_some() {
+                    pending.push(next);
+                }
+            }
+        }
+    }
+
+    platform
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DB: &str = r#"
+        [[rom]]
+        sha1 = "0123456789ABCDEF0123456789ABCDEF01234567"
+        title = "Some game"
+        platform = "schip"
+        speed = 30
+        keys = "4/6 move"
+
+        [[rom]]
+        sha1 = "89abcdef0123456789abcdef0123456789abcdef"
+        title = "Other game"
+        platform = "chip8"
+        quirks = "schip"
+    "#;
+
+    /// `opcodes` as a program, ending in a jump to itself.
+    fn program(opcodes: &[u16]) -> Vec<u8> {
+        let end = 0x1200 + 2 * opcodes.len() as u16;
+        opcodes.iter().chain([end].iter()).flat_map(|op| vec![(op >> 8) as u8, *op as u8])
+            .collect()
+    }
+
+    #[test]
+    fn bundled_entries_are_valid() {
+        let db = RomDatabase::bundled();
+        for entry in db.rom.iter() {
+            assert_eq!(entry.sha1.len(), 40, "{}", entry.title);
+            assert!(entry.sha1.chars().all(|c| c.is_ascii_hexdigit()), "{}", entry.title);
+            if let Some(ref preset) = entry.quirks {
+                assert!(::quirks::Quirks::preset(preset).is_some(), "{}", entry.title);
+            }
+        }
+    }
+
+    #[test]
+    fn bundled_maze() {
+        // Maze by David Winter, public domain
+        let maze = [0xA2, 0x1E, 0xC2, 0x01, 0x32, 0x01, 0xA2, 0x1A, 0xD0, 0x14, 0x70, 0x04,
+                    0x30, 0x40, 0x12, 0x00, 0x60, 0x00, 0x71, 0x04, 0x31, 0x20, 0x12, 0x00,
+                    0x12, 0x18, 0x80, 0x40, 0x20, 0x10, 0x20, 0x40, 0x80, 0x10];
+        let db = RomDatabase::bundled();
+        let info = RomInfo::identify(&db, &::config::rom_id(&maze), &maze);
+
+        let entry = info.entry.as_ref().unwrap();
+        assert_eq!(entry.title, "Maze");
+        assert_eq!(entry.author.as_deref(), Some("David Winter"));
+        assert_eq!(info.platform, Platform::Chip8);
+        let config = info.recommended_config();
+        assert_eq!(config["quirks"]["preset"].as_str(), Some("chip8"));
+        assert_eq!(config["cpu"]["speed"].as_integer(), Some(9));
+    }
+
+    #[test]
+    fn lookup() {
+        let db = RomDatabase::parse(DB).unwrap();
+        assert_eq!(db.len(), 2);
+        let entry = db.lookup("0123456789abcdef0123456789abcdef01234567").unwrap();
+        assert_eq!(entry.title, "Some game");
+        assert_eq!(entry.platform, Platform::SuperChip);
+        assert_eq!(entry.speed, Some(30));
+        assert_eq!(entry.keys.as_deref(), Some("4/6 move"));
+        assert!(db.lookup("0000000000000000000000000000000000000000").is_none());
+    }
+
+    #[test]
+    fn extend_replaces_entries() {
+        let mut db = RomDatabase::parse(DB).unwrap();
+        db.extend(RomDatabase::parse(r#"
+            [[rom]]
+            sha1 = "0123456789abcdef0123456789abcdef01234567"
+            title = "Renamed"
+            platform = "xochip"
+        "#).unwrap());
+        assert_eq!(db.len(), 2);
+        assert_eq!(db.lookup("0123456789abcdef0123456789abcdef01234567").unwrap().title,
+                   "Renamed");
+    }
+
+    #[test]
+    fn identify_prefers_the_database() {
+        let db = RomDatabase::parse(DB).unwrap();
+        let xochip = program(&[0xF001]);
+
+        let info = RomInfo::identify(&db, "89abcdef0123456789abcdef0123456789abcdef", &xochip);
+        assert_eq!(info.platform, Platform::Chip8);
+        let config = info.recommended_config();
+        assert_eq!(config["quirks"]["preset"].as_str(), Some("schip"));
+        assert!(config.get("cpu").is_none());
+
+        let info = RomInfo::identify(&db, "0123456789abcdef0123456789abcdef01234567", &xochip);
+        assert_eq!(info.recommended_config()["cpu"]["speed"].as_integer(), Some(30));
+
+        let info = RomInfo::identify(&db, "unknown", &xochip);
+        assert!(info.entry.is_none());
+        assert_eq!(info.platform, Platform::XoChip);
+        assert_eq!(info.recommended_config()["quirks"]["preset"].as_str(), Some("xochip"));
+    }
+
+    #[test]
+    fn detected_chip8_keeps_the_default_quirks() {
+        let db = RomDatabase::parse(DB).unwrap();
+        let info = RomInfo::identify(&db, "unknown", &program(&[0x6001, 0xD005]));
+        assert_eq!(info.platform, Platform::Chip8);
+        assert_eq!(info.quirks_preset(), None);
+        assert!(info.recommended_config().get("quirks").is_none());
+    }
+
+    #[test]
+    fn detect_chip8() {
+        // LD V0, 1; ADD V0, 1; DRW V0, V0, 5
+        assert_eq!(detect_platform(&program(&[0x6001, 0x7001, 0xD005])), Platform::Chip8);
+        assert_eq!(detect_platform(&[]), Platform::Chip8);
+    }
+
+    #[test]
+    fn detect_schip() {
+        // HIGH, LOW, LD HF, V0 and LD R, V3
+        for &op in [0x00FF, 0x00FE, 0xF030, 0xF375].iter() {
+            assert_eq!(detect_platform(&program(&[0x6001, op])), Platform::SuperChip,
+                       "{:04X}", op);
+        }
+    }
+
+    #[test]
+    fn detect_xochip() {
+        // LD I, long NNNN; SAVE V1-V2 and PLANE 1
+        for ops in [&[0xF000, 0x0300][..], &[0x5122], &[0xF101]].iter() {
+            assert_eq!(detect_platform(&program(ops)), Platform::XoChip, "{:04X?}", ops);
+        }
+        // XO-CHIP wins over SUPER-CHIP
+        assert_eq!(detect_platform(&program(&[0x00FF, 0xF101])), Platform::XoChip);
+    }
+
+    #[test]
+    fn data_is_not_scanned() {
+        // JP over a sprite that looks like HIGH
+        let rom = [0x12, 0x04, 0x00, 0xFF, 0x12, 0x04];
+        assert_eq!(detect_platform(&rom), Platform::Chip8);
+    }
+}
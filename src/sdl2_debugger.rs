@@ -0,0 +1,297 @@
+use sdl2;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::render::Canvas;
+
+use core::Chip8;
+use disasm::disassemble;
+use font::GLYPH_HEIGHT;
+use peripherals::{Chip8Input, Chip8Key};
+use sdl2_overlay::draw_text;
+use sdl2_peripherals::{Display, Keyboard};
+
+/// Size of a font pixel in the debugger window.
+const UNIT: i32 = 2;
+const LINE_HEIGHT: i32 = (GLYPH_HEIGHT as i32 + 3) * UNIT;
+const MARGIN: i32 = 8;
+
+const COLUMNS: u32 = 72;
+const ROWS: u32 = 34;
+
+/// Instructions listed before and after the one in the middle of the
+/// disassembly.
+const DISASM_BEFORE: u16 = 8;
+const DISASM_AFTER: u16 = 10;
+
+const MEMORY_ROWS: usize = 10;
+
+/// Frames a write stays highlighted in the memory view.
+const WRITE_FADE: u8 = 60;
+
+const BACKGROUND: Color = Color { r: 0x18, g: 0x18, b: 0x20, a: 0xFF };
+const TEXT: Color = Color { r: 0xD0, g: 0xD0, b: 0xD0, a: 0xFF };
+const LABEL: Color = Color { r: 0x80, g: 0x80, b: 0x90, a: 0xFF };
+const CURRENT: Color = Color { r: 0xFF, g: 0xD0, b: 0x40, a: 0xFF };
+const BREAKPOINT: Color = Color { r: 0xFF, g: 0x50, b: 0x50, a: 0xFF };
+const SPRITE: Color = Color { r: 0x50, g: 0xC0, b: 0xFF, a: 0xFF };
+const PRESSED: Color = Color { r: 0x60, g: 0xFF, b: 0x60, a: 0xFF };
+
+/// Keypad layout of the COSMAC VIP.
+const KEYPAD: [[Chip8Key; 4]; 4] = [
+    [Chip8Key::Key1, Chip8Key::Key2, Chip8Key::Key3, Chip8Key::KeyC],
+    [Chip8Key::Key4, Chip8Key::Key5, Chip8Key::Key6, Chip8Key::KeyD],
+    [Chip8Key::Key7, Chip8Key::Key8, Chip8Key::Key9, Chip8Key::KeyE],
+    [Chip8Key::KeyA, Chip8Key::Key0, Chip8Key::KeyB, Chip8Key::KeyF],
+];
+
+/// A second window showing the machine state while a ROM runs: registers,
+/// stack, timers, keypad, disassembly around the PC and a memory view.
+///
+/// The disassembly follows the PC and the memory view follows I until they
+/// are scrolled with the arrow and page keys, Home goes back to following.
+pub struct Debugger {
+    // Selected instruction, None follows the PC
+    cursor: Option<u16>,
+    // First row of the memory view, None follows I
+    memory_start: Option<u16>,
+
+    // Memory as of the last update and frames since each byte changed
+    previous: Vec<u8>,
+    write_age: Vec<u8>,
+
+    canvas: Canvas<sdl2::video::Window>,
+}
+
+impl Debugger {
+    pub fn new(video_subsystem: &sdl2::VideoSubsystem) -> Result<Debugger, String> {
+        let width = COLUMNS as i32 * 6 * UNIT + 2 * MARGIN;
+        let height = ROWS as i32 * LINE_HEIGHT + 2 * MARGIN;
+        let window = video_subsystem.window("chip8 debugger", width as u32, height as u32)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+
+        Ok(Debugger {
+            cursor: None,
+            memory_start: None,
+            previous: Vec::new(),
+            write_age: Vec::new(),
+            canvas: canvas,
+        })
+    }
+
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    /// The selected instruction, breakpoints are toggled here.
+    pub fn cursor(&self, chip8: &Chip8<Display, Keyboard>) -> u16 {
+        self.cursor.unwrap_or_else(|| chip8.pc())
+    }
+
+    /// Scroll the views. Returns false for keys the debugger doesn't use.
+    pub fn navigate(&mut self, key: Keycode, chip8: &Chip8<Display, Keyboard>) -> bool {
+        let cursor = self.cursor(chip8);
+        let memory_start = self.memory_start(chip8);
+        let page = (MEMORY_ROWS * 16) as u16;
+
+        match key {
+            Keycode::Up => self.cursor = Some(cursor.saturating_sub(2)),
+            Keycode::Down => self.cursor = Some((cursor + 2).min(0xFFE)),
+            Keycode::PageUp => self.memory_start = Some(memory_start.saturating_sub(page)),
+            Keycode::PageDown => {
+                self.memory_start = Some((memory_start + page).min(0x1000 - page));
+            }
+            Keycode::Home => {
+                self.cursor = None;
+                self.memory_start = None;
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn memory_start(&self, chip8: &Chip8<Display, Keyboard>) -> u16 {
+        match self.memory_start {
+            Some(start) => start,
+            None => {
+                // Show I on the second row
+                let row = chip8.index() & !0xF;
+                row.saturating_sub(16).min(0x1000 - (MEMORY_ROWS * 16) as u16)
+            }
+        }
+    }
+
+    /// Track memory writes, called once per real frame.
+    pub fn update(&mut self, chip8: &Chip8<Display, Keyboard>) {
+        let memory = chip8.memory();
+        if self.previous.len() != memory.len() {
+            self.previous = memory.to_vec();
+            self.write_age = vec![WRITE_FADE; memory.len()];
+            return;
+        }
+
+        for (idx, (old, new)) in self.previous.iter_mut().zip(memory.iter()).enumerate() {
+            if old != new {
+                *old = *new;
+                self.write_age[idx] = 0;
+            } else if self.write_age[idx] < WRITE_FADE {
+                self.write_age[idx] += 1;
+            }
+        }
+    }
+
+    pub fn draw(&mut self, chip8: &Chip8<Display, Keyboard>, paused: bool) {
+        self.canvas.set_draw_color(BACKGROUND);
+        self.canvas.clear();
+
+        self.draw_registers(chip8);
+        self.draw_keypad(chip8);
+        self.draw_disassembly(chip8);
+        self.draw_memory(chip8);
+
+        let help = if paused {
+            "PAUSED   S STEP  SPACE RUN  B BREAKPOINT  HOME FOLLOW"
+        } else {
+            "RUNNING  S STEP  SPACE PAUSE  B BREAKPOINT  HOME FOLLOW"
+        };
+        self.text(0, ROWS as i32 - 1, help, LABEL);
+
+        self.canvas.present();
+    }
+
+    /// Draw text at a character column and row.
+    fn text(&mut self, column: i32, row: i32, text: &str, color: Color) {
+        let x = MARGIN + column * 6 * UNIT;
+        let y = MARGIN + row * LINE_HEIGHT;
+        draw_text(&mut self.canvas, x, y, UNIT, text, color);
+    }
+
+    fn draw_registers(&mut self, chip8: &Chip8<Display, Keyboard>) {
+        let registers = chip8.registers();
+        for (idx, value) in registers.iter().enumerate() {
+            let column = (idx % 4) as i32 * 7;
+            let row = (idx / 4) as i32;
+            self.text(column, row, &format!("V{:X}", idx), LABEL);
+            self.text(column + 3, row, &format!("{:02X}", value), TEXT);
+        }
+
+        let fields = [
+            ("PC", format!("{:03X}", chip8.pc())),
+            ("I", format!("{:03X}", chip8.index())),
+            ("SP", format!("{:X}", chip8.stack_ptr())),
+            ("DT", format!("{:02X}", chip8.delay_timer())),
+            ("ST", format!("{:02X}", chip8.sound_timer())),
+        ];
+        for (idx, &(label, ref value)) in fields.iter().enumerate() {
+            let column = (idx % 3) as i32 * 9;
+            let row = 5 + (idx / 3) as i32;
+            self.text(column, row, label, LABEL);
+            self.text(column + 3, row, value, TEXT);
+        }
+
+        self.text(0, 8, "STACK", LABEL);
+        let stack_ptr = chip8.stack_ptr() as usize;
+        for (idx, addr) in chip8.stack().iter().enumerate() {
+            let column = (idx % 4) as i32 * 7;
+            let row = 9 + (idx / 4) as i32;
+            // Entries below the stack pointer are in use
+            let color = if idx < stack_ptr { TEXT } else { LABEL };
+            self.text(column, row, &format!("{:X}:{:03X}", idx, addr), color);
+        }
+    }
+
+    fn draw_keypad(&mut self, chip8: &Chip8<Display, Keyboard>) {
+        self.text(0, 14, "KEYPAD", LABEL);
+        for (row, keys) in KEYPAD.iter().enumerate() {
+            for (column, key) in keys.iter().enumerate() {
+                let pressed = chip8.keyboard().is_some_and(|keyboard| keyboard.key_pressed(*key));
+                let color = if pressed { PRESSED } else { LABEL };
+                self.text(column as i32 * 2, 15 + row as i32, &format!("{:X}", *key as u8), color);
+            }
+        }
+    }
+
+    fn draw_disassembly(&mut self, chip8: &Chip8<Display, Keyboard>) {
+        let left = 30;
+        let pc = chip8.pc();
+        let cursor = self.cursor(chip8);
+        let first = cursor.saturating_sub(DISASM_BEFORE * 2);
+
+        for line in 0..(DISASM_BEFORE + DISASM_AFTER + 1) {
+            let addr = first + line * 2;
+            if addr as usize + 1 >= chip8.memory().len() {
+                break;
+            }
+
+            let breakpoint = chip8.breakpoints().contains(&addr);
+            let marker = match (addr == pc, addr == cursor && self.cursor.is_some()) {
+                (true, _) => ">",
+                (false, true) => "-",
+                _ => " ",
+            };
+            let color = if addr == pc {
+                CURRENT
+            } else if breakpoint {
+                BREAKPOINT
+            } else {
+                TEXT
+            };
+
+            let row = line as i32;
+            if breakpoint {
+                self.text(left, row, "*", BREAKPOINT);
+            }
+            let memory = chip8.memory();
+            let word = ((memory[addr as usize] as u16) << 8) | memory[addr as usize + 1] as u16;
+            let text = format!("{}{:03X} {:04X} {}", marker, addr, word, disassemble(memory, addr));
+            self.text(left + 1, row, &text, color);
+        }
+    }
+
+    fn draw_memory(&mut self, chip8: &Chip8<Display, Keyboard>) {
+        let top = 20;
+        let start = self.memory_start(chip8) as usize;
+
+        // Bytes read by the last sprite drawn
+        let sprite = chip8.last_draw()
+            .map_or(0..0, |draw| draw.index as usize..(draw.index as usize + draw.height as usize));
+        let index = chip8.index() as usize;
+
+        self.text(0, top - 1, "MEMORY", LABEL);
+        for row in 0..MEMORY_ROWS {
+            let addr = start + row * 16;
+            let y = top + row as i32;
+            self.text(0, y, &format!("{:03X}", addr), LABEL);
+
+            for col in 0..16 {
+                let idx = addr + col;
+                let value = chip8.memory()[idx];
+                let age = self.write_age.get(idx).cloned().unwrap_or(WRITE_FADE);
+                let color = if age < WRITE_FADE {
+                    fade(BREAKPOINT, TEXT, age as f32 / WRITE_FADE as f32)
+                } else if sprite.start <= idx && idx < sprite.end {
+                    SPRITE
+                } else if idx == index {
+                    CURRENT
+                } else {
+                    TEXT
+                };
+                self.text(4 + col as i32 * 3, y, &format!("{:02X}", value), color);
+            }
+        }
+
+        let legend_x = 4 + 16 * 3 + 1;
+        let legend = [("WRITE", BREAKPOINT), ("SPRITE", SPRITE), ("I", CURRENT)];
+        for (idx, &(label, color)) in legend.iter().enumerate() {
+            self.text(legend_x, top + idx as i32, label, color);
+        }
+    }
+}
+
+/// Blend from `from` to `to` as `amount` goes from 0 to 1.
+fn fade(from: Color, to: Color, amount: f32) -> Color {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+    Color::RGB(mix(from.r, to.r), mix(from.g, to.g), mix(from.b, to.b))
+}
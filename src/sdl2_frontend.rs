@@ -0,0 +1,793 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use sdl2::keyboard::Keycode;
+
+use config::{self, Config, Palette, SettingsSource, PALETTE_NAMES};
+use core::{Chip8, FRAME_BYTES, MAX_PROGRAM_SIZE, STATE_SIZE};
+use error::Error;
+use peripherals::Chip8Key;
+use sdl2_debugger::Debugger;
+use sdl2_overlay::Menu;
+use sdl2_sprite_viewer::SpriteViewer;
+use sdl2_gamepad::GamepadProfile;
+use sdl2_peripherals::{Beeper, Display, Hotkey, Indicator, Keyboard};
+use timing::FramePacer;
+
+/// Real frames per emulated frame in slow motion.
+const SLOW_MOTION_FACTOR: u32 = 4;
+
+const SAVE_SLOTS: u8 = 10;
+
+/// File extensions listed by the "Load ROM" menu.
+const ROM_EXTENSIONS: [&str; 3] = ["ch8", "c8", "rom"];
+
+/// Chip-8 keys in the order they are asked for when remapping, row by row
+/// on the original keypad.
+const KEYPAD_ORDER: [Chip8Key; 16] = [
+    Chip8Key::Key1, Chip8Key::Key2, Chip8Key::Key3, Chip8Key::KeyC,
+    Chip8Key::Key4, Chip8Key::Key5, Chip8Key::Key6, Chip8Key::KeyD,
+    Chip8Key::Key7, Chip8Key::Key8, Chip8Key::Key9, Chip8Key::KeyE,
+    Chip8Key::KeyA, Chip8Key::Key0, Chip8Key::KeyB, Chip8Key::KeyF,
+];
+
+// Entries of the main menu
+const RESUME: usize = 0;
+const LOAD_ROM: usize = 1;
+const PALETTE: usize = 2;
+const REMAP_KEYS: usize = 3;
+const QUIT: usize = 4;
+
+/// Pages of the pause menu.
+enum MenuPage {
+    Main,
+    Roms(Vec<PathBuf>),
+    /// Waiting for the key to bind to `KEYPAD_ORDER[n]`.
+    Remap(usize),
+}
+
+/// Runs a `Chip8` in an SDL window, handling the emulator hotkeys for pause,
+/// frame advance, fast-forward, slow motion, speed changes and save states,
+/// the pause menu and the debugger and sprite viewer windows.
+///
+/// Speed changes only affect how many emulated frames run per real frame,
+/// the timers always tick once per emulated frame.
+pub struct Frontend {
+    chip8: Chip8<Display, Keyboard>,
+    beeper: Option<Beeper>,
+    settings: SettingsSource,
+    title: String,
+    rom: PathBuf,
+    rom_id: String,
+
+    paused: bool,
+    step: bool,
+    fast_forward: bool,
+    slow_motion: bool,
+    frame_count: u32,
+    quit: bool,
+
+    menu: Option<MenuPage>,
+    selected: usize,
+    show_status: bool,
+    slot: u8,
+    debugger: Option<Debugger>,
+    sprite_viewer: Option<SpriteViewer>,
+
+    // Emulated frames per second, measured over the last second
+    fps: u32,
+    fps_frames: u32,
+    fps_since: Instant,
+}
+
+impl Frontend {
+    /// `rom` and `rom_id` identify the loaded ROM, they are used to find
+    /// other ROMs to load and to name save states. ROMs loaded from the menu
+    /// get their settings from `settings`.
+    pub fn new(chip8: Chip8<Display, Keyboard>,
+               beeper: Option<Beeper>,
+               settings: SettingsSource,
+               title: &str,
+               rom: &Path,
+               rom_id: &str) -> Frontend {
+        let mut frontend = Frontend {
+            chip8: chip8,
+            beeper: beeper,
+            settings: settings,
+            title: title.to_string(),
+            rom: rom.to_path_buf(),
+            rom_id: rom_id.to_string(),
+
+            paused: false,
+            step: false,
+            fast_forward: false,
+            slow_motion: false,
+            frame_count: 0,
+            quit: false,
+
+            menu: None,
+            selected: 0,
+            show_status: false,
+            slot: 0,
+            debugger: None,
+            sprite_viewer: None,
+
+            fps: 0,
+            fps_frames: 0,
+            fps_since: Instant::now(),
+        };
+        frontend.update_title();
+        frontend
+    }
+
+    pub fn run(&mut self) {
+        let mut pacer = FramePacer::new();
+
+        loop {
+            if self.chip8.poll_input() {
+                break;
+            }
+            self.handle_hotkeys();
+            if self.quit {
+                break;
+            }
+
+            if self.menu.is_some() {
+                // The pause menu stops emulation entirely
+            } else if self.paused {
+                if self.step {
+                    self.run_frame();
+                    self.step = false;
+                }
+            } else if self.fast_forward {
+                // Uncapped: run frames for as long as this real frame lasts
+                let deadline = pacer.deadline();
+                if self.run_frame() {
+                    while Instant::now() < deadline && self.run_frame() {}
+                }
+            } else if self.slow_motion {
+                if self.frame_count.is_multiple_of(SLOW_MOTION_FACTOR) {
+                    self.run_frame();
+                }
+            } else {
+                self.run_frame();
+            }
+            self.frame_count = self.frame_count.wrapping_add(1);
+
+            let stopped = self.paused || self.menu.is_some();
+            let sound = !stopped && self.chip8.sound_active();
+            if let Some(ref mut beeper) = self.beeper {
+                beeper.set_playing(sound);
+            }
+
+            let indicator = self.indicator();
+            let status = self.status();
+            if let Some(display) = self.chip8.display_mut() {
+                display.set_indicator(indicator);
+                display.overlay_mut().set_status(status);
+            }
+            self.chip8.present();
+
+            if let Some(ref mut debugger) = self.debugger {
+                debugger.update(&self.chip8);
+                debugger.draw(&self.chip8, stopped);
+            }
+            if let Some(ref mut viewer) = self.sprite_viewer {
+                viewer.draw(&self.chip8);
+            }
+
+            if !self.fast_forward || stopped {
+                pacer.wait();
+            } else {
+                pacer = FramePacer::new();
+            }
+        }
+    }
+
+    /// Run an emulated frame, pausing when it stops at a breakpoint or the
+    /// program faults. Returns false if it did.
+    fn run_frame(&mut self) -> bool {
+        self.chip8.run_frame();
+        self.fps_frames += 1;
+
+        if self.check_fault() {
+            return false;
+        }
+        if self.chip8.breakpoint_hit() {
+            self.paused = true;
+            let message = format!("Breakpoint at {:03X}", self.chip8.pc());
+            self.notify(&message);
+            return false;
+        }
+        true
+    }
+
+    /// Pause and report a fault that halted the machine. Returns true if
+    /// there was one.
+    fn check_fault(&mut self) -> bool {
+        match self.chip8.fault() {
+            Some(fault) => {
+                if !self.paused {
+                    self.paused = true;
+                    error!("{}", fault);
+                    self.notify(&fault.to_string());
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Open the debugger window, or close it if it is open.
+    pub fn toggle_debugger(&mut self) {
+        if self.debugger.take().is_none() {
+            let video_subsystem = match self.chip8.display() {
+                Some(display) => display.video_subsystem(),
+                None => return,
+            };
+            match Debugger::new(&video_subsystem) {
+                Ok(debugger) => self.debugger = Some(debugger),
+                Err(e) => warn!("Unable to open the debugger: {}", e),
+            }
+        }
+
+        let window_id = self.debugger.as_ref().map(|debugger| debugger.window_id());
+        if let Some(keyboard) = self.chip8.keyboard_mut() {
+            keyboard.set_debug_window(window_id);
+        }
+    }
+
+    /// Open the sprite viewer window, or close it if it is open.
+    pub fn toggle_sprite_viewer(&mut self) {
+        if self.sprite_viewer.take().is_none() {
+            let video_subsystem = match self.chip8.display() {
+                Some(display) => display.video_subsystem(),
+                None => return,
+            };
+            match SpriteViewer::new(&video_subsystem) {
+                Ok(viewer) => self.sprite_viewer = Some(viewer),
+                Err(e) => warn!("Unable to open the sprite viewer: {}", e),
+            }
+        }
+
+        let window_id = self.sprite_viewer.as_ref().map(|viewer| viewer.window_id());
+        if let Some(keyboard) = self.chip8.keyboard_mut() {
+            keyboard.set_sprite_window(window_id);
+        }
+    }
+
+    fn sprite_key(&mut self, key: Keycode) {
+        match key {
+            Keycode::D => {
+                let (addr, text) = match self.sprite_viewer {
+                    Some(ref viewer) => viewer.selected_sprite(&self.chip8),
+                    None => return,
+                };
+                info!("Sprite at {:#05X}:\n{}", addr, text);
+                let message = format!("Sprite at {:03X} logged", addr);
+                self.notify(&message);
+            }
+            Keycode::Escape | Keycode::F11 => self.toggle_sprite_viewer(),
+            key => {
+                if let Some(ref mut viewer) = self.sprite_viewer {
+                    viewer.handle_key(key, &self.chip8);
+                }
+            }
+        }
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Apply the quirks, speed, CPU backend, palette, keymap and gamepad
+    /// profile of `config`, the settings that can change while running.
+    /// Stops at the first invalid setting.
+    pub fn configure(&mut self, config: &Config, rom: &Path) -> Result<(), String> {
+        let quirks = config.quirks.quirks()?;
+        let backend = config.cpu.backend()?;
+        let palette = config.display.palette()?;
+        let profile = GamepadProfile::from_config(&config.gamepad, &rom.to_string_lossy())?;
+
+        if let Some(keyboard) = self.chip8.keyboard_mut() {
+            keyboard.reset_keymap();
+            keyboard.set_keymap(&config.keymap)?;
+            if let Some(gamepad) = keyboard.gamepad_mut() {
+                gamepad.set_profile(profile);
+            }
+        }
+        if let Some(display) = self.chip8.display_mut() {
+            display.set_palette(palette);
+        }
+        self.chip8.set_quirks(quirks);
+        self.chip8.set_cycles_per_frame(config.cpu.speed);
+        self.chip8.set_decode_cache(config.cpu.decode_cache);
+        self.chip8.set_backend(backend);
+        Ok(())
+    }
+
+    fn debug_key(&mut self, key: Keycode) {
+        match key {
+            Keycode::S | Keycode::F10 => {
+                self.paused = true;
+                self.chip8.step();
+                self.check_fault();
+            }
+            Keycode::Space => {
+                self.paused = !self.paused;
+                info!("Paused: {}", self.paused);
+            }
+            Keycode::B | Keycode::F9 => {
+                let addr = match self.debugger {
+                    Some(ref debugger) => debugger.cursor(&self.chip8),
+                    None => return,
+                };
+                let enabled = !self.chip8.breakpoints().contains(&addr);
+                self.chip8.set_breakpoint(addr, enabled);
+                info!("Breakpoint at {:#05X}: {}", addr, enabled);
+            }
+            Keycode::Escape | Keycode::F12 => self.toggle_debugger(),
+            key => {
+                if let Some(ref mut debugger) = self.debugger {
+                    debugger.navigate(key, &self.chip8);
+                }
+            }
+        }
+    }
+
+    fn handle_hotkeys(&mut self) {
+        let hotkeys = match self.chip8.keyboard_mut() {
+            Some(keyboard) => keyboard.take_hotkeys(),
+            None => return,
+        };
+
+        for hotkey in hotkeys {
+            match hotkey {
+                Hotkey::Key(key) => self.menu_key(key),
+                Hotkey::DebugKey(key) => self.debug_key(key),
+                Hotkey::Debugger => self.toggle_debugger(),
+                Hotkey::SpriteKey(key) => self.sprite_key(key),
+                Hotkey::SpriteViewer => self.toggle_sprite_viewer(),
+                Hotkey::Menu => self.open_menu(),
+                Hotkey::Status => self.show_status = !self.show_status,
+                Hotkey::SaveState => self.save_state(),
+                Hotkey::LoadState => self.load_state(),
+                Hotkey::NextSlot => {
+                    self.slot = (self.slot + 1) % SAVE_SLOTS;
+                    let empty = self.slot_path().is_none_or(|path| !path.exists());
+                    let message = format!("Slot {}{}", self.slot, if empty { " (empty)" } else { "" });
+                    self.notify(&message);
+                }
+                Hotkey::Pause => {
+                    self.paused = !self.paused;
+                    info!("Paused: {}", self.paused);
+                }
+                Hotkey::FrameAdvance => {
+                    self.paused = true;
+                    self.step = true;
+                }
+                Hotkey::FastForward(on) => self.fast_forward = on,
+                Hotkey::SlowMotion => {
+                    self.slow_motion = !self.slow_motion;
+                    info!("Slow motion: {}", self.slow_motion);
+                }
+                Hotkey::SpeedUp | Hotkey::SpeedDown => {
+                    let speed = self.chip8.cycles_per_frame();
+                    let speed = if hotkey == Hotkey::SpeedUp {
+                        speed + 1
+                    } else {
+                        speed.saturating_sub(1).max(1)
+                    };
+                    self.chip8.set_cycles_per_frame(speed);
+                    info!("Speed: {} instructions per frame", speed);
+                    self.update_title();
+                }
+            }
+        }
+    }
+
+    fn indicator(&self) -> Option<Indicator> {
+        if self.paused || self.menu.is_some() {
+            Some(Indicator::Paused)
+        } else if self.fast_forward {
+            Some(Indicator::FastForward)
+        } else if self.slow_motion {
+            Some(Indicator::SlowMotion)
+        } else {
+            None
+        }
+    }
+
+    /// The status line: emulated FPS, speed and quirks preset.
+    fn status(&mut self) -> Option<String> {
+        let elapsed = self.fps_since.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            let secs = elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 * 1e-9;
+            self.fps = (self.fps_frames as f32 / secs).round() as u32;
+            self.fps_frames = 0;
+            self.fps_since = Instant::now();
+        }
+
+        if !self.show_status {
+            return None;
+        }
+
+        let quirks = self.chip8.quirks();
+        Some(format!("{} FPS  {} IPF  {}", self.fps, self.chip8.cycles_per_frame(),
+                     quirks.preset_name().unwrap_or("custom")))
+    }
+
+    fn notify(&mut self, message: &str) {
+        if let Some(display) = self.chip8.display_mut() {
+            display.overlay_mut().notify(message);
+        }
+    }
+
+    fn update_title(&mut self) {
+        let title = format!("{} [{} ipf]", self.title, self.chip8.cycles_per_frame());
+        if let Some(display) = self.chip8.display_mut() {
+            display.set_title(&title);
+        }
+    }
+
+    fn open_menu(&mut self) {
+        if let Some(keyboard) = self.chip8.keyboard_mut() {
+            keyboard.set_capture(true);
+        }
+        self.selected = RESUME;
+        self.menu = Some(MenuPage::Main);
+        self.show_menu();
+    }
+
+    fn close_menu(&mut self) {
+        if let Some(keyboard) = self.chip8.keyboard_mut() {
+            keyboard.set_capture(false);
+        }
+        self.menu = None;
+        self.show_menu();
+    }
+
+    /// Handle a key pressed while the menu is open.
+    fn menu_key(&mut self, key: Keycode) {
+        let page = match self.menu.take() {
+            Some(page) => page,
+            None => return,
+        };
+
+        let next = match page {
+            MenuPage::Remap(idx) => self.remap_key(idx, key),
+            page => {
+                let len = self.menu_items(&page).1.len();
+                match key {
+                    Keycode::Up => {
+                        self.selected = (self.selected + len - 1) % len;
+                        Some(page)
+                    }
+                    Keycode::Down => {
+                        self.selected = (self.selected + 1) % len;
+                        Some(page)
+                    }
+                    Keycode::Left | Keycode::Right if self.on_palette(&page) => {
+                        self.cycle_palette(key == Keycode::Right);
+                        Some(page)
+                    }
+                    Keycode::Return | Keycode::KpEnter | Keycode::Space => self.select(page),
+                    Keycode::Escape | Keycode::Backspace => match page {
+                        MenuPage::Main => None,
+                        _ => {
+                            self.selected = LOAD_ROM;
+                            Some(MenuPage::Main)
+                        }
+                    },
+                    _ => Some(page),
+                }
+            }
+        };
+
+        if self.quit {
+            return;
+        }
+        match next {
+            Some(page) => {
+                self.menu = Some(page);
+                self.show_menu();
+            }
+            None => self.close_menu(),
+        }
+    }
+
+    fn on_palette(&self, page: &MenuPage) -> bool {
+        match *page {
+            MenuPage::Main => self.selected == PALETTE,
+            _ => false,
+        }
+    }
+
+    /// Activate the selected entry, returning the page to show next or
+    /// `None` to close the menu.
+    fn select(&mut self, page: MenuPage) -> Option<MenuPage> {
+        match page {
+            MenuPage::Main => match self.selected {
+                RESUME => None,
+                LOAD_ROM => {
+                    self.selected = 0;
+                    Some(MenuPage::Roms(self.list_roms()))
+                }
+                PALETTE => {
+                    self.cycle_palette(true);
+                    Some(MenuPage::Main)
+                }
+                REMAP_KEYS => Some(MenuPage::Remap(0)),
+                _ => {
+                    self.quit = true;
+                    None
+                }
+            },
+            MenuPage::Roms(roms) => match roms.get(self.selected).cloned() {
+                Some(path) => {
+                    self.load_rom(&path);
+                    None
+                }
+                None => Some(MenuPage::Roms(roms)),
+            },
+            page => Some(page),
+        }
+    }
+
+    fn remap_key(&mut self, idx: usize, key: Keycode) -> Option<MenuPage> {
+        self.selected = REMAP_KEYS;
+        if key == Keycode::Escape {
+            self.notify("Remapping cancelled");
+            return Some(MenuPage::Main);
+        }
+
+        if let Some(keyboard) = self.chip8.keyboard_mut() {
+            keyboard.bind_key(KEYPAD_ORDER[idx], key);
+        }
+
+        if idx + 1 < KEYPAD_ORDER.len() {
+            Some(MenuPage::Remap(idx + 1))
+        } else {
+            self.notify("Keys remapped");
+            Some(MenuPage::Main)
+        }
+    }
+
+    /// Title and entries of a menu page.
+    fn menu_items(&self, page: &MenuPage) -> (String, Vec<String>) {
+        match *page {
+            MenuPage::Main => {
+                let palette = self.palette_index()
+                    .map_or("custom", |idx| PALETTE_NAMES[idx]);
+                let mut items = vec![String::new(); QUIT + 1];
+                items[RESUME] = "Resume".to_string();
+                items[LOAD_ROM] = "Load ROM".to_string();
+                items[PALETTE] = format!("Palette: < {} >", palette);
+                items[REMAP_KEYS] = "Remap keys".to_string();
+                items[QUIT] = "Quit".to_string();
+                ("Paused".to_string(), items)
+            }
+            MenuPage::Roms(ref roms) => {
+                let mut items: Vec<String> = roms.iter()
+                    .map(|path| file_name(path))
+                    .collect();
+                if items.is_empty() {
+                    items.push("(no ROMs found)".to_string());
+                }
+                ("Load ROM".to_string(), items)
+            }
+            MenuPage::Remap(idx) => {
+                let key = KEYPAD_ORDER[idx];
+                let current = self.chip8.keyboard()
+                    .and_then(|keyboard| keyboard.binding(key))
+                    .map_or("none".to_string(), |code| code.name());
+                let items = vec![
+                    format!("Press a key for {:X}", key as u8),
+                    format!("Currently: {}", current),
+                    "Escape cancels".to_string(),
+                ];
+                ("Remap keys".to_string(), items)
+            }
+        }
+    }
+
+    fn show_menu(&mut self) {
+        let menu = self.menu.as_ref().map(|page| {
+            let (title, items) = self.menu_items(page);
+            let selected = match *page {
+                MenuPage::Remap(_) => 0,
+                _ => self.selected,
+            };
+            Menu {
+                title: title,
+                items: items,
+                selected: selected,
+            }
+        });
+
+        if let Some(display) = self.chip8.display_mut() {
+            display.overlay_mut().set_menu(menu);
+        }
+    }
+
+    fn palette_index(&self) -> Option<usize> {
+        let current = match self.chip8.display() {
+            Some(display) => display.palette(),
+            None => return None,
+        };
+        PALETTE_NAMES.iter().position(|name| Palette::preset(name) == Some(current))
+    }
+
+    fn cycle_palette(&mut self, forward: bool) {
+        let count = PALETTE_NAMES.len();
+        let next = match (self.palette_index(), forward) {
+            (Some(idx), true) => (idx + 1) % count,
+            (Some(idx), false) => (idx + count - 1) % count,
+            (None, _) => 0,
+        };
+
+        if let (Some(display), Some(palette)) = (self.chip8.display_mut(),
+                                                 Palette::preset(PALETTE_NAMES[next])) {
+            display.set_palette(palette);
+        }
+    }
+
+    /// ROMs in the same directory as the current one.
+    fn list_roms(&self) -> Vec<PathBuf> {
+        let dir = match self.rom.parent() {
+            Some(dir) if dir != Path::new("") => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Unable to list {}: {}", dir.display(), e);
+                return Vec::new();
+            }
+        };
+
+        let mut roms: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .filter(|path| {
+                path.extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| {
+                        ROM_EXTENSIONS.iter().any(|rom_ext| ext.eq_ignore_ascii_case(rom_ext))
+                    })
+            })
+            .collect();
+        roms.sort();
+        roms
+    }
+
+    /// Replace the running ROM, configured like the first one was: from its
+    /// recommended settings, the config file and the command line.
+    fn load_rom(&mut self, path: &Path) {
+        let name = file_name(path);
+        let mut program = Vec::new();
+        if let Err(e) = File::open(path).and_then(|mut file| file.read_to_end(&mut program)) {
+            warn!("Unable to read {}: {}", path.display(), e);
+            self.notify(&format!("Unable to read {}", name));
+            return;
+        }
+
+        // Checked first so that the running ROM is left untouched
+        if program.len() > MAX_PROGRAM_SIZE {
+            warn!("Unable to load {}: {}", path.display(), Error::ProgramTooLong(program.len()));
+            self.notify(&format!("{} is too large", name));
+            return;
+        }
+
+        let settings = match self.settings.resolve(&program) {
+            Ok(settings) => settings,
+            Err(e) => {
+                warn!("Unable to load {}: {}", path.display(), e);
+                self.notify("Invalid config file");
+                return;
+            }
+        };
+        settings.log();
+        if let Err(e) = self.configure(&settings.config, path) {
+            warn!("Invalid settings for {}: {}", path.display(), e);
+            self.notify("Invalid config file");
+            return;
+        }
+
+        self.chip8.reset();
+        self.chip8.upload_rom(&program).expect("Invalid program length");
+
+        self.rom = path.to_path_buf();
+        self.rom_id = settings.rom_id;
+        self.title = format!("chip8 - {}", settings.info.title().unwrap_or_else(|| name.clone()));
+        self.paused = false;
+        self.update_title();
+        self.notify(&format!("Loaded {}", name));
+    }
+
+    /// `$XDG_DATA_HOME/chip8/saves/<rom sha1>.<slot>.state`
+    fn slot_path(&self) -> Option<PathBuf> {
+        config::data_dir().map(|dir| {
+            dir.join("saves").join(format!("{}.{}.state", self.rom_id, self.slot))
+        })
+    }
+
+    /// Write the machine state followed by the screen contents to the
+    /// current slot.
+    fn save_state(&mut self) {
+        let path = match self.slot_path() {
+            Some(path) => path,
+            None => {
+                self.notify("No directory for save states");
+                return;
+            }
+        };
+
+        let mut state = self.chip8.save_state();
+        state.extend(self.chip8.save_frame());
+
+        let result = path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| File::create(&path))
+            .and_then(|mut file| file.write_all(&state));
+
+        let message = match result {
+            Ok(()) => format!("Saved slot {}", self.slot),
+            Err(e) => {
+                warn!("Unable to write {}: {}", path.display(), e);
+                format!("Unable to save slot {}", self.slot)
+            }
+        };
+        self.notify(&message);
+    }
+
+    fn load_state(&mut self) {
+        let path = match self.slot_path() {
+            Some(path) => path,
+            None => {
+                self.notify("No directory for save states");
+                return;
+            }
+        };
+
+        let mut state = Vec::new();
+        match File::open(&path).and_then(|mut file| file.read_to_end(&mut state)) {
+            Ok(_) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                let message = format!("Slot {} is empty", self.slot);
+                self.notify(&message);
+                return;
+            }
+            Err(e) => {
+                warn!("Unable to read {}: {}", path.display(), e);
+                let message = format!("Unable to load slot {}", self.slot);
+                self.notify(&message);
+                return;
+            }
+        }
+
+        let result = if state.len() == STATE_SIZE + FRAME_BYTES {
+            let (machine, frame) = state.split_at(STATE_SIZE);
+            self.chip8.load_state(machine).and_then(|_| self.chip8.load_frame(frame))
+        } else {
+            Err(Error::NotAState)
+        };
+
+        let message = match result {
+            Ok(()) => format!("Loaded slot {}", self.slot),
+            Err(e) => {
+                warn!("Unable to load {}: {}", path.display(), e);
+                format!("Unable to load slot {}", self.slot)
+            }
+        };
+        self.notify(&message);
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned())
+}
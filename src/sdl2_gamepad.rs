@@ -0,0 +1,279 @@
+use sdl2;
+use sdl2::event::Event;
+use sdl2::controller::{Axis, Button, GameController};
+
+use config::GamepadConfig;
+use peripherals::Chip8Key;
+
+/// Mapping from controller inputs to Chip-8 keys.
+///
+/// The D-pad and the left stick share the four direction slots, every other
+/// button is mapped individually.
+#[derive(Clone, Debug)]
+pub struct GamepadProfile {
+    pub name: String,
+    pub up: Option<Chip8Key>,
+    pub down: Option<Chip8Key>,
+    pub left: Option<Chip8Key>,
+    pub right: Option<Chip8Key>,
+    pub buttons: Vec<(Button, Chip8Key)>,
+}
+
+impl Default for GamepadProfile {
+    fn default() -> GamepadProfile {
+        GamepadProfile {
+            name: "default".to_string(),
+            up: Some(Chip8Key::Key2),
+            down: Some(Chip8Key::Key8),
+            left: Some(Chip8Key::Key4),
+            right: Some(Chip8Key::Key6),
+            buttons: vec![
+                (Button::A, Chip8Key::Key5),
+                (Button::B, Chip8Key::Key0),
+                (Button::X, Chip8Key::Key7),
+                (Button::Y, Chip8Key::Key9),
+                (Button::LeftShoulder, Chip8Key::Key1),
+                (Button::RightShoulder, Chip8Key::Key3),
+                (Button::Back, Chip8Key::KeyA),
+                (Button::Start, Chip8Key::KeyB),
+            ],
+        }
+    }
+}
+
+impl GamepadProfile {
+    /// Look up one of the built-in profiles by name, ignoring case.
+    pub fn builtin(name: &str) -> Option<GamepadProfile> {
+        use self::Chip8Key::*;
+
+        let profile = |name: &str, up, down, left, right, buttons| GamepadProfile {
+            name: name.to_string(),
+            up: up,
+            down: down,
+            left: left,
+            right: right,
+            buttons: buttons,
+        };
+
+        match name.to_lowercase().as_str() {
+            "default" => Some(GamepadProfile::default()),
+            "brix" | "breakout" => Some(profile("brix",
+                None, None, Some(Key4), Some(Key6),
+                vec![])),
+            "invaders" => Some(profile("invaders",
+                None, None, Some(Key4), Some(Key6),
+                vec![(Button::A, Key5)])),
+            "pong" | "pong2" => Some(profile("pong",
+                Some(Key1), Some(Key4), None, None,
+                vec![])),
+            "tetris" => Some(profile("tetris",
+                None, Some(Key7), Some(Key5), Some(Key6),
+                vec![(Button::A, Key4)])),
+            "tank" => Some(profile("tank",
+                Some(Key2), Some(Key8), Some(Key4), Some(Key6),
+                vec![(Button::A, Key5)])),
+            "ufo" => Some(profile("ufo",
+                Some(Key5), None, Some(Key4), Some(Key6),
+                vec![])),
+            "blinky" => Some(profile("blinky",
+                Some(Key3), Some(Key6), Some(Key7), Some(Key8),
+                vec![])),
+            _ => None,
+        }
+    }
+
+    /// Pick the built-in profile matching a ROM's file name, falling back to
+    /// the default profile for unknown ROMs.
+    pub fn for_rom(rom_name: &str) -> GamepadProfile {
+        let stem = rom_name.rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(rom_name)
+            .split('.')
+            .next()
+            .unwrap_or("");
+
+        GamepadProfile::builtin(stem).unwrap_or_default()
+    }
+
+    /// The profile `config` asks for: its custom mapping, else its built-in
+    /// profile, else the one matching the ROM's file name.
+    pub fn from_config(config: &GamepadConfig, rom_name: &str) -> Result<GamepadProfile, String> {
+        match (config.mapping.as_ref(), config.profile.as_ref()) {
+            (Some(mapping), _) => GamepadProfile::parse(mapping),
+            (None, Some(name)) => GamepadProfile::builtin(name)
+                .ok_or_else(|| format!("Unknown gamepad profile: {}", name)),
+            (None, None) => Ok(GamepadProfile::for_rom(rom_name)),
+        }
+    }
+
+    /// Parse a custom profile of the form `up=2,down=8,a=5,start=b`.
+    ///
+    /// Direction names are `up`, `down`, `left` and `right`, button names use
+    /// the SDL game controller mapping names (`a`, `b`, `x`, `y`, `back`,
+    /// `start`, `leftshoulder`, ...). Values are hex Chip-8 keys.
+    pub fn parse(mapping: &str) -> Result<GamepadProfile, String> {
+        let mut profile = GamepadProfile {
+            name: "custom".to_string(),
+            up: None,
+            down: None,
+            left: None,
+            right: None,
+            buttons: Vec::new(),
+        };
+
+        for entry in mapping.split(',').map(|e| e.trim()).filter(|e| !e.is_empty()) {
+            let mut parts = entry.splitn(2, '=');
+            let input = parts.next().unwrap_or("").trim().to_lowercase();
+            let value = parts.next()
+                .ok_or_else(|| format!("Missing key in gamepad mapping: {}", entry))?;
+            let key = u8::from_str_radix(value.trim(), 16)
+                .map_err(|_| format!("Invalid key in gamepad mapping: {}", entry))
+                .and_then(|key| Chip8Key::new(key).map_err(String::from))?;
+
+            match input.as_str() {
+                "up" => profile.up = Some(key),
+                "down" => profile.down = Some(key),
+                "left" => profile.left = Some(key),
+                "right" => profile.right = Some(key),
+                name => match Button::from_string(name) {
+                    Some(button) => profile.buttons.push((button, key)),
+                    None => return Err(format!("Unknown gamepad button: {}", name)),
+                },
+            }
+        }
+
+        Ok(profile)
+    }
+
+    fn button_key(&self, button: Button) -> Option<Chip8Key> {
+        match button {
+            Button::DPadUp => self.up,
+            Button::DPadDown => self.down,
+            Button::DPadLeft => self.left,
+            Button::DPadRight => self.right,
+            _ => self.buttons.iter()
+                .find(|&&(b, _)| b == button)
+                .map(|&(_, key)| key),
+        }
+    }
+}
+
+pub struct Gamepad {
+    profile: GamepadProfile,
+    deadzone: u16,
+
+    keys_pressed: [bool; 16],
+
+    subsystem: sdl2::GameControllerSubsystem,
+    controllers: Vec<GameController>,
+}
+
+impl Gamepad {
+    pub fn new(sdl_context: &sdl2::Sdl, profile: GamepadProfile, deadzone: u16) -> Gamepad {
+        let subsystem = sdl_context.game_controller().unwrap();
+
+        let mut gamepad = Gamepad {
+            profile: profile,
+            deadzone: deadzone,
+            keys_pressed: [false; 16],
+            subsystem: subsystem,
+            controllers: Vec::new(),
+        };
+
+        // Controllers plugged in before start-up are reported through
+        // ControllerDeviceAdded events as well, so there is nothing to open
+        // eagerly here.
+        gamepad.refresh();
+        gamepad
+    }
+
+    pub fn set_profile(&mut self, profile: GamepadProfile) {
+        info!("Gamepad profile: {}", profile.name);
+        self.profile = profile;
+        self.refresh();
+    }
+
+    pub fn keys_pressed(&self) -> &[bool; 16] {
+        &self.keys_pressed
+    }
+
+    /// Update the gamepad from an SDL event. Returns true if the event was a
+    /// controller event.
+    pub fn handle_event(&mut self, event: &Event) -> bool {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                match self.subsystem.open(which) {
+                    Ok(controller) => {
+                        info!("Gamepad connected: {}", controller.name());
+                        self.controllers.push(controller);
+                    }
+                    Err(e) => warn!("Unable to open gamepad {}: {:?}", which, e),
+                }
+            }
+
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.controllers.retain(|c| c.instance_id() != which);
+                info!("Gamepad disconnected: {}", which);
+            }
+
+            Event::ControllerButtonDown { .. } |
+            Event::ControllerButtonUp { .. } |
+            Event::ControllerAxisMotion { .. } |
+            Event::ControllerDeviceRemapped { .. } => {}
+
+            _ => return false,
+        }
+
+        self.refresh();
+        true
+    }
+
+    /// Recompute the pressed keys from the live state of every controller.
+    fn refresh(&mut self) {
+        let mut keys = [false; 16];
+
+        for controller in self.controllers.iter() {
+            // Widened so that the full range of deadzones can be negated
+            let x = controller.axis(Axis::LeftX) as i32;
+            let y = controller.axis(Axis::LeftY) as i32;
+            let deadzone = self.deadzone as i32;
+
+            let mut press = |key: Option<Chip8Key>| {
+                if let Some(key) = key {
+                    keys[key as usize] = true;
+                }
+            };
+
+            if y < -deadzone { press(self.profile.up); }
+            if y > deadzone { press(self.profile.down); }
+            if x < -deadzone { press(self.profile.left); }
+            if x > deadzone { press(self.profile.right); }
+
+            for &button in ALL_BUTTONS.iter() {
+                if controller.button(button) {
+                    press(self.profile.button_key(button));
+                }
+            }
+        }
+
+        self.keys_pressed = keys;
+    }
+}
+
+const ALL_BUTTONS: [Button; 15] = [
+    Button::A,
+    Button::B,
+    Button::X,
+    Button::Y,
+    Button::Back,
+    Button::Guide,
+    Button::Start,
+    Button::LeftStick,
+    Button::RightStick,
+    Button::LeftShoulder,
+    Button::RightShoulder,
+    Button::DPadUp,
+    Button::DPadDown,
+    Button::DPadLeft,
+    Button::DPadRight,
+];
@@ -0,0 +1,173 @@
+use std::time::{Duration, Instant};
+
+use sdl2;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+
+use font::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+/// How long a notification stays on screen.
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+
+/// Menu entries shown at once, longer menus scroll with the selection.
+const MENU_LINES: usize = 10;
+
+const TEXT: Color = Color { r: 0xFF, g: 0xFF, b: 0xFF, a: 0xFF };
+const HIGHLIGHT: Color = Color { r: 0xFF, g: 0xD0, b: 0x40, a: 0xFF };
+const BACKDROP: Color = Color { r: 0x00, g: 0x00, b: 0x00, a: 0xC0 };
+
+/// A list of entries with one of them selected.
+#[derive(Clone, Debug)]
+pub struct Menu {
+    pub title: String,
+    pub items: Vec<String>,
+    pub selected: usize,
+}
+
+/// Text drawn on top of the screen: a status line, short notifications and
+/// menus. It never touches the emulated framebuffer.
+pub struct Overlay {
+    status: Option<String>,
+    message: Option<(String, Instant)>,
+    menu: Option<Menu>,
+    // Something changed since the last render
+    changed: bool,
+}
+
+impl Overlay {
+    pub fn new() -> Overlay {
+        Overlay {
+            status: None,
+            message: None,
+            menu: None,
+            changed: false,
+        }
+    }
+
+    /// Set the status line in the top left corner, `None` hides it.
+    pub fn set_status(&mut self, status: Option<String>) {
+        if status != self.status {
+            self.status = status;
+            self.changed = true;
+        }
+    }
+
+    /// Show a message at the bottom of the screen for a couple of seconds.
+    pub fn notify(&mut self, message: &str) {
+        info!("{}", message);
+        self.message = Some((message.to_string(), Instant::now()));
+        self.changed = true;
+    }
+
+    pub fn set_menu(&mut self, menu: Option<Menu>) {
+        self.menu = menu;
+        self.changed = true;
+    }
+
+    /// Whether the overlay has to be drawn again even though the emulated
+    /// screen didn't change.
+    pub fn needs_redraw(&self) -> bool {
+        self.changed || self.status.is_some() || self.message.is_some()
+    }
+
+    /// Draw the overlay into `viewport`, with text scaled to its size.
+    pub fn render(&mut self, canvas: &mut Canvas<sdl2::video::Window>, viewport: &Rect) {
+        self.changed = false;
+        if let Some((_, shown)) = self.message {
+            if shown.elapsed() >= MESSAGE_DURATION {
+                self.message = None;
+            }
+        }
+
+        // Text is sized so about 25 lines fit on the screen
+        let unit = (viewport.height() / 160).max(1) as i32;
+        let line_height = (GLYPH_HEIGHT as i32 + 2) * unit;
+        let margin = 2 * unit;
+
+        canvas.set_blend_mode(BlendMode::Blend);
+
+        if let Some(ref status) = self.status {
+            let x = viewport.x() + margin;
+            let y = viewport.y() + margin;
+            draw_box(canvas, x, y, text_width(status, unit), line_height, unit);
+            draw_text(canvas, x, y + unit, unit, status, TEXT);
+        }
+
+        if let Some((ref message, _)) = self.message {
+            let x = viewport.x() + margin;
+            let y = viewport.y() + viewport.height() as i32 - margin - line_height;
+            draw_box(canvas, x, y, text_width(message, unit), line_height, unit);
+            draw_text(canvas, x, y + unit, unit, message, TEXT);
+        }
+
+        if let Some(ref menu) = self.menu {
+            // Keep the selection in the middle of the visible part
+            let first = menu.selected
+                .saturating_sub(MENU_LINES / 2)
+                .min(menu.items.len().saturating_sub(MENU_LINES));
+            let visible = &menu.items[first..menu.items.len().min(first + MENU_LINES)];
+
+            let width = visible.iter()
+                .map(|item| text_width(item, unit) + text_width("> ", unit))
+                .chain(Some(text_width(&menu.title, unit)))
+                .max()
+                .unwrap_or(0);
+            let height = line_height * (visible.len() as i32 + 2);
+            let x = viewport.x() + (viewport.width() as i32 - width) / 2;
+            let y = viewport.y() + (viewport.height() as i32 - height) / 2;
+
+            draw_box(canvas, x, y, width, height, unit);
+            draw_text(canvas, x, y + unit, unit, &menu.title, HIGHLIGHT);
+            for (idx, item) in visible.iter().enumerate() {
+                let y = y + line_height * (idx as i32 + 2) + unit;
+                if first + idx == menu.selected {
+                    draw_text(canvas, x, y, unit, &format!("> {}", item), HIGHLIGHT);
+                } else {
+                    draw_text(canvas, x, y, unit, &format!("  {}", item), TEXT);
+                }
+            }
+        }
+
+        canvas.set_blend_mode(BlendMode::None);
+    }
+}
+
+impl Default for Overlay {
+    fn default() -> Overlay {
+        Overlay::new()
+    }
+}
+
+/// Width in pixels of `text` drawn with `draw_text`.
+pub fn text_width(text: &str, unit: i32) -> i32 {
+    text.chars().count() as i32 * (GLYPH_WIDTH as i32 + 1) * unit
+}
+
+/// Darken the area behind a block of text, with a small border around it.
+fn draw_box(canvas: &mut Canvas<sdl2::video::Window>, x: i32, y: i32, w: i32, h: i32, unit: i32) {
+    canvas.set_draw_color(BACKDROP);
+    let _ = canvas.fill_rect(Rect::new(x - unit, y - unit,
+                                       (w + unit) as u32, (h + unit) as u32));
+}
+
+/// Draw `text` with its top left corner at `x`, `y`, each font pixel
+/// `unit` pixels wide.
+pub fn draw_text(canvas: &mut Canvas<sdl2::video::Window>,
+                 x: i32, y: i32, unit: i32, text: &str, color: Color) {
+    let mut rects = Vec::new();
+    for (idx, c) in text.chars().enumerate() {
+        let left = x + idx as i32 * (GLYPH_WIDTH as i32 + 1) * unit;
+        for (row, bits) in font::glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                    rects.push(Rect::new(left + col as i32 * unit, y + row as i32 * unit,
+                                         unit as u32, unit as u32));
+                }
+            }
+        }
+    }
+
+    canvas.set_draw_color(color);
+    let _ = canvas.fill_rects(&rects);
+}
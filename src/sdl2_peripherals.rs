@@ -1,90 +1,508 @@
-use std::time::Duration;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
 
 use sdl2;
-use sdl2::render;
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::render::{self, BlendMode};
 use sdl2::pixels::Color;
-use sdl2::event::Event;
+use sdl2::event::{Event, WindowEvent};
 use sdl2::keyboard::Keycode;
 use sdl2::rect::Rect;
 
-use peripherals::{Chip8Disp, Chip8Input, Chip8Key, PixelData};
+use peripherals::{Chip8Disp, Chip8Input, Chip8Key, Framebuffer, KeyEvent, KeyQueue, Region};
+use sdl2_gamepad::Gamepad;
+use sdl2_overlay::Overlay;
+use config::{AudioConfig, DisplayConfig, Palette, Persistence, Rgb, ScaleMode};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Indicator {
+    Paused,
+    FastForward,
+    SlowMotion,
+}
+
+/// Length of one 60 Hz frame, the unit persistence is measured in.
+const FRAME_SECS: f32 = 1.0 / 60.0;
 
 pub struct Display {
-    data: [[bool; 64]; 32],
+    // The frame as of the last present
+    data: Framebuffer,
+    // Runs of lit pixels in each row of `data`, updated for the rows in
+    // the dirty region of each present
+    spans: Vec<Vec<(usize, usize)>>,
+
+    persistence: Persistence,
+    intensity: [[f32; 64]; 32],
+    history: VecDeque<(Instant, Framebuffer)>,
+    last_draw: Instant,
+    // Window size at the last draw, to redraw after a resize
+    last_size: (u32, u32),
+    indicator: Option<Indicator>,
+    overlay: Overlay,
+    redraw: bool,
+
+    foreground: Color,
+    background: Color,
+    scale_mode: ScaleMode,
+    grid: bool,
+    scanlines: bool,
 
     canvas: render::Canvas<sdl2::video::Window>,
 }
 
 impl Display {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Display {
-        let video_subsystem = sdl_context.video().unwrap();
+    pub fn new(sdl_context: &sdl2::Sdl, config: &DisplayConfig) -> Result<Display, String> {
+        let palette = config.palette()?;
+        let scale_mode = config.scale_mode()?;
+        let persistence = config.persistence()?;
+        let scale = config.scale.max(1);
+
+        let video_subsystem = sdl_context.video()?;
+
+        let mut builder = video_subsystem.window("chip8", 64 * scale, 32 * scale);
+        builder.position_centered();
+        if config.resizable {
+            builder.resizable();
+        }
+        if config.fullscreen {
+            builder.fullscreen_desktop();
+        }
+        let window = builder.build().map_err(|e| e.to_string())?;
+
+        let mut builder = window.into_canvas();
+        if config.vsync {
+            builder = builder.present_vsync();
+        }
+        let canvas = builder.build().map_err(|e| e.to_string())?;
 
-        let window = video_subsystem.window("chip8", 640, 320)
-            .position_centered()
-            .build()
-            .unwrap();
+        let mut display = Display {
+            data: Framebuffer::new(),
+            spans: vec![Vec::new(); 32],
 
-        let mut canvas = window.into_canvas().build().unwrap();
-        canvas.clear();
-        canvas.present();
+            persistence: persistence,
+            intensity: [[0.0; 64]; 32],
+            history: VecDeque::new(),
+            last_draw: Instant::now(),
+            last_size: (0, 0),
+            indicator: None,
+            overlay: Overlay::new(),
+            redraw: false,
 
-        Display {
-            data: [[false; 64]; 32],
+            foreground: as_color(palette.foreground),
+            background: as_color(palette.background),
+            scale_mode: scale_mode,
+            grid: config.grid,
+            scanlines: config.scanlines,
 
             canvas: canvas,
+        };
+        display.draw();
+
+        Ok(display)
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        if let Err(e) = self.canvas.window_mut().set_title(title) {
+            warn!("Invalid window title: {}", e);
         }
     }
-}
 
-impl Chip8Disp for Display {
-    fn set_pixel_data(&mut self, data: &[PixelData]) -> bool {
-        let mut collision = false;
-        for (_, pixel) in data.iter().enumerate() {
-            if pixel.x >= 64 || pixel.y >= 32 {
-                warn!("Invalid pixel value: {:?}", pixel);
-                continue;
+    /// Show an emulation speed icon in the corner of the screen.
+    pub fn set_indicator(&mut self, indicator: Option<Indicator>) {
+        if indicator != self.indicator {
+            self.indicator = indicator;
+            self.redraw = true;
+        }
+    }
+
+    /// The video subsystem the window belongs to, to open more windows.
+    pub fn video_subsystem(&self) -> sdl2::VideoSubsystem {
+        self.canvas.window().subsystem().clone()
+    }
+
+    pub fn overlay_mut(&mut self) -> &mut Overlay {
+        &mut self.overlay
+    }
+
+    pub fn palette(&self) -> Palette {
+        let rgb = |color: Color| Rgb(color.r, color.g, color.b);
+        Palette {
+            foreground: rgb(self.foreground),
+            background: rgb(self.background),
+        }
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.foreground = as_color(palette.foreground);
+        self.background = as_color(palette.background);
+        self.redraw = true;
+    }
+
+    /// Area of the window the 64x32 screen is drawn into.
+    fn viewport(&self) -> Rect {
+        let (width, height) = self.canvas.output_size().unwrap_or((640, 320));
+
+        let (w, h) = match self.scale_mode {
+            ScaleMode::Stretch => (width, height),
+            ScaleMode::Aspect => {
+                if width >= height * 2 {
+                    (height * 2, height)
+                } else {
+                    (width, width / 2)
+                }
             }
+            ScaleMode::Integer => {
+                let scale = (width / 64).min(height / 32).max(1);
+                (64 * scale, 32 * scale)
+            }
+        };
+
+        let x = (width as i32 - w as i32) / 2;
+        let y = (height as i32 - h as i32) / 2;
+        Rect::new(x, y, w.max(1), h.max(1))
+    }
+
+    fn cell(viewport: &Rect, x: usize, y: usize) -> Rect {
+        let w = viewport.width() as usize;
+        let h = viewport.height() as usize;
+
+        let left = x * w / 64;
+        let right = (x + 1) * w / 64;
+        let top = y * h / 32;
+        let bottom = (y + 1) * h / 32;
+
+        Rect::new(viewport.x() + left as i32,
+                  viewport.y() + top as i32,
+                  (right - left).max(1) as u32,
+                  (bottom - top).max(1) as u32)
+    }
+
+    /// Seconds since the last draw.
+    fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_draw);
+        self.last_draw = now;
+        elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 * 1e-9
+    }
+
+    /// Fade the phosphor intensities by `decay` per frame and light the
+    /// pixels of the current frame, from 0 (background) to 1.
+    fn fade(&mut self, decay: f32, elapsed: f32) {
+        let factor = decay.powf(elapsed / FRAME_SECS);
+        for (y, line) in self.intensity.iter_mut().enumerate() {
+            for (x, intensity) in line.iter_mut().enumerate() {
+                *intensity = if self.data.pixel(x, y) { 1.0 } else { *intensity * factor };
+            }
+        }
+    }
+
+    /// The current frame merged with the frames of the last `frames` 60 Hz
+    /// frames.
+    fn merge(&mut self, frames: u32) -> Framebuffer {
+        let now = Instant::now();
+        let window = Duration::from_millis((frames as u64 * 1000) / 60);
+        self.history.retain(|&(time, _)| now.duration_since(time) < window);
+        self.history.push_back((now, self.data));
 
-            if pixel.val && self.data[pixel.y][pixel.x] {
-                self.data[pixel.y][pixel.x] = false;
-                collision = true;
-            } else if !self.data[pixel.y][pixel.x] {
-                self.data[pixel.y][pixel.x] = pixel.val;
+        let mut merged = Framebuffer::new();
+        for (_, frame) in self.history.iter() {
+            for y in 0..32 {
+                let row = merged.row(y) | frame.row(y);
+                merged.set_row(y, row);
             }
         }
+        merged
+    }
 
-        collision
+    /// One rectangle per run of lit pixels, drawn with a single call.
+    fn fill_spans(canvas: &mut render::Canvas<sdl2::video::Window>, color: Color,
+                  viewport: &Rect, spans: &[Vec<(usize, usize)>]) {
+        let rects: Vec<Rect> = spans.iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.iter().map(move |&(x, len)| (x, y, len)))
+            .map(|(x, y, len)| {
+                let first = Display::cell(viewport, x, y);
+                let last = Display::cell(viewport, x + len - 1, y);
+                Rect::new(first.x(), first.y(), (last.right() - first.x()) as u32,
+                          first.height())
+            })
+            .collect();
+
+        canvas.set_draw_color(color);
+        let _ = canvas.fill_rects(&rects);
     }
-    
+
+    fn shade_color(&self, intensity: f32) -> Color {
+        let mix = |bg: u8, fg: u8| {
+            (bg as f32 + (fg as f32 - bg as f32) * intensity).round() as u8
+        };
+
+        Color::RGB(mix(self.background.r, self.foreground.r),
+                   mix(self.background.g, self.foreground.g),
+                   mix(self.background.b, self.foreground.b))
+    }
+
+    /// Render the last presented frame with the overlay and indicator.
     fn draw(&mut self) {
-        self.canvas.set_draw_color(Color::RGB(255, 255, 255));
+        self.last_size = self.canvas.output_size().unwrap_or((0, 0));
+        let viewport = self.viewport();
+
+        let elapsed = self.tick();
+
+        // Letterbox bars take the background colour as well
+        self.canvas.set_draw_color(self.background);
         self.canvas.clear();
-        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
-        for (y, line) in self.data.iter().enumerate() {
-            for (x, value) in line.iter().enumerate() {
-                if *value {
-                    let x = x as i32;
-                    let y = y as i32;
-                    self.canvas.fill_rect(Rect::new(x*10, y*10, 10, 10)).unwrap();
+        match self.persistence {
+            Persistence::Off => {
+                Display::fill_spans(&mut self.canvas, self.foreground, &viewport, &self.spans);
+            }
+            Persistence::Or { frames } => {
+                let merged = self.merge(frames);
+                let merged: Vec<_> = (0..32).map(|y| spans(merged.row(y))).collect();
+                Display::fill_spans(&mut self.canvas, self.foreground, &viewport, &merged);
+            }
+            Persistence::Phosphor { decay } => {
+                self.fade(decay, elapsed);
+                for y in 0..32 {
+                    for x in 0..64 {
+                        // Fully faded pixels are left as background
+                        let intensity = self.intensity[y][x];
+                        if intensity >= 1.0 / 255.0 {
+                            let color = self.shade_color(intensity);
+                            self.canvas.set_draw_color(color);
+                            let _ = self.canvas.fill_rect(Display::cell(&viewport, x, y));
+                        }
+                    }
                 }
             }
         }
+
+        if self.grid {
+            self.draw_grid(&viewport);
+        }
+        if self.scanlines {
+            self.draw_scanlines(&viewport);
+        }
+        self.draw_indicator(&viewport);
+        self.overlay.render(&mut self.canvas, &viewport);
         self.canvas.present();
+        self.redraw = false;
     }
 
-    fn clear(&mut self) {
-        self.data = [[false; 64]; 32];
+    fn draw_indicator(&mut self, viewport: &Rect) {
+        let indicator = match self.indicator {
+            Some(indicator) => indicator,
+            None => return,
+        };
+
+        // Icons are drawn on a 7x7 grid of emulated pixels in the top right
+        let unit = (viewport.height() / 32).max(1) as i32;
+        let left = viewport.x() + viewport.width() as i32 - 9 * unit;
+        let top = viewport.y() + 2 * unit;
+
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+        let _ = self.canvas.fill_rect(Rect::new(left - unit, top - unit,
+                                                (9 * unit) as u32, (9 * unit) as u32));
+        self.canvas.set_blend_mode(BlendMode::None);
         self.canvas.set_draw_color(Color::RGB(255, 255, 255));
-        self.canvas.clear();
-        self.canvas.present();
+
+        let mut rect = |x: i32, y: i32, w: i32, h: i32| {
+            let _ = self.canvas.fill_rect(Rect::new(left + x * unit, top + y * unit,
+                                                    (w * unit) as u32, (h * unit) as u32));
+        };
+
+        // Right-pointing triangle 4 units wide starting at column x
+        let triangle = |rect: &mut dyn FnMut(i32, i32, i32, i32), x: i32| {
+            for (row, width) in [1, 2, 3, 4, 3, 2, 1].iter().enumerate() {
+                rect(x, row as i32, *width, 1);
+            }
+        };
+
+        match indicator {
+            Indicator::Paused => {
+                rect(1, 0, 2, 7);
+                rect(4, 0, 2, 7);
+            }
+            Indicator::FastForward => {
+                triangle(&mut rect, 0);
+                triangle(&mut rect, 3);
+            }
+            Indicator::SlowMotion => {
+                rect(0, 0, 1, 7);
+                triangle(&mut rect, 2);
+            }
+        }
+    }
+
+    fn draw_grid(&mut self, viewport: &Rect) {
+        let bg = self.background;
+        let fg = self.foreground;
+        let blend = |b: u8, f: u8| ((b as u16 * 3 + f as u16) / 4) as u8;
+        self.canvas.set_draw_color(Color::RGB(blend(bg.r, fg.r),
+                                              blend(bg.g, fg.g),
+                                              blend(bg.b, fg.b)));
+
+        let top = viewport.y();
+        let bottom = viewport.y() + viewport.height() as i32 - 1;
+        for x in 1..64 {
+            let edge = Display::cell(viewport, x, 0).x();
+            let _ = self.canvas.draw_line((edge, top), (edge, bottom));
+        }
+
+        let left = viewport.x();
+        let right = viewport.x() + viewport.width() as i32 - 1;
+        for y in 1..32 {
+            let edge = Display::cell(viewport, 0, y).y();
+            let _ = self.canvas.draw_line((left, edge), (right, edge));
+        }
+    }
+
+    fn draw_scanlines(&mut self, viewport: &Rect) {
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 72));
+
+        let left = viewport.x();
+        let right = viewport.x() + viewport.width() as i32 - 1;
+        let mut y = viewport.y() + 1;
+        while y < viewport.y() + viewport.height() as i32 {
+            let _ = self.canvas.draw_line((left, y), (right, y));
+            y += 2;
+        }
+
+        self.canvas.set_blend_mode(BlendMode::None);
+    }
+}
+
+fn as_color(rgb: Rgb) -> Color {
+    Color::RGB(rgb.0, rgb.1, rgb.2)
+}
+
+/// Runs of lit pixels in a framebuffer row, as first column and length.
+fn spans(mut row: u64) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut x = 0;
+    while row != 0 {
+        let skip = row.leading_zeros() as usize;
+        row <<= skip;
+        let len = (!row).leading_zeros() as usize;
+        spans.push((x + skip, len));
+        row = row.checked_shl(len as u32).unwrap_or(0);
+        x += skip + len;
     }
+    spans
+}
+
+impl Chip8Disp for Display {
+    fn present(&mut self, frame: &Framebuffer, dirty: Option<Region>) {
+        self.data = *frame;
+        if let Some(dirty) = dirty {
+            for y in dirty.y..(dirty.y + dirty.height) {
+                self.spans[y] = spans(frame.row(y));
+            }
+        }
+        self.draw();
+    }
+
+    fn animating(&self) -> bool {
+        self.redraw ||
+            self.overlay.needs_redraw() ||
+            self.persistence != Persistence::Off ||
+            self.canvas.output_size().ok() != Some(self.last_size)
+    }
+}
+
+
+const DEFAULT_KEYMAP: [(Keycode, Chip8Key); 16] = [
+    (Keycode::Num1, Chip8Key::Key1),
+    (Keycode::Num2, Chip8Key::Key2),
+    (Keycode::Num3, Chip8Key::Key3),
+    (Keycode::Num4, Chip8Key::KeyC),
+    (Keycode::Q, Chip8Key::Key4),
+    (Keycode::W, Chip8Key::Key5),
+    (Keycode::E, Chip8Key::Key6),
+    (Keycode::R, Chip8Key::KeyD),
+    (Keycode::A, Chip8Key::Key7),
+    (Keycode::S, Chip8Key::Key8),
+    (Keycode::D, Chip8Key::Key9),
+    (Keycode::F, Chip8Key::KeyE),
+    (Keycode::Z, Chip8Key::KeyA),
+    (Keycode::X, Chip8Key::Key0),
+    (Keycode::C, Chip8Key::KeyB),
+    (Keycode::V, Chip8Key::KeyF),
+];
+
+/// Emulator controls on keys that aren't mapped to the Chip-8 keypad.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Hotkey {
+    /// Escape opens the pause menu.
+    Menu,
+    /// F1 toggles the status line.
+    Status,
+    /// F5 saves the state to the current slot, F7 loads it and F6 selects
+    /// the next slot.
+    SaveState,
+    LoadState,
+    NextSlot,
+    /// F11 opens or closes the sprite viewer window.
+    SpriteViewer,
+    /// F12 opens or closes the debugger window.
+    Debugger,
+    /// P toggles pause.
+    Pause,
+    /// N runs a single frame while paused.
+    FrameAdvance,
+    /// Tab runs as fast as possible while held.
+    FastForward(bool),
+    /// Backspace toggles quarter speed.
+    SlowMotion,
+    /// `=` and `-` change the instructions executed per frame.
+    SpeedUp,
+    SpeedDown,
+    /// Any key pressed while the keyboard is captured, see
+    /// `Keyboard::set_capture`.
+    Key(Keycode),
+    /// Any key pressed in the debugger window.
+    DebugKey(Keycode),
+    /// Any key pressed in the sprite viewer window.
+    SpriteKey(Keycode),
 }
 
+impl Hotkey {
+    fn from_keycode(key: Keycode, down: bool, repeat: bool) -> Option<Hotkey> {
+        match (key, down) {
+            (Keycode::Tab, down) => Some(Hotkey::FastForward(down)),
+            (_, false) => None,
+            (Keycode::Equals, true) | (Keycode::KpPlus, true) => Some(Hotkey::SpeedUp),
+            (Keycode::Minus, true) | (Keycode::KpMinus, true) => Some(Hotkey::SpeedDown),
+            // Toggles ignore key repeat
+            (_, true) if repeat => None,
+            (Keycode::P, true) => Some(Hotkey::Pause),
+            (Keycode::N, true) => Some(Hotkey::FrameAdvance),
+            (Keycode::Backspace, true) => Some(Hotkey::SlowMotion),
+            (Keycode::Escape, true) => Some(Hotkey::Menu),
+            (Keycode::F1, true) => Some(Hotkey::Status),
+            (Keycode::F5, true) => Some(Hotkey::SaveState),
+            (Keycode::F6, true) => Some(Hotkey::NextSlot),
+            (Keycode::F7, true) => Some(Hotkey::LoadState),
+            (Keycode::F11, true) => Some(Hotkey::SpriteViewer),
+            (Keycode::F12, true) => Some(Hotkey::Debugger),
+            _ => None,
+        }
+    }
+}
 
 pub struct Keyboard {
-    last_key_pressed: Option<Chip8Key>,
+    // Keys held on the keyboard, the keypad also includes the gamepad
     keys_pressed: [bool; 16],
+    keys: KeyQueue,
+    keymap: Vec<(Keycode, Chip8Key)>,
+    hotkeys: Vec<Hotkey>,
+    capture: bool,
+    debug_window: Option<u32>,
+    sprite_window: Option<u32>,
 
+    gamepad: Option<Gamepad>,
     event_pump: sdl2::EventPump,
 }
 
@@ -92,70 +510,192 @@ impl Keyboard {
     pub fn new(sdl_context: &sdl2::Sdl) -> Keyboard {
         let event_pump = sdl_context.event_pump().unwrap();
         Keyboard { 
-            last_key_pressed: None,
             keys_pressed: [false; 16],
+            keys: KeyQueue::new(),
+            keymap: DEFAULT_KEYMAP.to_vec(),
+            hotkeys: Vec::new(),
+            capture: false,
+            debug_window: None,
+            sprite_window: None,
+            gamepad: None,
             event_pump: event_pump,
         }
     }
 
-    fn sdl_key_as_chip8key(sdl_key: Keycode) -> Option<Chip8Key> {
-        match sdl_key {
-            Keycode::Num1 => Some(Chip8Key::Key1),
-            Keycode::Num2 => Some(Chip8Key::Key2),
-            Keycode::Num3 => Some(Chip8Key::Key3),
-            Keycode::Q => Some(Chip8Key::Key4),
-            Keycode::W => Some(Chip8Key::Key5),
-            Keycode::E => Some(Chip8Key::Key6),
-            Keycode::A => Some(Chip8Key::Key7),
-            Keycode::S => Some(Chip8Key::Key8),
-            Keycode::D => Some(Chip8Key::Key9),
-            Keycode::X => Some(Chip8Key::Key0),
-            Keycode::Z => Some(Chip8Key::KeyA),
-            Keycode::C => Some(Chip8Key::KeyB),
-            Keycode::Num4 => Some(Chip8Key::KeyC),
-            Keycode::R => Some(Chip8Key::KeyD),
-            Keycode::F => Some(Chip8Key::KeyE),
-            Keycode::V => Some(Chip8Key::KeyF),
-            _ => None,
+    pub fn connect_gamepad(&mut self, gamepad: Gamepad) {
+        self.gamepad = Some(gamepad);
+    }
+
+    pub fn gamepad_mut(&mut self) -> Option<&mut Gamepad> {
+        self.gamepad.as_mut()
+    }
+
+    /// Hotkeys pressed since the last call, in order.
+    pub fn take_hotkeys(&mut self) -> Vec<Hotkey> {
+        self.hotkeys.drain(..).collect()
+    }
+
+    /// While the keyboard is captured every key press is reported as
+    /// `Hotkey::Key` and the Chip-8 keypad reads as released.
+    pub fn set_capture(&mut self, capture: bool) {
+        self.capture = capture;
+        self.keys_pressed = [false; 16];
+        self.update_keys();
+    }
+
+    /// Send key presses in the window with this id to the debugger instead
+    /// of the keypad, see `Hotkey::DebugKey`.
+    pub fn set_debug_window(&mut self, window_id: Option<u32>) {
+        self.debug_window = window_id;
+    }
+
+    /// Like `set_debug_window` for the sprite viewer, see
+    /// `Hotkey::SpriteKey`.
+    pub fn set_sprite_window(&mut self, window_id: Option<u32>) {
+        self.sprite_window = window_id;
+    }
+
+    /// Go back to the default key bindings.
+    pub fn reset_keymap(&mut self) {
+        self.keymap = DEFAULT_KEYMAP.to_vec();
+    }
+
+    /// Rebind Chip-8 keys, given as hex digits, to SDL key names such as
+    /// `"Space"` or `"Left"`. Keys that aren't mentioned keep their binding.
+    pub fn set_keymap(&mut self, keymap: &HashMap<String, String>) -> Result<(), String> {
+        for (chip8_key, sdl_name) in keymap.iter() {
+            let chip8_key = u8::from_str_radix(chip8_key.trim(), 16)
+                .map_err(|_| format!("Invalid Chip-8 key in keymap: {}", chip8_key))
+                .and_then(|key| Chip8Key::new(key).map_err(String::from))?;
+            let sdl_key = Keycode::from_name(sdl_name)
+                .ok_or_else(|| format!("Unknown key name in keymap: {}", sdl_name))?;
+
+            self.bind_key(chip8_key, sdl_key);
+        }
+
+        Ok(())
+    }
+
+    /// Bind a Chip-8 key to an SDL key, replacing the previous bindings of
+    /// both.
+    pub fn bind_key(&mut self, chip8_key: Chip8Key, sdl_key: Keycode) {
+        self.keymap.retain(|&(_, key)| key != chip8_key);
+        self.keymap.retain(|&(code, _)| code != sdl_key);
+        self.keymap.push((sdl_key, chip8_key));
+    }
+
+    /// The SDL key a Chip-8 key is bound to.
+    pub fn binding(&self, chip8_key: Chip8Key) -> Option<Keycode> {
+        self.keymap.iter()
+            .find(|&&(_, key)| key == chip8_key)
+            .map(|&(code, _)| code)
+    }
+
+    /// Update the keypad from the keyboard and the gamepad, so a key held
+    /// on both is released when neither holds it anymore.
+    fn update_keys(&mut self) {
+        let mut keys = self.keys_pressed;
+        if let Some(ref gamepad) = self.gamepad {
+            for (key, &pressed) in keys.iter_mut().zip(gamepad.keys_pressed().iter()) {
+                *key |= pressed;
+            }
         }
+
+        self.keys.set_keys(&keys);
+    }
+
+    fn sdl_key_as_chip8key(keymap: &[(Keycode, Chip8Key)], sdl_key: Keycode) -> Option<Chip8Key> {
+        keymap.iter()
+            .find(|&&(code, _)| code == sdl_key)
+            .map(|&(_, key)| key)
     }
 }
 
 impl Chip8Input for Keyboard {
-    fn last_key_pressed(&self) -> Option<Chip8Key> {
-        self.last_key_pressed
+    fn key_pressed(&self, key: Chip8Key) -> bool {
+        self.keys.key_pressed(key)
     }
 
-    fn key_pressed(&self, key: Chip8Key) -> bool {
-        self.keys_pressed[key as usize]
+    fn next_event(&mut self) -> Option<KeyEvent> {
+        self.keys.next_event()
     }
 
     fn poll(&mut self) -> bool {
-        for event in self.event_pump.poll_iter() {
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+        for event in events {
+            let gamepad_event = match self.gamepad {
+                Some(ref mut gamepad) => gamepad.handle_event(&event),
+                None => false,
+            };
+            if gamepad_event {
+                self.update_keys();
+                continue;
+            }
+
             match event {
-                Event::Quit { .. } | 
-                Event::KeyDown { keycode: Some(Keycode::Escape), ..} => {
+                Event::Quit { .. } => {
                     return true;
                 }
 
-                Event::KeyDown { keycode: Some(key), .. } => {
-                    let chip8_key = Keyboard::sdl_key_as_chip8key(key);
-                    self.last_key_pressed = chip8_key;
+                // With more windows open SDL only sends Quit once all of
+                // them are closed
+                Event::Window { window_id, win_event: WindowEvent::Close, .. } => {
+                    if Some(window_id) == self.debug_window {
+                        self.hotkeys.push(Hotkey::Debugger);
+                    } else if Some(window_id) == self.sprite_window {
+                        self.hotkeys.push(Hotkey::SpriteViewer);
+                    } else {
+                        return true;
+                    }
+                }
+
+                Event::KeyDown { keycode: Some(key), window_id, .. }
+                    if Some(window_id) == self.debug_window => {
+                    self.hotkeys.push(Hotkey::DebugKey(key));
+                }
+
+                Event::KeyDown { keycode: Some(key), window_id, .. }
+                    if Some(window_id) == self.sprite_window => {
+                    self.hotkeys.push(Hotkey::SpriteKey(key));
+                }
+
+                Event::KeyUp { window_id, .. }
+                    if Some(window_id) == self.debug_window ||
+                       Some(window_id) == self.sprite_window => {}
+
+                Event::KeyDown { keycode: Some(key), repeat: false, .. } if self.capture => {
+                    self.hotkeys.push(Hotkey::Key(key));
+                }
+
+                Event::KeyDown { .. } | Event::KeyUp { .. } if self.capture => {}
+
+                Event::KeyDown { keycode: Some(key), repeat, .. } => {
+                    let chip8_key = Keyboard::sdl_key_as_chip8key(&self.keymap, key);
+                    if chip8_key.is_none() {
+                        if let Some(hotkey) = Hotkey::from_keycode(key, true, repeat) {
+                            self.hotkeys.push(hotkey);
+                        }
+                        continue;
+                    }
 
                     if let Some(key) = chip8_key {
                         self.keys_pressed[key as usize] = true;
+                        self.update_keys();
                     }
                 }
 
                 Event::KeyUp { keycode: Some(key), .. } => {
-                    let chip8_key = Keyboard::sdl_key_as_chip8key(key);
-
-                    if self.last_key_pressed == chip8_key {
-                        self.last_key_pressed = None;
+                    let chip8_key = Keyboard::sdl_key_as_chip8key(&self.keymap, key);
+                    if chip8_key.is_none() {
+                        if let Some(hotkey) = Hotkey::from_keycode(key, false, false) {
+                            self.hotkeys.push(hotkey);
+                        }
+                        continue;
                     }
 
                     if let Some(key) = chip8_key {
                         self.keys_pressed[key as usize] = false;
+                        self.update_keys();
                     }
                 }
 
@@ -167,3 +707,67 @@ impl Chip8Input for Keyboard {
     }
 }
 
+
+
+struct SquareWave {
+    phase_inc: f32,
+    phase: f32,
+    volume: f32,
+}
+
+impl AudioCallback for SquareWave {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for x in out.iter_mut() {
+            *x = if self.phase <= 0.5 { self.volume } else { -self.volume };
+            self.phase = (self.phase + self.phase_inc) % 1.0;
+        }
+    }
+}
+
+/// Plays a square wave tone while the sound timer is running.
+pub struct Beeper {
+    device: AudioDevice<SquareWave>,
+    playing: bool,
+}
+
+impl Beeper {
+    pub fn new(sdl_context: &sdl2::Sdl, config: &AudioConfig) -> Result<Beeper, String> {
+        let audio_subsystem = sdl_context.audio()?;
+
+        let spec = AudioSpecDesired {
+            freq: Some(44100),
+            channels: Some(1),
+            samples: None,
+        };
+
+        let frequency = config.frequency;
+        let volume = config.volume.clamp(0.0, 1.0);
+        let device = audio_subsystem.open_playback(None, &spec, |spec| {
+            SquareWave {
+                phase_inc: frequency / spec.freq as f32,
+                phase: 0.0,
+                volume: volume,
+            }
+        })?;
+
+        Ok(Beeper {
+            device: device,
+            playing: false,
+        })
+    }
+
+    pub fn set_playing(&mut self, playing: bool) {
+        if playing == self.playing {
+            return;
+        }
+
+        if playing {
+            self.device.resume();
+        } else {
+            self.device.pause();
+        }
+        self.playing = playing;
+    }
+}
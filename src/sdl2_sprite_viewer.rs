@@ -0,0 +1,208 @@
+use sdl2;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+
+use core::Chip8;
+use font::GLYPH_HEIGHT;
+use sdl2_overlay::draw_text;
+use sdl2_peripherals::{Display, Keyboard};
+use sprites::{sprite, sprite_to_string};
+
+const MEMORY_SIZE: usize = 4096;
+
+/// Size of a font pixel in the header and of a sprite pixel in the grid.
+const UNIT: i32 = 2;
+const PIXEL: i32 = 4;
+
+const WIDTH: u32 = 720;
+const HEIGHT: u32 = 560;
+const MARGIN: i32 = 8;
+const LINE_HEIGHT: i32 = (GLYPH_HEIGHT as i32 + 3) * UNIT;
+
+/// Sprites per row of the grid.
+const COLUMNS: usize = 16;
+const CELL_WIDTH: i32 = 8 * PIXEL + 8;
+/// Room for the address labels left of the grid.
+const LABEL_WIDTH: i32 = 4 * 6 + 8;
+
+const BACKGROUND: Color = Color { r: 0x18, g: 0x18, b: 0x20, a: 0xFF };
+const CELL: Color = Color { r: 0x28, g: 0x28, b: 0x34, a: 0xFF };
+const PIXEL_ON: Color = Color { r: 0xE0, g: 0xE0, b: 0xE0, a: 0xFF };
+const TEXT: Color = Color { r: 0xD0, g: 0xD0, b: 0xD0, a: 0xFF };
+const LABEL: Color = Color { r: 0x80, g: 0x80, b: 0x90, a: 0xFF };
+const CURSOR: Color = Color { r: 0xFF, g: 0xD0, b: 0x40, a: 0xFF };
+const SPRITE: Color = Color { r: 0x50, g: 0xC0, b: 0xFF, a: 0xFF };
+
+/// A window showing memory as a grid of sprites the way `Draw` reads them:
+/// 8 pixels wide, `height` bytes tall, one sprite after the other.
+///
+/// The arrow keys move the cursor by sprite, page keys by screen, `,` and
+/// `.` shift the grid by a single byte to line it up with the ROM's
+/// sprites, `[` and `]` change the height, Home jumps to the last sprite
+/// drawn and D writes the selected sprite to the log.
+pub struct SpriteViewer {
+    start: usize,
+    height: usize,
+    cursor: usize,
+
+    canvas: Canvas<sdl2::video::Window>,
+}
+
+impl SpriteViewer {
+    pub fn new(video_subsystem: &sdl2::VideoSubsystem) -> Result<SpriteViewer, String> {
+        let window = video_subsystem.window("chip8 sprites", WIDTH, HEIGHT)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+
+        Ok(SpriteViewer {
+            start: 0x200,
+            height: 8,
+            cursor: 0x200,
+            canvas: canvas,
+        })
+    }
+
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    /// Handle a key pressed in the window. Returns false for keys the viewer
+    /// doesn't use.
+    pub fn handle_key(&mut self, key: Keycode, chip8: &Chip8<Display, Keyboard>) -> bool {
+        let row = self.height * COLUMNS;
+        let page = row * self.rows();
+
+        match key {
+            Keycode::Left => self.move_cursor(-(self.height as isize)),
+            Keycode::Right => self.move_cursor(self.height as isize),
+            Keycode::Up => self.move_cursor(-(row as isize)),
+            Keycode::Down => self.move_cursor(row as isize),
+            Keycode::PageUp => {
+                self.start = self.start.saturating_sub(page);
+                self.move_cursor(-(page as isize));
+            }
+            Keycode::PageDown => {
+                self.start = (self.start + page).min(MEMORY_SIZE - 1);
+                self.move_cursor(page as isize);
+            }
+            Keycode::Comma => {
+                self.start = self.start.saturating_sub(1);
+                self.cursor = self.cursor.saturating_sub(1);
+            }
+            Keycode::Period => {
+                self.start = (self.start + 1).min(MEMORY_SIZE - 1);
+                self.cursor = (self.cursor + 1).min(MEMORY_SIZE - 1);
+            }
+            Keycode::LeftBracket => self.height = (self.height - 1).max(1),
+            Keycode::RightBracket => self.height = (self.height + 1).min(15),
+            Keycode::Home => {
+                if let Some(draw) = chip8.last_draw() {
+                    self.start = draw.index as usize;
+                    self.cursor = draw.index as usize;
+                    self.height = (draw.height as usize).max(1);
+                }
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Address and text dump of the selected sprite.
+    pub fn selected_sprite(&self, chip8: &Chip8<Display, Keyboard>) -> (usize, String) {
+        (self.cursor, sprite_to_string(chip8.memory(), self.cursor, self.height))
+    }
+
+    fn rows(&self) -> usize {
+        let grid_height = HEIGHT as i32 - 2 * MARGIN - 2 * LINE_HEIGHT;
+        (grid_height / self.cell_height()).max(1) as usize
+    }
+
+    fn cell_height(&self) -> i32 {
+        self.height as i32 * PIXEL + 8
+    }
+
+    /// Move the cursor, scrolling the grid by whole rows to keep it visible.
+    fn move_cursor(&mut self, offset: isize) {
+        let cursor = self.cursor as isize + offset;
+        if cursor < 0 || cursor >= MEMORY_SIZE as isize {
+            return;
+        }
+        self.cursor = cursor as usize;
+
+        let row = self.height * COLUMNS;
+        let page = row * self.rows();
+        while self.cursor < self.start {
+            self.start = self.start.saturating_sub(row);
+        }
+        while self.cursor >= self.start + page {
+            self.start += row;
+        }
+    }
+
+    pub fn draw(&mut self, chip8: &Chip8<Display, Keyboard>) {
+        self.canvas.set_draw_color(BACKGROUND);
+        self.canvas.clear();
+
+        let header = format!("CURSOR {:03X}  HEIGHT {}  BYTE {:02X}",
+                             self.cursor, self.height,
+                             chip8.memory().get(self.cursor).cloned().unwrap_or(0));
+        draw_text(&mut self.canvas, MARGIN, MARGIN, UNIT, &header, TEXT);
+        draw_text(&mut self.canvas, MARGIN, HEIGHT as i32 - MARGIN - LINE_HEIGHT + UNIT, UNIT,
+                  "ARROWS MOVE  , . ALIGN  [ ] HEIGHT  HOME LAST DRAW  D LOG", LABEL);
+
+        let last_draw = chip8.last_draw().map(|draw| draw.index as usize);
+        let top = MARGIN + LINE_HEIGHT;
+        let cell_height = self.cell_height();
+
+        for row in 0..self.rows() {
+            let row_addr = self.start + row * self.height * COLUMNS;
+            if row_addr >= MEMORY_SIZE {
+                break;
+            }
+
+            let y = top + row as i32 * cell_height;
+            draw_text(&mut self.canvas, MARGIN, y + 4, 1, &format!("{:03X}", row_addr), LABEL);
+
+            for column in 0..COLUMNS {
+                let addr = row_addr + column * self.height;
+                if addr >= MEMORY_SIZE {
+                    break;
+                }
+
+                let x = MARGIN + LABEL_WIDTH + column as i32 * CELL_WIDTH;
+                let frame = if addr == self.cursor {
+                    CURSOR
+                } else if Some(addr) == last_draw {
+                    SPRITE
+                } else {
+                    CELL
+                };
+                self.canvas.set_draw_color(frame);
+                let _ = self.canvas.fill_rect(Rect::new(x, y, (CELL_WIDTH - 4) as u32,
+                                                        (cell_height - 4) as u32));
+                self.canvas.set_draw_color(BACKGROUND);
+                let _ = self.canvas.fill_rect(Rect::new(x + 2, y + 2, (CELL_WIDTH - 8) as u32,
+                                                        (cell_height - 8) as u32));
+
+                let mut pixels = Vec::new();
+                for (line, bits) in sprite(chip8.memory(), addr, self.height).iter().enumerate() {
+                    for (bit, set) in bits.iter().enumerate() {
+                        if *set {
+                            pixels.push(Rect::new(x + 2 + bit as i32 * PIXEL,
+                                                  y + 2 + line as i32 * PIXEL,
+                                                  PIXEL as u32, PIXEL as u32));
+                        }
+                    }
+                }
+                self.canvas.set_draw_color(PIXEL_ON);
+                let _ = self.canvas.fill_rects(&pixels);
+            }
+        }
+
+        self.canvas.present();
+    }
+}
@@ -0,0 +1,195 @@
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use toml;
+
+use config::rom_id;
+use core::DEFAULT_CYCLES_PER_FRAME;
+use headless::{frame_from_str, frame_to_string, headless, Framebuffer, HeadlessConfig,
+               ScriptedInput};
+use quirks::Quirks;
+
+const SPEC_FILE: &str = "snapshot.toml";
+
+/// How a ROM is run and at which frames its screen is recorded. Stored as
+/// `snapshot.toml` in the snapshot directory so a recording can be verified
+/// without repeating the options.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SnapshotSpec {
+    /// SHA-1 of the ROM the snapshots were recorded with.
+    pub rom: String,
+    /// Quirks preset, the default quirks if not set.
+    pub quirks: Option<String>,
+    /// Instructions executed per 60 Hz frame.
+    pub speed: usize,
+    pub seed: u32,
+    /// Input script in the format read by `ScriptedInput::parse`.
+    pub input: String,
+    pub frames: Vec<usize>,
+}
+
+impl SnapshotSpec {
+    pub fn new(program: &[u8], frames: Vec<usize>) -> SnapshotSpec {
+        SnapshotSpec {
+            rom: rom_id(program),
+            quirks: None,
+            speed: DEFAULT_CYCLES_PER_FRAME,
+            seed: 0,
+            input: String::new(),
+            frames: frames,
+        }
+    }
+
+    pub fn config(&self) -> Result<HeadlessConfig, String> {
+        let quirks = match self.quirks {
+            Some(ref name) => {
+                Quirks::preset(name).ok_or_else(|| format!("Unknown quirks preset: {}", name))?
+            }
+            None => Quirks::default(),
+        };
+
+        Ok(HeadlessConfig {
+            quirks: quirks,
+            cycles_per_frame: self.speed,
+            seed: self.seed,
+            input: ScriptedInput::parse(&self.input)?,
+            ..HeadlessConfig::default()
+        })
+    }
+}
+
+/// A snapshot that doesn't match the screen of the current run.
+#[derive(Clone, Debug)]
+pub struct Mismatch {
+    pub frame: usize,
+    pub expected: Framebuffer,
+    pub found: Framebuffer,
+}
+
+impl Mismatch {
+    pub fn diff(&self) -> String {
+        format!("frame {}:\n{}", self.frame, diff(&self.expected, &self.found))
+    }
+}
+
+/// Run `program` and capture the screen at each of the frames in `spec`.
+pub fn take_snapshots(program: &[u8], spec: &SnapshotSpec)
+                      -> Result<Vec<(usize, Framebuffer)>, String> {
+    let mut frames = spec.frames.clone();
+    frames.sort();
+    frames.dedup();
+
+    let mut chip8 = headless(program, &spec.config()?)?;
+    let mut frame = 0;
+    let mut snapshots = Vec::new();
+    for target in frames {
+        while frame < target {
+            chip8.poll_input();
+            chip8.run_frame();
+            if let Some(fault) = chip8.fault() {
+                return Err(format!("Frame {}: {}", frame, fault));
+            }
+            frame += 1;
+        }
+        snapshots.push((frame, *chip8.framebuffer()));
+    }
+
+    Ok(snapshots)
+}
+
+fn snapshot_file(frame: usize) -> String {
+    format!("frame-{:05}.txt", frame)
+}
+
+/// Run `program` and write `spec` and its snapshots to `dir`, replacing any
+/// earlier recording.
+pub fn record<P: AsRef<Path>>(dir: P, program: &[u8], spec: &SnapshotSpec) -> Result<(), String> {
+    let dir = dir.as_ref();
+    let snapshots = take_snapshots(program, spec)?;
+
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Unable to create {}: {}", dir.display(), e))?;
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("").to_string();
+        if name.starts_with("frame-") && name.ends_with(".txt") {
+            fs::remove_file(&path).map_err(|e| format!("Unable to remove {}: {}", name, e))?;
+        }
+    }
+
+    let spec = toml::to_string(spec).map_err(|e| e.to_string())?;
+    write_file(&dir.join(SPEC_FILE), &spec)?;
+    for (frame, screen) in snapshots {
+        write_file(&dir.join(snapshot_file(frame)), &frame_to_string(&screen))?;
+    }
+
+    Ok(())
+}
+
+/// Read the spec of a recording in `dir`.
+pub fn load_spec<P: AsRef<Path>>(dir: P) -> Result<SnapshotSpec, String> {
+    let path = dir.as_ref().join(SPEC_FILE);
+    toml::from_str(&read_file(&path)?).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Run `program` as recorded in `dir` and compare the screens, returning the
+/// snapshots that don't match.
+pub fn verify<P: AsRef<Path>>(dir: P, program: &[u8]) -> Result<Vec<Mismatch>, String> {
+    let dir = dir.as_ref();
+    let spec = load_spec(dir)?;
+    if spec.rom != rom_id(program) {
+        return Err(format!("Snapshots in {} were recorded with another ROM ({})",
+                           dir.display(), spec.rom));
+    }
+
+    let mut mismatches = Vec::new();
+    for (frame, found) in take_snapshots(program, &spec)? {
+        let path = dir.join(snapshot_file(frame));
+        let expected = frame_from_str(&read_file(&path)?)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if expected != found {
+            mismatches.push(Mismatch {
+                frame: frame,
+                expected: expected,
+                found: found,
+            });
+        }
+    }
+
+    Ok(mismatches)
+}
+
+/// Both framebuffers in one picture: `#` and `.` where they agree, `+` for
+/// pixels only lit in `found` and `-` for pixels only lit in `expected`.
+/// Lines with differences are marked with `>`.
+pub fn diff(expected: &Framebuffer, found: &Framebuffer) -> String {
+    let mut text = String::with_capacity(67 * 32);
+    for y in 0..32 {
+        text.push_str(if expected.row(y) != found.row(y) { "> " } else { "  " });
+        for x in 0..64 {
+            text.push(match (expected.pixel(x, y), found.pixel(x, y)) {
+                (true, true) => '#',
+                (false, false) => '.',
+                (false, true) => '+',
+                (true, false) => '-',
+            });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn read_file(path: &Path) -> Result<String, String> {
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    Ok(text)
+}
+
+fn write_file(path: &Path, text: &str) -> Result<(), String> {
+    File::create(path)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+}
@@ -0,0 +1,29 @@
+/// Rows of the sprite `Draw` would read from `memory` at `index`, `height`
+/// bytes tall. Each row is 8 pixels with the leftmost first, rows past the
+/// end of memory are blank.
+pub fn sprite(memory: &[u8], index: usize, height: usize) -> Vec<[bool; 8]> {
+    (index..index + height)
+        .map(|addr| {
+            let byte = memory.get(addr).cloned().unwrap_or(0);
+            let mut row = [false; 8];
+            for (bit, pixel) in row.iter_mut().enumerate() {
+                *pixel = byte & (0x80 >> bit) != 0;
+            }
+            row
+        })
+        .collect()
+}
+
+/// A sprite as text, one line per row with `#` for set pixels and `.` for
+/// clear ones, followed by the byte value.
+pub fn sprite_to_string(memory: &[u8], index: usize, height: usize) -> String {
+    let mut text = String::new();
+    for (offset, row) in sprite(memory, index, height).iter().enumerate() {
+        for pixel in row.iter() {
+            text.push(if *pixel { '#' } else { '.' });
+        }
+        let byte = memory.get(index + offset).cloned().unwrap_or(0);
+        text.push_str(&format!("  {:02X}\n", byte));
+    }
+    text
+}
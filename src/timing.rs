@@ -0,0 +1,41 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Keeps a loop running at 60 Hz in real time.
+pub struct FramePacer {
+    rate: Duration,
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    pub fn new() -> FramePacer {
+        FramePacer {
+            rate: Duration::new(0, 1_000_000_000 / 60),
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// When the current frame ends.
+    pub fn deadline(&self) -> Instant {
+        self.next_frame + self.rate
+    }
+
+    /// Sleep until the current frame ends and start the next one.
+    pub fn wait(&mut self) {
+        self.next_frame += self.rate;
+        let now = Instant::now();
+        if self.next_frame > now {
+            thread::sleep(self.next_frame - now);
+        } else if now - self.next_frame > self.rate * 4 {
+            // Too far behind to catch up, don't try to run frames back to back
+            debug!("Dropped frames");
+            self.next_frame = now;
+        }
+    }
+}
+
+impl Default for FramePacer {
+    fn default() -> FramePacer {
+        FramePacer::new()
+    }
+}
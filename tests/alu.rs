@@ -0,0 +1,186 @@
+//! Exhaustive tests of the 8XYN instructions: every operation on every pair
+//! of values, and every opcode through the core, with and without the quirks
+//! that change them.
+
+#![cfg(feature = "std")]
+#![allow(clippy::redundant_field_names)]
+
+extern crate chip8_emulator;
+
+use chip8_emulator::alu::{execute, AluOp, AluResult, ALU_OPS};
+use chip8_emulator::headless::{headless, HeadlessConfig};
+use chip8_emulator::quirks::{Quirks, PRESET_NAMES};
+
+/// Every combination of the quirks that affect the ALU.
+fn alu_quirks() -> Vec<Quirks> {
+    let mut all = Vec::new();
+    for bits in 0..8 {
+        all.push(Quirks {
+            shift_vy: bits & 1 != 0,
+            vf_reset: bits & 2 != 0,
+            legacy_alu_flags: bits & 4 != 0,
+            ..Quirks::default()
+        });
+    }
+    all
+}
+
+/// The operations as written in the spec, with wide arithmetic instead of
+/// wrapping and overflow checks.
+fn expected(op: AluOp, x: u8, y: u8, quirks: &Quirks) -> AluResult {
+    let (wide_x, wide_y) = (x as u16, y as u16);
+    let shifted = if quirks.shift_vy { y } else { x };
+    let logic_flag = if quirks.vf_reset { Some(0) } else { None };
+    let (value, flag) = match op {
+        AluOp::Load => (y, None),
+        AluOp::Or => (x | y, logic_flag),
+        AluOp::And => (x & y, logic_flag),
+        AluOp::Xor => (x ^ y, logic_flag),
+        AluOp::Add => {
+            let sum = wide_x + wide_y;
+            let flag = if sum > 0xFF {
+                Some(1)
+            } else if quirks.legacy_alu_flags {
+                None
+            } else {
+                Some(0)
+            };
+            ((sum % 0x100) as u8, flag)
+        }
+        AluOp::Sub | AluOp::SubN => {
+            let (a, b) = if op == AluOp::Sub { (wide_x, wide_y) } else { (wide_y, wide_x) };
+            let no_borrow = if quirks.legacy_alu_flags { a > b } else { a >= b };
+            (((0x100 + a - b) % 0x100) as u8, Some(no_borrow as u8))
+        }
+        AluOp::ShiftR => (shifted / 2, Some(shifted % 2)),
+        AluOp::ShiftL => (((shifted as u16 * 2) % 0x100) as u8, Some((shifted >= 0x80) as u8)),
+    };
+
+    AluResult {
+        value: value,
+        flag: flag,
+    }
+}
+
+#[test]
+fn every_value() {
+    for quirks in alu_quirks() {
+        for op in ALU_OPS.iter() {
+            for x in 0..256 {
+                for y in 0..256 {
+                    let (x, y) = (x as u8, y as u8);
+                    assert_eq!(execute(*op, x, y, &quirks), expected(*op, x, y, &quirks),
+                               "{:?} {:02X} {:02X} {:?}", op, x, y, quirks);
+                }
+            }
+        }
+    }
+}
+
+/// Run a single 8XYN instruction with the given registers.
+fn run(op: AluOp, x: usize, y: usize, registers: &[u8; 16], quirks: &Quirks) -> [u8; 16] {
+    let opcode = 0x8000 | (x as u16) << 8 | (y as u16) << 4 | op.nibble() as u16;
+    let config = HeadlessConfig {
+        quirks: *quirks,
+        ..HeadlessConfig::default()
+    };
+    let mut chip8 = headless(&[(opcode >> 8) as u8, opcode as u8], &config).unwrap();
+
+    // Registers follow the magic, version and memory in a saved state
+    let mut state = chip8.save_state();
+    state[4101..4117].copy_from_slice(registers);
+    chip8.load_state(&state).unwrap();
+    chip8.step();
+
+    assert_eq!(chip8.pc(), 0x202);
+    chip8.registers()
+}
+
+/// Every 8XYN opcode, so VF and equal registers as operands too. The
+/// operands are read first, then VX is written and then VF, except for the
+/// arithmetic instructions with `legacy_alu_flags`, where the shifts read
+/// their operand again after writing VF.
+#[test]
+fn every_opcode() {
+    let values = [(0x00, 0x00), (0x01, 0x01), (0x05, 0x0A), (0x0A, 0x05), (0x7F, 0x81),
+                  (0x80, 0x80), (0xFF, 0x01), (0xFF, 0xFF)];
+    let mut presets: Vec<Quirks> = PRESET_NAMES.iter()
+        .map(|name| Quirks::preset(name).unwrap())
+        .collect();
+    presets.push(Quirks::default());
+    presets.push(Quirks { legacy_alu_flags: true, ..Quirks::default() });
+
+    for quirks in presets.iter() {
+        for op in ALU_OPS.iter() {
+            for x in 0..16 {
+                for y in 0..16 {
+                    for &(x_val, y_val) in values.iter() {
+                        let mut registers = [0x42; 16];
+                        registers[x] = x_val;
+                        registers[y] = y_val;
+
+                        let result = expected(*op, registers[x], registers[y], quirks);
+                        let mut after = registers;
+                        let arithmetic = *op != AluOp::Or && *op != AluOp::And && *op != AluOp::Xor;
+                        match result.flag {
+                            Some(flag) if quirks.legacy_alu_flags && arithmetic => {
+                                after[0xF] = flag;
+                                after[x] = match *op {
+                                    AluOp::ShiftR | AluOp::ShiftL => {
+                                        expected(*op, after[x], after[y], quirks).value
+                                    }
+                                    _ => result.value,
+                                };
+                            }
+                            Some(flag) => {
+                                after[x] = result.value;
+                                after[0xF] = flag;
+                            }
+                            None => after[x] = result.value,
+                        }
+
+                        assert_eq!(run(*op, x, y, &registers, quirks), after,
+                                   "8{:X}{:X}{:X} with {:02X?} {:?}",
+                                   x, y, op.nibble(), registers, quirks);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn flag_register_as_operand() {
+    let quirks = Quirks::default();
+    let legacy = Quirks { legacy_alu_flags: true, ..Quirks::default() };
+    let mut registers = [0; 16];
+
+    // 8F14: VF = VF + V1 overflows, the carry wins
+    registers[0xF] = 0xFF;
+    registers[0x1] = 0x01;
+    assert_eq!(run(AluOp::Add, 0xF, 0x1, &registers, &quirks)[0xF], 1);
+    assert_eq!(run(AluOp::Add, 0xF, 0x1, &registers, &legacy)[0xF], 0);
+
+    // 8F06: VF = VF >> 1 shifts out a 1
+    registers[0xF] = 0x03;
+    assert_eq!(run(AluOp::ShiftR, 0xF, 0x0, &registers, &quirks)[0xF], 1);
+
+    // 8F06 and 8F0E: the legacy shifts write the flag and then shift the new
+    // VF over it
+    assert_eq!(run(AluOp::ShiftR, 0xF, 0x0, &registers, &legacy)[0xF], 0);
+    assert_eq!(run(AluOp::ShiftL, 0xF, 0x0, &registers, &legacy)[0xF], 0);
+    registers[0xF] = 0x02;
+    assert_eq!(run(AluOp::ShiftR, 0xF, 0x0, &registers, &quirks)[0xF], 0);
+    assert_eq!(run(AluOp::ShiftR, 0xF, 0x0, &registers, &legacy)[0xF], 0);
+    registers[0xF] = 0x81;
+    assert_eq!(run(AluOp::ShiftL, 0xF, 0x0, &registers, &quirks)[0xF], 1);
+    assert_eq!(run(AluOp::ShiftL, 0xF, 0x0, &registers, &legacy)[0xF], 0x02);
+
+    // 81F5: V1 = V1 - VF with equal operands doesn't borrow
+    registers[0xF] = 0x05;
+    registers[0x1] = 0x05;
+    let result = run(AluOp::Sub, 0x1, 0xF, &registers, &quirks);
+    assert_eq!((result[0x1], result[0xF]), (0, 1));
+    let result = run(AluOp::Sub, 0x1, 0xF, &registers, &legacy);
+    assert_eq!((result[0x1], result[0xF]), (0, 0));
+}
@@ -0,0 +1,208 @@
+//! Lockstep differential tests of the block translator: every program runs
+//! on the plain interpreter and as translated blocks, and the two machines
+//! must agree after every frame.
+
+#![cfg(feature = "std")]
+#![allow(clippy::redundant_field_names)]
+
+extern crate chip8_emulator;
+extern crate proptest;
+
+mod programs;
+
+use proptest::prelude::*;
+
+use chip8_emulator::blocks::Backend;
+use chip8_emulator::headless::{headless, HeadlessChip8, HeadlessConfig, ScriptedInput};
+use chip8_emulator::quirks::{Quirks, PRESET_NAMES};
+
+fn machines(program: &[u8], config: &HeadlessConfig) -> (HeadlessChip8, HeadlessChip8) {
+    let interpreter = headless(program, config).unwrap();
+    let blocks = headless(program, &HeadlessConfig { backend: Backend::Blocks, ..config.clone() })
+        .unwrap();
+    assert_eq!(blocks.backend(), Backend::Blocks);
+
+    (interpreter, blocks)
+}
+
+fn assert_same(interpreter: &HeadlessChip8, blocks: &HeadlessChip8, frame: usize) {
+    assert!(interpreter.save_state() == blocks.save_state(), "state differs on frame {}", frame);
+    assert!(interpreter.framebuffer() == blocks.framebuffer(),
+            "screen differs on frame {}", frame);
+    assert_eq!(interpreter.fault(), blocks.fault(), "fault differs on frame {}", frame);
+    assert_eq!(interpreter.instructions(), blocks.instructions(),
+               "instruction count differs on frame {}", frame);
+    assert_eq!(interpreter.breakpoint_hit(), blocks.breakpoint_hit(),
+               "breakpoint differs on frame {}", frame);
+}
+
+fn run(program: &[u8], config: &HeadlessConfig, frames: usize) -> HeadlessChip8 {
+    let (mut interpreter, mut blocks) = machines(program, config);
+    for frame in 0..frames {
+        for chip8 in [&mut interpreter, &mut blocks].iter_mut() {
+            chip8.poll_input();
+            chip8.run_frame();
+        }
+        assert_same(&interpreter, &blocks, frame);
+    }
+
+    blocks
+}
+
+#[test]
+fn programs() {
+    let programs = [(programs::ALU, ""), (programs::FLOW, "5:3 20:5 30:-5 40:5 60:-5"),
+                    (programs::MEMORY, "")];
+    let mut presets = vec![Quirks::default()];
+    presets.extend(PRESET_NAMES.iter().map(|name| Quirks::preset(name).unwrap()));
+
+    for &(program, input) in programs.iter() {
+        for quirks in presets.iter() {
+            // Odd speeds end frames in the middle of blocks
+            for &speed in [1, 7, 9, 1000].iter() {
+                let config = HeadlessConfig {
+                    quirks: *quirks,
+                    cycles_per_frame: speed,
+                    input: ScriptedInput::parse(input).unwrap(),
+                    ..HeadlessConfig::default()
+                };
+                run(program, &config, 200);
+            }
+        }
+    }
+}
+
+// LD V3, 0; loop: ADD V3, 1; LD V0, #12; LD V1, #18; LD I, #300; ADD I, V3;
+// LD DT, V3; SE V3, 2; JP target; LD I, target; LD [I], V1; target: JP loop;
+// LD V4, 42; end: JP end
+//
+// The second time around target is patched to JP #218
+const PATCH_JUMP: &[u8] = &[0x63, 0x00, 0x73, 0x01, 0x60, 0x12, 0x61, 0x18, 0xA3, 0x00,
+                            0xF3, 0x1E, 0xF3, 0x15, 0x33, 0x02, 0x12, 0x16, 0xA2, 0x16,
+                            0xF1, 0x55, 0x12, 0x02, 0x64, 0x2A, 0x12, 0x1A];
+
+#[test]
+fn self_modifying() {
+    let chip8 = run(PATCH_JUMP, &HeadlessConfig::default(), 10);
+    assert_eq!(chip8.pc(), 0x21A);
+    assert_eq!(chip8.registers()[4], 42);
+}
+
+#[test]
+fn blocks_are_patched() {
+    // LD V0, 1; LD V1, 2; LD V2, 3; LD V3, 4; end: JP end
+    let program = [0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x63, 0x04, 0x12, 0x08];
+    let (mut interpreter, mut blocks) = machines(&program, &HeadlessConfig::default());
+    for frame in 0..4 {
+        for chip8 in [&mut interpreter, &mut blocks].iter_mut() {
+            chip8.run_frame();
+            // Change the middle of the block and jump back to its start
+            chip8.write_memory(0x205, &[0x10 + frame as u8]).unwrap();
+            chip8.write_memory(0x209, &[0x00]).unwrap();
+        }
+        assert_same(&interpreter, &blocks, frame);
+        let expected = if frame == 0 { 3 } else { 0x10 + frame as u8 - 1 };
+        assert_eq!(blocks.registers()[2], expected);
+    }
+}
+
+#[test]
+fn quirks_change() {
+    // LD V0, 5; LD V1, 3; SHR V0, V1; JP #200
+    let program = [0x60, 0x05, 0x61, 0x03, 0x80, 0x16, 0x12, 0x00];
+    let config = HeadlessConfig {
+        cycles_per_frame: 8,
+        ..HeadlessConfig::default()
+    };
+    let (mut interpreter, mut blocks) = machines(&program, &config);
+    for (frame, &shift_vy) in [false, true, false].iter().enumerate() {
+        for chip8 in [&mut interpreter, &mut blocks].iter_mut() {
+            chip8.set_quirks(Quirks { shift_vy: shift_vy, ..Quirks::default() });
+            chip8.run_frame();
+        }
+        assert_same(&interpreter, &blocks, frame);
+        assert_eq!(blocks.registers()[0], if shift_vy { 1 } else { 2 });
+    }
+}
+
+#[test]
+fn breakpoints_inside_blocks() {
+    // LD V0, 1; ADD V0, 1; ADD V0, 1; ADD V0, 1; JP #200
+    let program = [0x60, 0x01, 0x70, 0x01, 0x70, 0x01, 0x70, 0x01, 0x12, 0x00];
+    let (mut interpreter, mut blocks) = machines(&program, &HeadlessConfig::default());
+    for chip8 in [&mut interpreter, &mut blocks].iter_mut() {
+        chip8.set_breakpoint(0x204, true);
+    }
+
+    for frame in 0..6 {
+        for chip8 in [&mut interpreter, &mut blocks].iter_mut() {
+            chip8.run_frame();
+        }
+        assert_same(&interpreter, &blocks, frame);
+        assert!(blocks.breakpoint_hit());
+        assert_eq!(blocks.pc(), 0x204);
+    }
+
+    for chip8 in [&mut interpreter, &mut blocks].iter_mut() {
+        chip8.step();
+        chip8.set_breakpoint(0x204, false);
+        chip8.run_frame();
+    }
+    assert_same(&interpreter, &blocks, 6);
+}
+
+/// Opcodes of one instruction with random operands.
+fn opcodes(base: u16, mask: u16) -> BoxedStrategy<u16> {
+    any::<u16>().prop_map(move |operands| base | (operands & mask)).boxed()
+}
+
+/// Opcodes that take an address, kept within the 64 bytes of the program.
+fn addressed(base: u16) -> BoxedStrategy<u16> {
+    any::<u16>().prop_map(move |addr| base | (0x200 + addr % 0x40)).boxed()
+}
+
+fn straight_line() -> BoxedStrategy<u16> {
+    prop_oneof![
+        opcodes(0x0100, 0x0FF), opcodes(0x6000, 0xFFF), opcodes(0x7000, 0xFFF),
+        opcodes(0x8000, 0xFF0), opcodes(0x8001, 0xFF0), opcodes(0x8002, 0xFF0),
+        opcodes(0x8003, 0xFF0), opcodes(0x8004, 0xFF0), opcodes(0x8005, 0xFF0),
+        opcodes(0x8006, 0xFF0), opcodes(0x8007, 0xFF0), opcodes(0x800E, 0xFF0),
+        opcodes(0xF007, 0xF00), opcodes(0xF015, 0xF00), opcodes(0xF018, 0xF00),
+        opcodes(0xF01E, 0xF00), opcodes(0xF029, 0xF00),
+    ].boxed()
+}
+
+fn block_end() -> BoxedStrategy<u16> {
+    prop_oneof![
+        addressed(0x1000), addressed(0x2000), addressed(0xA000), addressed(0xB000),
+        Just(0x00E0), Just(0x00EE), opcodes(0x3000, 0xFFF), opcodes(0x4000, 0xFFF),
+        opcodes(0x5000, 0xFF0), opcodes(0x9000, 0xFF0), opcodes(0xC000, 0xFFF),
+        opcodes(0xD000, 0xFFF), opcodes(0xE09E, 0xF00), opcodes(0xE0A1, 0xF00),
+        opcodes(0xF033, 0xF00), opcodes(0xF055, 0xF00), opcodes(0xF065, 0xF00),
+    ].boxed()
+}
+
+proptest! {
+    /// Random programs, mostly straight-line code broken up by jumps, skips
+    /// and stores into the program itself.
+    #[test]
+    fn random_programs(ref program in prop::collection::vec(prop_oneof![
+        3 => straight_line(), 1 => block_end(),
+    ], 32), preset in 0..4usize, speed in 1..40usize, ref input in "([0-9]:[0-9a-f] ){0,4}") {
+        let bytes: Vec<u8> = program.iter()
+            .flat_map(|opcode| vec![(opcode >> 8) as u8, *opcode as u8])
+            .collect();
+        let quirks = match preset {
+            0 => Quirks::default(),
+            n => Quirks::preset(PRESET_NAMES[n - 1]).unwrap(),
+        };
+        let config = HeadlessConfig {
+            quirks: quirks,
+            cycles_per_frame: speed,
+            seed: 7,
+            input: ScriptedInput::parse(input).unwrap(),
+            ..HeadlessConfig::default()
+        };
+        run(&bytes, &config, 20);
+    }
+}
@@ -0,0 +1,247 @@
+//! Runs test programs without a window and compares the screen after a fixed
+//! number of frames against golden images in `tests/golden`, once for each
+//! quirk preset.
+//!
+//! Only the programs in `tests/programs` have golden images committed. The
+//! community test ROMs aren't distributed with the crate and neither are
+//! their golden images. Copy them to `tests/roms` to include them. ROMs that
+//! aren't there are skipped, unless `CHIP8_REQUIRE_TEST_ROMS` is set, which
+//! makes them fail instead. Run the tests with `CHIP8_UPDATE_GOLDEN=1` to
+//! write the golden images after checking the screens are right.
+
+#![cfg(feature = "std")]
+#![allow(clippy::redundant_field_names)]
+
+extern crate chip8_emulator;
+
+mod programs;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use chip8_emulator::headless::{frame_from_str, frame_to_string, run_headless, Framebuffer,
+                               HeadlessConfig, ScriptedInput};
+use chip8_emulator::quirks::{Quirks, PRESET_NAMES};
+use chip8_emulator::snapshot::diff;
+
+/// Frames to run the programs in `programs` for, enough for all of them to
+/// finish with `display_wait`.
+const PROGRAM_FRAMES: usize = 200;
+
+/// A community test ROM and how to run it.
+struct TestRom {
+    name: &'static str,
+    file: &'static str,
+    frames: usize,
+    /// Byte written to 0x1FF before starting. The Timendus test suite reads
+    /// it to pick a test or platform instead of showing a menu.
+    select: Option<u8>,
+    input: &'static str,
+}
+
+const TEST_ROMS: [TestRom; 7] = [
+    TestRom { name: "chip8-logo", file: "1-chip8-logo.ch8", frames: 60, select: None, input: "" },
+    TestRom { name: "ibm-logo", file: "2-ibm-logo.ch8", frames: 60, select: None, input: "" },
+    TestRom { name: "corax", file: "3-corax+.ch8", frames: 60, select: None, input: "" },
+    TestRom { name: "flags", file: "4-flags.ch8", frames: 120, select: None, input: "" },
+    // The platform is chosen by preset in `run_rom`
+    TestRom { name: "quirks", file: "5-quirks.ch8", frames: 600, select: Some(0), input: "" },
+    // The FX0A test, pressing and releasing A
+    TestRom { name: "keypad", file: "6-keypad.ch8", frames: 120, select: Some(3),
+              input: "30:A 40:-A" },
+    TestRom { name: "bc-test", file: "BC_test.ch8", frames: 120, select: None, input: "" },
+];
+
+fn test_dir(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join(name)
+}
+
+/// Quirks for each preset, plus the ones used when no platform is set.
+fn presets() -> Vec<(&'static str, Quirks)> {
+    let mut presets = vec![("default", Quirks::default())];
+    for name in PRESET_NAMES.iter() {
+        presets.push((name, Quirks::preset(name).unwrap()));
+    }
+    presets
+}
+
+/// Compare `frame` with the golden image `name`, or write it when
+/// `CHIP8_UPDATE_GOLDEN` is set.
+fn check_golden(name: &str, frame: &Framebuffer) -> Result<(), String> {
+    let path = test_dir("golden").join(format!("{}.txt", name));
+    if env::var_os("CHIP8_UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).map_err(|e| e.to_string())?;
+        let mut file = File::create(&path).map_err(|e| e.to_string())?;
+        return file.write_all(frame_to_string(frame).as_bytes()).map_err(|e| e.to_string());
+    }
+
+    let mut text = String::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|e| format!("{}: {}, run with CHIP8_UPDATE_GOLDEN=1 to create it",
+                             path.display(), e))?;
+    let golden = frame_from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    if golden != *frame {
+        return Err(format!("{} doesn't match the golden image, + is lit only now and - only \
+                            in the image:\n{}", name, diff(&golden, frame)));
+    }
+
+    Ok(())
+}
+
+fn run_program(name: &str, program: &[u8], input: &str) {
+    let mut failures = Vec::new();
+    for (preset, quirks) in presets() {
+        let config = HeadlessConfig {
+            quirks: quirks,
+            input: ScriptedInput::parse(input).unwrap(),
+            ..HeadlessConfig::default()
+        };
+
+        let result = run_headless(program, &config, PROGRAM_FRAMES)
+            .and_then(|frame| check_golden(&format!("{}-{}", name, preset), &frame));
+        if let Err(e) = result {
+            failures.push(e);
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn alu() {
+    run_program("alu", programs::ALU, "");
+}
+
+#[test]
+fn flow() {
+    run_program("flow", programs::FLOW, "5:3 20:5 30:-5 40:5 60:-5");
+}
+
+#[test]
+fn memory() {
+    run_program("memory", programs::MEMORY, "");
+}
+
+/// Run the test ROM under every preset, returns false if it isn't in
+/// `tests/roms`.
+fn run_rom(rom: &TestRom) -> Result<bool, String> {
+    let path = test_dir("roms").join(rom.file);
+    let mut program = Vec::new();
+    match File::open(&path) {
+        Ok(mut file) => file.read_to_end(&mut program).map_err(|e| e.to_string())?,
+        Err(_) => return Ok(false),
+    };
+
+    for (platform, (preset, quirks)) in presets().into_iter().enumerate() {
+        let select = match rom.select {
+            // The quirks test numbers its platforms from 1 like `PRESET_NAMES`
+            Some(0) => platform.max(1) as u8,
+            other => other.unwrap_or(0),
+        };
+        let config = HeadlessConfig {
+            quirks: quirks,
+            pokes: if rom.select.is_some() { vec![(0x1FF, select)] } else { Vec::new() },
+            input: ScriptedInput::parse(rom.input)?,
+            ..HeadlessConfig::default()
+        };
+
+        let frame = run_headless(&program, &config, rom.frames)?;
+        check_golden(&format!("{}-{}", rom.name, preset), &frame)?;
+    }
+
+    Ok(true)
+}
+
+#[test]
+fn test_roms() {
+    let required = env::var_os("CHIP8_REQUIRE_TEST_ROMS").is_some();
+    let mut failures = Vec::new();
+    for rom in TEST_ROMS.iter() {
+        match run_rom(rom) {
+            Ok(true) => {}
+            Ok(false) if required => {
+                failures.push(format!("{}: {} is not in tests/roms", rom.name, rom.file));
+            }
+            Ok(false) => {}
+            Err(e) => failures.push(format!("{}: {}", rom.name, e)),
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+/// Every instruction is run by one of the programs. Checked on the program
+/// bytes, so data could count too, but each program keeps its data at the
+/// end where it's hard to miss.
+#[test]
+fn programs_cover_every_instruction() {
+    fn kind(op: u16) -> Option<&'static str> {
+        let n = op & 0xF;
+        let nn = op & 0xFF;
+        Some(match op >> 12 {
+            0x0 if op == 0x00E0 => "00E0",
+            0x0 if op == 0x00EE => "00EE",
+            0x0 => "0NNN",
+            0x1 => "1NNN",
+            0x2 => "2NNN",
+            0x3 => "3XNN",
+            0x4 => "4XNN",
+            0x5 if n == 0 => "5XY0",
+            0x6 => "6XNN",
+            0x7 => "7XNN",
+            0x8 => match n {
+                0x0 => "8XY0",
+                0x1 => "8XY1",
+                0x2 => "8XY2",
+                0x3 => "8XY3",
+                0x4 => "8XY4",
+                0x5 => "8XY5",
+                0x6 => "8XY6",
+                0x7 => "8XY7",
+                0xE => "8XYE",
+                _ => return None,
+            },
+            0x9 if n == 0 => "9XY0",
+            0xA => "ANNN",
+            0xB => "BNNN",
+            0xC => "CXNN",
+            0xD => "DXYN",
+            0xE if nn == 0x9E => "EX9E",
+            0xE if nn == 0xA1 => "EXA1",
+            0xF => match nn {
+                0x07 => "FX07",
+                0x0A => "FX0A",
+                0x15 => "FX15",
+                0x18 => "FX18",
+                0x1E => "FX1E",
+                0x29 => "FX29",
+                0x33 => "FX33",
+                0x55 => "FX55",
+                0x65 => "FX65",
+                _ => return None,
+            },
+            _ => return None,
+        })
+    }
+
+    let all = ["00E0", "00EE", "0NNN", "1NNN", "2NNN", "3XNN", "4XNN", "5XY0", "6XNN", "7XNN",
+               "8XY0", "8XY1", "8XY2", "8XY3", "8XY4", "8XY5", "8XY6", "8XY7", "8XYE", "9XY0",
+               "ANNN", "BNNN", "CXNN", "DXYN", "EX9E", "EXA1", "FX07", "FX0A", "FX15", "FX18",
+               "FX1E", "FX29", "FX33", "FX55", "FX65"];
+
+    let mut covered = Vec::new();
+    for program in [programs::ALU, programs::FLOW, programs::MEMORY].iter() {
+        for word in program.chunks(2).filter(|word| word.len() == 2) {
+            if let Some(kind) = kind((word[0] as u16) << 8 | word[1] as u16) {
+                covered.push(kind);
+            }
+        }
+    }
+
+    let missing: Vec<&str> = all.iter().cloned().filter(|kind| !covered.contains(kind)).collect();
+    assert!(missing.is_empty(), "Not covered: {:?}", missing);
+}
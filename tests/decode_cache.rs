@@ -0,0 +1,132 @@
+//! The decode cache must never change what a program does: programs run in
+//! lockstep with and without it, including ones that write over their own
+//! code.
+
+#![cfg(feature = "std")]
+
+extern crate chip8_emulator;
+extern crate proptest;
+
+mod programs;
+
+use proptest::prelude::*;
+
+use chip8_emulator::headless::{headless, HeadlessChip8, HeadlessConfig, ScriptedInput};
+use chip8_emulator::quirks::Quirks;
+
+fn run(program: &[u8], config: &HeadlessConfig, frames: usize)
+       -> (HeadlessChip8, HeadlessChip8) {
+    let mut plain = headless(program, config).unwrap();
+    let mut cached = headless(program, &HeadlessConfig { decode_cache: true, ..config.clone() })
+        .unwrap();
+    assert!(cached.decode_cache() && !plain.decode_cache());
+
+    for frame in 0..frames {
+        for chip8 in [&mut plain, &mut cached].iter_mut() {
+            chip8.poll_input();
+            chip8.run_frame();
+        }
+        assert!(plain.save_state() == cached.save_state(), "state differs on frame {}", frame);
+        assert!(plain.framebuffer() == cached.framebuffer(), "screen differs on frame {}", frame);
+        assert_eq!(plain.fault(), cached.fault());
+        assert_eq!(plain.instructions(), cached.instructions());
+    }
+
+    (plain, cached)
+}
+
+#[test]
+fn programs() {
+    let programs = [(programs::ALU, ""), (programs::FLOW, "5:3 20:5 30:-5 40:5 60:-5"),
+                    (programs::MEMORY, "")];
+    for &(program, input) in programs.iter() {
+        let config = HeadlessConfig {
+            input: ScriptedInput::parse(input).unwrap(),
+            ..HeadlessConfig::default()
+        };
+        run(program, &config, 200);
+    }
+}
+
+/// Run a program that patches an instruction it already ran.
+fn patched(program: &[u8]) -> HeadlessChip8 {
+    let (_, cached) = run(program, &HeadlessConfig::default(), 10);
+    assert_eq!(cached.pc(), 0x216);
+    assert_eq!(cached.registers()[4], 42);
+    cached
+}
+
+#[test]
+fn store_registers_invalidates() {
+    // LD V0, #12; LD V1, #14; LD V3, 0; loop: ADD V3, 1; SE V3, 2; JP target;
+    // LD I, target; LD [I], V1; JP target; target: JP loop; LD V4, 42; end: JP end
+    //
+    // The second time around target is patched to JP #214
+    patched(&[0x60, 0x12, 0x61, 0x14, 0x63, 0x00, 0x73, 0x01, 0x33, 0x02, 0x12, 0x12,
+              0xA2, 0x12, 0xF1, 0x55, 0x12, 0x12, 0x12, 0x06, 0x64, 0x2A, 0x12, 0x16]);
+}
+
+#[test]
+fn load_bcd_invalidates() {
+    // LD V3, 0; loop: ADD V3, 1; SE V3, 2; JP target; LD V5, 0; LD I, target;
+    // LD B, V5; target: JP loop; SYS 0; SYS 0; LD V4, 42; end: JP end
+    //
+    // The second time around target and the byte after it are zeroed, so
+    // the program falls through to LD V4, 42
+    patched(&[0x63, 0x00, 0x73, 0x01, 0x33, 0x02, 0x12, 0x0E, 0x65, 0x00, 0xA2, 0x0E,
+              0xF5, 0x33, 0x12, 0x02, 0x00, 0x00, 0x00, 0x00, 0x64, 0x2A, 0x12, 0x16]);
+}
+
+#[test]
+fn write_memory_invalidates() {
+    // LD V4, 42; end: JP end
+    let mut chip8 = headless(&[0x64, 0x2A, 0x12, 0x02],
+                             &HeadlessConfig { decode_cache: true, ..HeadlessConfig::default() })
+        .unwrap();
+    chip8.run_frame();
+    assert_eq!(chip8.registers()[4], 42);
+
+    // Patch the low byte of the jump, then the instruction before it
+    chip8.write_memory(0x203, &[0x00]).unwrap();
+    chip8.run_frame();
+    assert_eq!(chip8.pc(), 0x200);
+    chip8.write_memory(0x200, &[0x65]).unwrap();
+    chip8.run_frame();
+    assert_eq!(chip8.registers()[5], 42);
+}
+
+/// Opcodes of one instruction with random operands.
+fn opcodes(base: u16, mask: u16) -> BoxedStrategy<u16> {
+    any::<u16>().prop_map(move |operands| base | (operands & mask)).boxed()
+}
+
+/// Opcodes that take an address, kept within the 64 bytes of the program.
+fn addressed(base: u16) -> BoxedStrategy<u16> {
+    any::<u16>().prop_map(move |addr| base | (0x200 + addr % 0x40)).boxed()
+}
+
+proptest! {
+    /// Random programs that jump around and store into their own code.
+    #[test]
+    fn self_modifying(ref program in prop::collection::vec(prop_oneof![
+        addressed(0x1000), addressed(0x2000), addressed(0xA000), addressed(0xB000),
+        Just(0x00EE), opcodes(0x3000, 0xFFF), opcodes(0x6000, 0xFFF), opcodes(0x7000, 0xFFF),
+        opcodes(0x8004, 0xFF0), opcodes(0xC000, 0xFFF), opcodes(0xD000, 0xFFF),
+        opcodes(0xF01E, 0xF00), opcodes(0xF033, 0xF00), opcodes(0xF055, 0xF00),
+        opcodes(0xF065, 0xF00),
+    ], 32), quirks in 0..4u8) {
+        let bytes: Vec<u8> = program.iter()
+            .flat_map(|opcode| vec![(opcode >> 8) as u8, *opcode as u8])
+            .collect();
+        let config = HeadlessConfig {
+            quirks: Quirks {
+                load_store_increment: quirks & 1 != 0,
+                jump_vx: quirks & 2 != 0,
+                ..Quirks::default()
+            },
+            cycles_per_frame: 50,
+            ..HeadlessConfig::default()
+        };
+        run(&bytes, &config, 20);
+    }
+}
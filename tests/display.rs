@@ -0,0 +1,86 @@
+#![cfg(feature = "std")]
+
+extern crate chip8_emulator;
+
+mod programs;
+
+use chip8_emulator::core::Chip8;
+use chip8_emulator::headless::{headless, HeadlessConfig, ScriptedInput};
+use chip8_emulator::peripherals::{Chip8Disp, Framebuffer, Region};
+use chip8_emulator::quirks::{Quirks, PRESET_NAMES};
+
+/// Remembers the dirty region of every present.
+struct Recorder {
+    presents: Vec<Option<Region>>,
+}
+
+impl Chip8Disp for Recorder {
+    fn present(&mut self, _frame: &Framebuffer, dirty: Option<Region>) {
+        self.presents.push(dirty);
+    }
+}
+
+/// The headless display only copies dirty regions, so it ends up with the
+/// core's framebuffer only if every change was reported.
+#[test]
+fn dirty_regions_cover_changes() {
+    let programs = [(programs::ALU, ""), (programs::FLOW, "5:3 20:5 30:-5 40:5 60:-5"),
+                    (programs::MEMORY, "")];
+    let mut presets = vec![Quirks::default()];
+    presets.extend(PRESET_NAMES.iter().map(|name| Quirks::preset(name).unwrap()));
+
+    for &(program, input) in programs.iter() {
+        for quirks in presets.iter() {
+            let config = HeadlessConfig {
+                quirks: *quirks,
+                input: ScriptedInput::parse(input).unwrap(),
+                ..HeadlessConfig::default()
+            };
+            let mut chip8 = headless(program, &config).unwrap();
+            for _ in 0..200 {
+                chip8.poll_input();
+                chip8.run_frame();
+                chip8.present();
+                assert!(chip8.display().unwrap().frame() == chip8.framebuffer());
+            }
+        }
+    }
+}
+
+#[test]
+fn present_only_changes() {
+    // LD V0, 62; LD V1, 30; LD F, V2; DRW V0, V1, 5; DRW V0, V1, 5; DRW V0, V1, 0;
+    // CLS; end: JP end
+    let program = [0x60, 0x3E, 0x61, 0x1E, 0xF2, 0x29, 0xD0, 0x15, 0xD0, 0x15, 0xD0, 0x10,
+                   0x00, 0xE0, 0x12, 0x0E];
+    let mut chip8: Chip8<Recorder, ScriptedInput> = Chip8::new();
+    chip8.set_quirks(Quirks { display_wait: true, ..Quirks::default() });
+    chip8.upload_rom(&program).unwrap();
+    chip8.connect_display(Recorder { presents: Vec::new() });
+    for _ in 0..6 {
+        chip8.run_frame();
+        chip8.present();
+    }
+
+    // Every draw waits for its own frame. The first frame shows the blank
+    // screen, the sprite at (62, 30) is clipped to 2x2 pixels, the second
+    // draw turns them off again and the empty one changes nothing
+    let sprite = Some(Region { x: 62, y: 30, width: 2, height: 2 });
+    assert_eq!(chip8.display().unwrap().presents,
+               vec![Some(Region::full()), sprite, sprite, Some(Region::full())]);
+}
+
+#[test]
+fn collisions() {
+    // LD I, sprite; DRW V0, V0, 1; LD V1, VF; DRW V0, V0, 1; LD V2, VF;
+    // DRW V0, V0, 1; end: JP end; sprite: #81
+    let program = [0xA2, 0x0E, 0xD0, 0x01, 0x81, 0xF0, 0xD0, 0x01, 0x82, 0xF0, 0xD0, 0x01,
+                   0x12, 0x0C, 0x81, 0x00];
+    let mut chip8 = headless(&program, &HeadlessConfig::default()).unwrap();
+    chip8.run_frame();
+
+    assert_eq!(&chip8.registers()[1..3], &[0, 1]);
+    assert_eq!(chip8.registers()[0xF], 0);
+    let lit: Vec<usize> = (0..64).filter(|&x| chip8.framebuffer().pixel(x, 0)).collect();
+    assert_eq!(lit, vec![0, 7]);
+}
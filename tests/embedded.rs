@@ -0,0 +1,143 @@
+//! The core as firmware sees it: this test crate is `no_std`, so it only
+//! reaches the API that is left when the crate is built without std.
+
+#![no_std]
+
+extern crate chip8_emulator;
+
+mod programs;
+
+use chip8_emulator::chip8::Chip8;
+use chip8_emulator::error::Error;
+use chip8_emulator::peripherals::{Chip8Disp, Chip8Input, Chip8Key, Framebuffer, KeyEvent,
+                                  KeyQueue, Region};
+
+/// An SSD1306 style LCD: pages of 8 rows with the top row in the low bit.
+struct Lcd {
+    pages: [[u8; 64]; 4],
+}
+
+impl Lcd {
+    fn pixel(&self, x: usize, y: usize) -> bool {
+        self.pages[y / 8][x] & (1 << (y % 8)) != 0
+    }
+}
+
+impl Chip8Disp for Lcd {
+    fn present(&mut self, frame: &Framebuffer, dirty: Option<Region>) {
+        if let Some(dirty) = dirty {
+            for y in dirty.y..(dirty.y + dirty.height) {
+                for x in dirty.x..(dirty.x + dirty.width) {
+                    if frame.pixel(x, y) {
+                        self.pages[y / 8][x] |= 1 << (y % 8);
+                    } else {
+                        self.pages[y / 8][x] &= !(1 << (y % 8));
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Buttons {
+    keys: KeyQueue,
+}
+
+impl Chip8Input for Buttons {
+    fn key_pressed(&self, key: Chip8Key) -> bool {
+        self.keys.key_pressed(key)
+    }
+
+    fn next_event(&mut self) -> Option<KeyEvent> {
+        self.keys.next_event()
+    }
+
+    fn poll(&mut self) -> bool {
+        false
+    }
+}
+
+fn chip8(program: &[u8]) -> Chip8<Lcd, Buttons> {
+    let mut chip8 = Chip8::new();
+    chip8.seed_rng(1);
+    chip8.upload_rom(program).unwrap();
+    chip8.connect_display(Lcd { pages: [[0; 64]; 4] });
+    chip8.connect_keyboard(Buttons { keys: KeyQueue::new() });
+    chip8
+}
+
+fn run(chip8: &mut Chip8<Lcd, Buttons>, frames: usize) {
+    for _ in 0..frames {
+        chip8.poll_input();
+        chip8.run_frame();
+        chip8.present();
+    }
+    assert_eq!(chip8.fault(), None);
+}
+
+fn assert_lcd_matches(chip8: &Chip8<Lcd, Buttons>) {
+    let lcd = chip8.display().unwrap();
+    for y in 0..32 {
+        for x in 0..64 {
+            assert_eq!(lcd.pixel(x, y), chip8.framebuffer().pixel(x, y), "pixel {}, {}", x, y);
+        }
+    }
+}
+
+#[test]
+fn digits() {
+    // Draw the 16 font digits in rows of 8
+    let mut chip8 = chip8(&[0x60, 0x00, 0x61, 0x00, 0x62, 0x00, 0xF0, 0x29, 0xD1, 0x25, 0x70, 0x01,
+                            0x71, 0x05, 0x31, 0x28, 0x12, 0x06, 0x61, 0x00, 0x72, 0x06, 0x30, 0x10,
+                            0x12, 0x06, 0x12, 0x1A]);
+    run(&mut chip8, 20);
+    assert_eq!(chip8.pc(), 0x21A);
+    assert_lcd_matches(&chip8);
+
+    // The "0" glyph at the top left: F0 90 90 90 F0
+    let lcd = chip8.display().unwrap();
+    for (y, &row) in [0xF0u8, 0x90, 0x90, 0x90, 0xF0].iter().enumerate() {
+        for x in 0..5 {
+            assert_eq!(lcd.pixel(x, y), row & (0x80 >> x) != 0);
+        }
+    }
+}
+
+#[test]
+fn alu_program() {
+    let mut chip8 = chip8(programs::ALU);
+    run(&mut chip8, 200);
+    assert_lcd_matches(&chip8);
+}
+
+#[test]
+fn load_key() {
+    // LD V5, K; end: JP end
+    let mut chip8 = chip8(&[0xF5, 0x0A, 0x12, 0x02]);
+    run(&mut chip8, 2);
+    assert_eq!(chip8.pc(), 0x200);
+
+    chip8.keyboard_mut().unwrap().keys.set_key(Chip8Key::Key7, true);
+    run(&mut chip8, 1);
+    assert_eq!(chip8.pc(), 0x200);
+    chip8.keyboard_mut().unwrap().keys.set_key(Chip8Key::Key7, false);
+    run(&mut chip8, 1);
+    assert_eq!(chip8.pc(), 0x202);
+    assert_eq!(chip8.registers()[5], 7);
+}
+
+#[test]
+fn errors() {
+    let mut chip8 = chip8(&[]);
+    assert_eq!(chip8.upload_rom(&[0; 4000]), Err(Error::ProgramTooLong(4000)));
+    assert_eq!(chip8.load_state(&[0; 10]), Err(Error::NotAState));
+    assert_eq!(Chip8Key::new(16), Err(Error::InvalidKey(16)));
+}
+
+#[test]
+fn seeded_random() {
+    // RND V0, #FF; end: JP end
+    let mut chip8 = chip8(&[0xC0, 0xFF, 0x12, 0x02]);
+    run(&mut chip8, 1);
+    assert_eq!(chip8.registers()[0], 0x30);
+}
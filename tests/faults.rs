@@ -0,0 +1,92 @@
+#![cfg(feature = "std")]
+
+extern crate chip8_emulator;
+
+use chip8_emulator::core::Fault;
+use chip8_emulator::headless::{headless, HeadlessConfig};
+use chip8_emulator::quirks::{Quirks, PRESET_NAMES};
+
+/// Run `program` for a second, returning the fault that stopped it.
+fn run(program: &[u8], config: &HeadlessConfig) -> Option<Fault> {
+    let mut chip8 = headless(program, config).unwrap();
+    for _ in 0..60 {
+        chip8.poll_input();
+        chip8.run_frame();
+        if chip8.fault().is_some() {
+            break;
+        }
+    }
+
+    chip8.fault()
+}
+
+fn fault(program: &[u8]) -> Option<Fault> {
+    run(program, &HeadlessConfig::default())
+}
+
+#[test]
+fn stack() {
+    assert_eq!(fault(&[0x00, 0xEE]), Some(Fault::StackUnderflow { pc: 0x200 }));
+    // Calls itself until the stack is full
+    assert_eq!(fault(&[0x22, 0x00]), Some(Fault::StackOverflow { pc: 0x200 }));
+}
+
+#[test]
+fn memory_through_index() {
+    // LD I, #FFF; DRW V0, V0, 5
+    assert_eq!(fault(&[0xAF, 0xFF, 0xD0, 0x05]),
+               Some(Fault::MemoryOutOfBounds { pc: 0x202, index: 0xFFF }));
+    // LD I, #FFE; LD B, V0
+    assert_eq!(fault(&[0xAF, 0xFE, 0xF0, 0x33]),
+               Some(Fault::MemoryOutOfBounds { pc: 0x202, index: 0xFFE }));
+    // LD I, #FF8; LD [I], VF and LD VF, [I]
+    assert_eq!(fault(&[0xAF, 0xF8, 0xFF, 0x55]),
+               Some(Fault::MemoryOutOfBounds { pc: 0x202, index: 0xFF8 }));
+    assert_eq!(fault(&[0xAF, 0xF8, 0xFF, 0x65]),
+               Some(Fault::MemoryOutOfBounds { pc: 0x202, index: 0xFF8 }));
+    // The last 16 bytes are fine: LD I, #FF0; LD [I], VF; LD VF, [I]; JP #206
+    assert_eq!(fault(&[0xAF, 0xF0, 0xFF, 0x55, 0xFF, 0x65, 0x12, 0x06]), None);
+    // ADD I, V0 carries I past #FFF: LD I, #FFF; LD V0, #FF; ADD I, V0; DRW V0, V0, 1
+    assert_eq!(fault(&[0xAF, 0xFF, 0x60, 0xFF, 0xF0, 0x1E, 0xD0, 0x01]),
+               Some(Fault::MemoryOutOfBounds { pc: 0x206, index: 0x10FE }));
+}
+
+#[test]
+fn program_counter() {
+    // Zeros are ignored SYS instructions, up to the end of memory
+    assert_eq!(fault(&[0x1F, 0xFE]), Some(Fault::PcOutOfBounds { pc: 0x1000 }));
+    assert_eq!(fault(&[0x1F, 0xFF]), Some(Fault::PcOutOfBounds { pc: 0xFFF }));
+}
+
+#[test]
+fn invalid_instruction() {
+    assert_eq!(fault(&[0x60, 0x01, 0x50, 0x01]),
+               Some(Fault::InvalidInstruction { pc: 0x202, opcode: 0x5001 }));
+}
+
+/// A cheap version of the `execute` fuzz target: random programs under every
+/// preset must run or fault without panicking.
+#[test]
+fn random_programs() {
+    let mut state: u32 = 0x1234_5678;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state
+    };
+
+    let mut presets = vec![Quirks::default()];
+    presets.extend(PRESET_NAMES.iter().map(|name| Quirks::preset(name).unwrap()));
+
+    for _ in 0..500 {
+        let program: Vec<u8> = (0..128).map(|_| next() as u8).collect();
+        for quirks in presets.iter() {
+            let config = HeadlessConfig {
+                quirks: *quirks,
+                ..HeadlessConfig::default()
+            };
+            run(&program, &config);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+#![cfg(feature = "std")]
+#![allow(clippy::redundant_field_names)]
+
+extern crate chip8_emulator;
+
+use chip8_emulator::headless::{headless, HeadlessChip8, HeadlessConfig, ScriptedInput};
+use chip8_emulator::quirks::Quirks;
+
+// LD V5, K; end: JP end
+const WAIT_KEY: &[u8] = &[0xF5, 0x0A, 0x12, 0x02];
+
+fn run(program: &[u8], input: &str, quirks: Quirks, frames: usize) -> HeadlessChip8 {
+    let config = HeadlessConfig {
+        quirks: quirks,
+        input: ScriptedInput::parse(input).unwrap(),
+        ..HeadlessConfig::default()
+    };
+    let mut chip8 = headless(program, &config).unwrap();
+    for _ in 0..frames {
+        chip8.poll_input();
+        chip8.run_frame();
+    }
+
+    chip8
+}
+
+/// The frame `WAIT_KEY` stores a key on, and the key.
+fn key_stored(input: &str, quirks: Quirks) -> Option<(usize, u8)> {
+    (1..60)
+        .map(|frames| (frames, run(WAIT_KEY, input, quirks, frames)))
+        .find(|(_, chip8)| chip8.pc() == 0x202)
+        .map(|(frames, chip8)| (frames - 1, chip8.registers()[5]))
+}
+
+#[test]
+fn waits_for_release() {
+    let quirks = Quirks::default();
+    // Key 3 is held before the instruction starts and doesn't count
+    assert_eq!(key_stored("0:3 10:7 12:-3 15:-7", quirks), Some((15, 7)));
+    assert_eq!(key_stored("0:3 20:-3", quirks), None);
+    // The first key pressed is the one that has to be released
+    assert_eq!(key_stored("10:1 11:2 12:-2 13:-1", quirks), Some((13, 1)));
+    // Presses and releases within a frame aren't lost
+    assert_eq!(key_stored("10:4 10:-4", quirks), Some((10, 4)));
+    assert_eq!(key_stored("10:4 10:-4 10:5 10:-5", quirks), Some((10, 4)));
+}
+
+#[test]
+fn key_on_press() {
+    let quirks = Quirks {
+        key_on_press: true,
+        ..Quirks::default()
+    };
+    assert_eq!(key_stored("0:3 10:7 12:-3 15:-7", quirks), Some((10, 7)));
+    assert_eq!(key_stored("0:3 20:-3", quirks), None);
+    assert_eq!(key_stored("10:1 11:2 12:-2 13:-1", quirks), Some((10, 1)));
+}
+
+#[test]
+fn timers_run_while_waiting() {
+    // LD VA, 60; LD DT, VA; LD V5, K; LD V6, DT; end: JP end
+    let program = [0x6A, 0x3C, 0xFA, 0x15, 0xF5, 0x0A, 0xF6, 0x07, 0x12, 0x08];
+    let chip8 = run(&program, "20:1 30:-1", Quirks::default(), 31);
+    assert_eq!(chip8.pc(), 0x208);
+    assert_eq!(chip8.registers()[6], 30);
+}
@@ -0,0 +1,362 @@
+//! Small test programs covering every instruction, assembled by hand. Each
+//! one prints the values it checks as decimal numbers, four to a line, with
+//! the expected value noted next to each `CALL print`. Sequences that depend
+//! on a quirk print different numbers for each preset.
+//!
+//! `print` is the same subroutine at the end of each program. It draws V0
+//! at VA, VB with the font and BCD instructions and moves on to the next
+//! position.
+
+// Each test crate only uses some of the programs
+#![allow(dead_code)]
+
+/// 7XNN and the 8XYN arithmetic and logic instructions, with the VF results.
+pub const ALU: &[u8] = &[
+    0x6A, 0x00, // 200: LD VA, 0
+    0x6B, 0x00, // 202: LD VB, 0
+    // 7XNN wraps around, 8XY0 copies
+    0x63, 0xFA, // 204: LD V3, 250
+    0x73, 0x0A, // 206: ADD V3, 10
+    0x84, 0x30, // 208: LD V4, V3
+    0x80, 0x40, // 20A: LD V0, V4
+    0x22, 0xB8, // 20C: CALL print  -> 4
+    // 8XY1
+    0x63, 0x0C, // 20E: LD V3, #0C
+    0x64, 0x0A, // 210: LD V4, #0A
+    0x6F, 0x05, // 212: LD VF, 5
+    0x83, 0x41, // 214: OR V3, V4
+    0x85, 0xF0, // 216: LD V5, VF
+    0x80, 0x30, // 218: LD V0, V3
+    0x22, 0xB8, // 21A: CALL print  -> 14
+    0x80, 0x50, // 21C: LD V0, V5
+    0x22, 0xB8, // 21E: CALL print  -> 0 with vf_reset, else 5
+    // 8XY2
+    0x63, 0x0C, // 220: LD V3, #0C
+    0x64, 0x0A, // 222: LD V4, #0A
+    0x6F, 0x05, // 224: LD VF, 5
+    0x83, 0x42, // 226: AND V3, V4
+    0x85, 0xF0, // 228: LD V5, VF
+    0x80, 0x30, // 22A: LD V0, V3
+    0x22, 0xB8, // 22C: CALL print  -> 8
+    0x80, 0x50, // 22E: LD V0, V5
+    0x22, 0xB8, // 230: CALL print  -> 0 with vf_reset, else 5
+    // 8XY3
+    0x63, 0x0C, // 232: LD V3, #0C
+    0x64, 0x0A, // 234: LD V4, #0A
+    0x6F, 0x05, // 236: LD VF, 5
+    0x83, 0x43, // 238: XOR V3, V4
+    0x85, 0xF0, // 23A: LD V5, VF
+    0x80, 0x30, // 23C: LD V0, V3
+    0x22, 0xB8, // 23E: CALL print  -> 6
+    0x80, 0x50, // 240: LD V0, V5
+    0x22, 0xB8, // 242: CALL print  -> 0 with vf_reset, else 5
+    // 8XY4 with carry
+    0x63, 0xC8, // 244: LD V3, 200
+    0x64, 0x64, // 246: LD V4, 100
+    0x83, 0x44, // 248: ADD V3, V4
+    0x85, 0xF0, // 24A: LD V5, VF
+    0x80, 0x30, // 24C: LD V0, V3
+    0x22, 0xB8, // 24E: CALL print  -> 44
+    0x80, 0x50, // 250: LD V0, V5
+    0x22, 0xB8, // 252: CALL print  -> 1
+    // 8XY4 without carry
+    0x6F, 0x00, // 254: LD VF, 0
+    0x63, 0x14, // 256: LD V3, 20
+    0x64, 0x1E, // 258: LD V4, 30
+    0x83, 0x44, // 25A: ADD V3, V4
+    0x85, 0xF0, // 25C: LD V5, VF
+    0x80, 0x30, // 25E: LD V0, V3
+    0x22, 0xB8, // 260: CALL print  -> 50
+    0x80, 0x50, // 262: LD V0, V5
+    0x22, 0xB8, // 264: CALL print  -> 0
+    // 8XY5 without borrow
+    0x63, 0x32, // 266: LD V3, 50
+    0x64, 0x14, // 268: LD V4, 20
+    0x83, 0x45, // 26A: SUB V3, V4
+    0x85, 0xF0, // 26C: LD V5, VF
+    0x80, 0x30, // 26E: LD V0, V3
+    0x22, 0xB8, // 270: CALL print  -> 30
+    0x80, 0x50, // 272: LD V0, V5
+    0x22, 0xB8, // 274: CALL print  -> 1
+    // 8XY5 with borrow
+    0x63, 0x14, // 276: LD V3, 20
+    0x64, 0x32, // 278: LD V4, 50
+    0x83, 0x45, // 27A: SUB V3, V4
+    0x85, 0xF0, // 27C: LD V5, VF
+    0x80, 0x30, // 27E: LD V0, V3
+    0x22, 0xB8, // 280: CALL print  -> 226
+    0x80, 0x50, // 282: LD V0, V5
+    0x22, 0xB8, // 284: CALL print  -> 0
+    // 8XY7
+    0x63, 0x14, // 286: LD V3, 20
+    0x64, 0x32, // 288: LD V4, 50
+    0x83, 0x47, // 28A: SUBN V3, V4
+    0x85, 0xF0, // 28C: LD V5, VF
+    0x80, 0x30, // 28E: LD V0, V3
+    0x22, 0xB8, // 290: CALL print  -> 30
+    0x80, 0x50, // 292: LD V0, V5
+    0x22, 0xB8, // 294: CALL print  -> 1
+    // 8XY6
+    0x63, 0x05, // 296: LD V3, #05
+    0x64, 0x0C, // 298: LD V4, #0C
+    0x83, 0x46, // 29A: SHR V3, V4
+    0x85, 0xF0, // 29C: LD V5, VF
+    0x80, 0x30, // 29E: LD V0, V3
+    0x22, 0xB8, // 2A0: CALL print  -> 6 with shift_vy, else 2
+    0x80, 0x50, // 2A2: LD V0, V5
+    0x22, 0xB8, // 2A4: CALL print  -> 0 with shift_vy, else 1
+    // 8XYE
+    0x63, 0x81, // 2A6: LD V3, #81
+    0x64, 0x40, // 2A8: LD V4, #40
+    0x83, 0x4E, // 2AA: SHL V3, V4
+    0x85, 0xF0, // 2AC: LD V5, VF
+    0x80, 0x30, // 2AE: LD V0, V3
+    0x22, 0xB8, // 2B0: CALL print  -> 128 with shift_vy, else 2
+    0x80, 0x50, // 2B2: LD V0, V5
+    0x22, 0xB8, // 2B4: CALL print  -> 0 with shift_vy, else 1
+    // end:
+    0x12, 0xB6, // 2B6: JP end
+    // print:
+    0xA2, 0xDA, // 2B8: LD I, digits
+    0xF0, 0x33, // 2BA: LD B, V0
+    0xF2, 0x65, // 2BC: LD V2, [I]
+    0xF0, 0x29, // 2BE: LD F, V0
+    0xDA, 0xB5, // 2C0: DRW VA, VB, 5
+    0x7A, 0x05, // 2C2: ADD VA, 5
+    0xF1, 0x29, // 2C4: LD F, V1
+    0xDA, 0xB5, // 2C6: DRW VA, VB, 5
+    0x7A, 0x05, // 2C8: ADD VA, 5
+    0xF2, 0x29, // 2CA: LD F, V2
+    0xDA, 0xB5, // 2CC: DRW VA, VB, 5
+    0x7A, 0x06, // 2CE: ADD VA, 6
+    0x3A, 0x40, // 2D0: SE VA, 64  Four numbers per line
+    0x00, 0xEE, // 2D2: RET
+    0x6A, 0x00, // 2D4: LD VA, 0
+    0x7B, 0x06, // 2D6: ADD VB, 6
+    0x00, 0xEE, // 2D8: RET
+    // digits:
+    0x00, 0x00, 0x00, // 2DA: data
+];
+
+/// Skips, jumps, calls, timers and input. Expects key 3 to be pressed on
+/// frame 5 and key 5 to be pressed on frames 20 and 40 and released on
+/// frames 30 and 60, the last number is the count of failed checks.
+pub const FLOW: &[u8] = &[
+    0x6A, 0x00, // 200: LD VA, 0
+    0x6B, 0x00, // 202: LD VB, 0
+    0x64, 0x00, // 204: LD V4, 0  Number of failed checks
+    // 3XNN, 4XNN, 5XY0 and 9XY0 must skip exactly when expected
+    0x65, 0x07, // 206: LD V5, 7
+    0x66, 0x07, // 208: LD V6, 7
+    0x67, 0x08, // 20A: LD V7, 8
+    0x35, 0x07, // 20C: SE V5, 7
+    0x74, 0x01, // 20E: ADD V4, 1
+    0x35, 0x08, // 210: SE V5, 8
+    0x12, 0x16, // 212: JP ok1
+    0x74, 0x01, // 214: ADD V4, 1
+    // ok1:
+    0x45, 0x08, // 216: SNE V5, 8
+    0x74, 0x01, // 218: ADD V4, 1
+    0x45, 0x07, // 21A: SNE V5, 7
+    0x12, 0x20, // 21C: JP ok2
+    0x74, 0x01, // 21E: ADD V4, 1
+    // ok2:
+    0x55, 0x60, // 220: SE V5, V6
+    0x74, 0x01, // 222: ADD V4, 1
+    0x55, 0x70, // 224: SE V5, V7
+    0x12, 0x2A, // 226: JP ok3
+    0x74, 0x01, // 228: ADD V4, 1
+    // ok3:
+    0x95, 0x70, // 22A: SNE V5, V7
+    0x74, 0x01, // 22C: ADD V4, 1
+    0x95, 0x60, // 22E: SNE V5, V6
+    0x12, 0x34, // 230: JP ok4
+    0x74, 0x01, // 232: ADD V4, 1
+    // ok4:
+    // 1NNN, 2NNN and 00EE, with nested calls
+    0x12, 0x38, // 234: JP ok5
+    0x74, 0x01, // 236: ADD V4, 1
+    // ok5:
+    0x67, 0x00, // 238: LD V7, 0
+    0x22, 0x9A, // 23A: CALL twice
+    0x37, 0x02, // 23C: SE V7, 2
+    0x74, 0x01, // 23E: ADD V4, 1
+    // 0NNN is ignored
+    0x01, 0x23, // 240: SYS #123
+    // BNNN jumps to NNN + V0, or to XNN + VX with jump_vx
+    0x60, 0x00, // 242: LD V0, 0
+    0x62, 0x02, // 244: LD V2, 2
+    0xB2, 0x48, // 246: JP V0, table
+    // table:
+    0x12, 0x4C, // 248: JP jump_v0
+    0x12, 0x50, // 24A: JP jump_vx
+    // jump_v0:
+    0x68, 0x01, // 24C: LD V8, 1
+    0x12, 0x52, // 24E: JP jumped
+    // jump_vx:
+    0x68, 0x02, // 250: LD V8, 2
+    // jumped:
+    0x80, 0x80, // 252: LD V0, V8
+    0x22, 0xA0, // 254: CALL print  -> 2 with jump_vx, else 1
+    // FX15 and FX07, the delay timer counts down to 0
+    0x65, 0x0A, // 256: LD V5, 10
+    0xF5, 0x15, // 258: LD DT, V5
+    0xF6, 0x07, // 25A: LD V6, DT
+    0x80, 0x60, // 25C: LD V0, V6
+    0x22, 0xA0, // 25E: CALL print  -> 10
+    // wait_dt:
+    0xF6, 0x07, // 260: LD V6, DT
+    0x36, 0x00, // 262: SE V6, 0
+    0x12, 0x60, // 264: JP wait_dt
+    // FX18
+    0xF5, 0x18, // 266: LD ST, V5
+    // FX0A ignores key 3, held since frame 5, and waits for key 5 to be
+    // pressed on frame 20 and released on frame 30
+    0xF6, 0x0A, // 268: LD V6, K
+    0x80, 0x60, // 26A: LD V0, V6
+    0x22, 0xA0, // 26C: CALL print  -> 5
+    // EX9E and EXA1 once key 5 is held again on frame 40
+    0x67, 0x05, // 26E: LD V7, 5
+    // pressed:
+    0xE7, 0x9E, // 270: SKP V7
+    0x12, 0x70, // 272: JP pressed
+    0xE7, 0xA1, // 274: SKNP V7
+    0x12, 0x7A, // 276: JP ok6
+    0x74, 0x01, // 278: ADD V4, 1
+    // ok6:
+    // Wait for the release on frame 60
+    // released:
+    0xE7, 0xA1, // 27A: SKNP V7
+    0x12, 0x7A, // 27C: JP released
+    0xE7, 0x9E, // 27E: SKP V7
+    0x12, 0x84, // 280: JP ok7
+    0x74, 0x01, // 282: ADD V4, 1
+    // ok7:
+    // CXNN is masked by NN
+    0xC5, 0x00, // 284: RND V5, #00
+    0x35, 0x00, // 286: SE V5, 0
+    0x74, 0x01, // 288: ADD V4, 1
+    0xC5, 0xF0, // 28A: RND V5, #F0
+    0x66, 0x0F, // 28C: LD V6, #0F
+    0x86, 0x52, // 28E: AND V6, V5
+    0x36, 0x00, // 290: SE V6, 0
+    0x74, 0x01, // 292: ADD V4, 1
+    0x80, 0x40, // 294: LD V0, V4
+    0x22, 0xA0, // 296: CALL print  -> 0
+    // end:
+    0x12, 0x98, // 298: JP end
+    // twice:
+    0x22, 0x9C, // 29A: CALL once
+    // once:
+    0x77, 0x01, // 29C: ADD V7, 1
+    0x00, 0xEE, // 29E: RET
+    // print:
+    0xA2, 0xC2, // 2A0: LD I, digits
+    0xF0, 0x33, // 2A2: LD B, V0
+    0xF2, 0x65, // 2A4: LD V2, [I]
+    0xF0, 0x29, // 2A6: LD F, V0
+    0xDA, 0xB5, // 2A8: DRW VA, VB, 5
+    0x7A, 0x05, // 2AA: ADD VA, 5
+    0xF1, 0x29, // 2AC: LD F, V1
+    0xDA, 0xB5, // 2AE: DRW VA, VB, 5
+    0x7A, 0x05, // 2B0: ADD VA, 5
+    0xF2, 0x29, // 2B2: LD F, V2
+    0xDA, 0xB5, // 2B4: DRW VA, VB, 5
+    0x7A, 0x06, // 2B6: ADD VA, 6
+    0x3A, 0x40, // 2B8: SE VA, 64  Four numbers per line
+    0x00, 0xEE, // 2BA: RET
+    0x6A, 0x00, // 2BC: LD VA, 0
+    0x7B, 0x06, // 2BE: ADD VB, 6
+    0x00, 0xEE, // 2C0: RET
+    // digits:
+    0x00, 0x00, 0x00, // 2C2: data
+];
+
+/// FX55, FX65 and FX1E, plus sprites colliding with each other and with the
+/// edges of the screen.
+pub const MEMORY: &[u8] = &[
+    // 00E0 clears whatever was drawn before
+    0xA2, 0x66, // 200: LD I, block
+    0x6C, 0x00, // 202: LD VC, 0
+    0xDC, 0xC4, // 204: DRW VC, VC, 4
+    0x00, 0xE0, // 206: CLS
+    0x6A, 0x00, // 208: LD VA, 0
+    0x6B, 0x00, // 20A: LD VB, 0
+    // FX55 then FX65 through the same I, which moved past the stored
+    // registers with load_store_increment
+    0x60, 0x01, // 20C: LD V0, 1
+    0x61, 0x02, // 20E: LD V1, 2
+    0x62, 0x03, // 210: LD V2, 3
+    0xA2, 0x6A, // 212: LD I, data
+    0xF2, 0x55, // 214: LD [I], V2
+    0x60, 0x00, // 216: LD V0, 0
+    0x61, 0x00, // 218: LD V1, 0
+    0x62, 0x00, // 21A: LD V2, 0
+    0xF2, 0x65, // 21C: LD V2, [I]
+    0x85, 0x00, // 21E: LD V5, V0
+    0x86, 0x10, // 220: LD V6, V1
+    0x87, 0x20, // 222: LD V7, V2
+    0x80, 0x50, // 224: LD V0, V5
+    0x22, 0x70, // 226: CALL print  -> 7 with load_store_increment, else 1
+    0x80, 0x60, // 228: LD V0, V6
+    0x22, 0x70, // 22A: CALL print  -> 8 with load_store_increment, else 2
+    0x80, 0x70, // 22C: LD V0, V7
+    0x22, 0x70, // 22E: CALL print  -> 9 with load_store_increment, else 3
+    // FX1E
+    0xA2, 0x6A, // 230: LD I, data
+    0x65, 0x04, // 232: LD V5, 4
+    0xF5, 0x1E, // 234: ADD I, V5
+    0xF0, 0x65, // 236: LD V0, [I]
+    0x22, 0x70, // 238: CALL print  -> 8
+    // DXYN sets VF on collision, drawing twice erases the sprite
+    0xA2, 0x66, // 23A: LD I, block
+    0x6C, 0x08, // 23C: LD VC, 8
+    0x6D, 0x14, // 23E: LD VD, 20
+    0xDC, 0xD4, // 240: DRW VC, VD, 4
+    0x85, 0xF0, // 242: LD V5, VF
+    0xDC, 0xD4, // 244: DRW VC, VD, 4
+    0x86, 0xF0, // 246: LD V6, VF
+    0x80, 0x50, // 248: LD V0, V5
+    0x22, 0x70, // 24A: CALL print  -> 0
+    0x80, 0x60, // 24C: LD V0, V6
+    0x22, 0x70, // 24E: CALL print  -> 1
+    // The start position wraps around: 70, 90 is drawn at 6, 26
+    0xA2, 0x66, // 250: LD I, block
+    0x6C, 0x46, // 252: LD VC, 70
+    0x6D, 0x5A, // 254: LD VD, 90
+    0xDC, 0xD4, // 256: DRW VC, VD, 4
+    // Sprites crossing the right and bottom edges are clipped, or wrap
+    // around without clip_sprites
+    0x6C, 0x3C, // 258: LD VC, 60
+    0x6D, 0x14, // 25A: LD VD, 20
+    0xDC, 0xD4, // 25C: DRW VC, VD, 4
+    0x6C, 0x14, // 25E: LD VC, 20
+    0x6D, 0x1E, // 260: LD VD, 30
+    0xDC, 0xD4, // 262: DRW VC, VD, 4
+    // end:
+    0x12, 0x64, // 264: JP end
+    // block:
+    0xFF, 0x81, 0x81, 0xFF, // 266: data
+    // data:
+    0x00, 0x00, 0x00, 0x07, 0x08, 0x09, // 26A: data
+    // print:
+    0xA2, 0x92, // 270: LD I, digits
+    0xF0, 0x33, // 272: LD B, V0
+    0xF2, 0x65, // 274: LD V2, [I]
+    0xF0, 0x29, // 276: LD F, V0
+    0xDA, 0xB5, // 278: DRW VA, VB, 5
+    0x7A, 0x05, // 27A: ADD VA, 5
+    0xF1, 0x29, // 27C: LD F, V1
+    0xDA, 0xB5, // 27E: DRW VA, VB, 5
+    0x7A, 0x05, // 280: ADD VA, 5
+    0xF2, 0x29, // 282: LD F, V2
+    0xDA, 0xB5, // 284: DRW VA, VB, 5
+    0x7A, 0x06, // 286: ADD VA, 6
+    0x3A, 0x40, // 288: SE VA, 64  Four numbers per line
+    0x00, 0xEE, // 28A: RET
+    0x6A, 0x00, // 28C: LD VA, 0
+    0x7B, 0x06, // 28E: ADD VB, 6
+    0x00, 0xEE, // 290: RET
+    // digits:
+    0x00, 0x00, 0x00, // 292: data
+];
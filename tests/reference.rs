@@ -0,0 +1,396 @@
+//! Property tests running single instructions on random machine states and
+//! comparing the result with a reference interpreter written from Cowgod's
+//! Chip-8 technical reference, for each quirks preset.
+
+#![cfg(feature = "std")]
+#![allow(clippy::redundant_field_names)]
+
+extern crate chip8_emulator;
+extern crate proptest;
+
+use proptest::prelude::*;
+
+use chip8_emulator::core::{Fault, STATE_SIZE};
+use chip8_emulator::headless::{headless, Framebuffer, HeadlessConfig, InputEvent};
+use chip8_emulator::peripherals::Chip8Key;
+use chip8_emulator::quirks::{Quirks, PRESET_NAMES};
+
+/// The screen pixel by pixel, independent of the emulator's packed rows.
+type Screen = [[bool; 64]; 32];
+
+/// Everything an instruction can read or change.
+#[derive(Clone, Debug, PartialEq)]
+struct Machine {
+    memory: Vec<u8>,
+    v: [u8; 16],
+    pc: u16,
+    i: u16,
+    stack: [u16; 16],
+    sp: u8,
+    dt: u8,
+    st: u8,
+    screen: Screen,
+    keys: [bool; 16],
+}
+
+impl Machine {
+    /// The state in the format of `Chip8::save_state`.
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = b"C8ST\x01".to_vec();
+        state.extend_from_slice(&self.memory);
+        state.extend_from_slice(&self.v);
+        for value in [self.pc, self.i].iter().chain(self.stack.iter()) {
+            state.push((*value >> 8) as u8);
+            state.push(*value as u8);
+        }
+        state.extend_from_slice(&[self.sp, self.dt, self.st]);
+        assert_eq!(state.len(), STATE_SIZE);
+        state
+    }
+
+    /// Replace the CPU state with one written by `Chip8::save_state`.
+    fn load_state(&mut self, state: &[u8]) {
+        let word = |offset: usize| ((state[offset] as u16) << 8) | state[offset + 1] as u16;
+        self.memory.copy_from_slice(&state[5..4101]);
+        self.v.copy_from_slice(&state[4101..4117]);
+        self.pc = word(4117);
+        self.i = word(4119);
+        for (idx, value) in self.stack.iter_mut().enumerate() {
+            *value = word(4121 + 2 * idx);
+        }
+        self.sp = state[4153];
+        self.dt = state[4154];
+        self.st = state[4155];
+    }
+
+    /// The differences to `other`, for failure messages.
+    fn diff(&self, other: &Machine) -> String {
+        let mut diff = Vec::new();
+        let changed = self.memory.iter().zip(other.memory.iter()).enumerate()
+            .filter(|&(_, (a, b))| a != b);
+        for (addr, (a, b)) in changed {
+            diff.push(format!("memory[{:03X}]: {:02X} != {:02X}", addr, a, b));
+        }
+        for (idx, (a, b)) in self.v.iter().zip(other.v.iter()).enumerate().filter(|&(_, (a, b))| a != b) {
+            diff.push(format!("V{:X}: {:02X} != {:02X}", idx, a, b));
+        }
+        let fields = [("PC", self.pc, other.pc), ("I", self.i, other.i),
+                      ("SP", self.sp as u16, other.sp as u16),
+                      ("DT", self.dt as u16, other.dt as u16),
+                      ("ST", self.st as u16, other.st as u16)];
+        for &(name, a, b) in fields.iter().filter(|&&(_, a, b)| a != b) {
+            diff.push(format!("{}: {:03X} != {:03X}", name, a, b));
+        }
+        if self.stack != other.stack {
+            diff.push(format!("stack: {:03X?} != {:03X?}", self.stack, other.stack));
+        }
+        if self.screen[..] != other.screen[..] {
+            diff.push("screen differs".to_string());
+        }
+        diff.join(", ")
+    }
+}
+
+/// What the reference interpreter expects a single instruction to do.
+enum Expected {
+    State(Machine),
+    /// `Rand`: the state with VX masked by the immediate, the random bits
+    /// themselves can't be predicted.
+    Random(Machine, usize, u8),
+    Fault(Fault),
+}
+
+/// Execute the instruction at PC on `m` following the spec.
+fn reference(m: &Machine, quirks: &Quirks) -> Expected {
+    let mut m = m.clone();
+    let pc = m.pc;
+    let op = ((m.memory[pc as usize] as u16) << 8) | m.memory[pc as usize + 1] as u16;
+    let nnn = op & 0xFFF;
+    let nn = op as u8;
+    let n = (op & 0xF) as usize;
+    let x = ((op >> 8) & 0xF) as usize;
+    let y = ((op >> 4) & 0xF) as usize;
+    let (vx, vy) = (m.v[x], m.v[y]);
+    let invalid = Expected::Fault(Fault::InvalidInstruction { pc: pc, opcode: op });
+    let out_of_bounds = Expected::Fault(Fault::MemoryOutOfBounds { pc: pc, index: m.i });
+
+    let mut next = pc + 2;
+    match op >> 12 {
+        0x0 => match op {
+            0x00E0 => m.screen = [[false; 64]; 32],
+            0x00EE => {
+                if m.sp == 0 {
+                    return Expected::Fault(Fault::StackUnderflow { pc: pc });
+                }
+                m.sp -= 1;
+                next = m.stack[m.sp as usize].wrapping_add(2);
+                m.stack[m.sp as usize] = 0;
+            }
+            // 0NNN calls machine code, which is ignored
+            _ => {}
+        },
+        0x1 => next = nnn,
+        0x2 => {
+            if m.sp == 16 {
+                return Expected::Fault(Fault::StackOverflow { pc: pc });
+            }
+            m.stack[m.sp as usize] = pc;
+            m.sp += 1;
+            next = nnn;
+        }
+        0x3 => if vx == nn { next += 2 },
+        0x4 => if vx != nn { next += 2 },
+        0x5 if n == 0 => if vx == vy { next += 2 },
+        0x6 => m.v[x] = nn,
+        0x7 => m.v[x] = vx.wrapping_add(nn),
+        0x8 => {
+            let shifted = if quirks.shift_vy { vy } else { vx };
+            // Result first, then the flag
+            let (result, flag) = match n {
+                0x0 => (vy, None),
+                0x1 => (vx | vy, if quirks.vf_reset { Some(0) } else { None }),
+                0x2 => (vx & vy, if quirks.vf_reset { Some(0) } else { None }),
+                0x3 => (vx ^ vy, if quirks.vf_reset { Some(0) } else { None }),
+                0x4 => (vx.wrapping_add(vy), Some((vx as u16 + vy as u16 > 0xFF) as u8)),
+                0x5 => (vx.wrapping_sub(vy), Some((vx >= vy) as u8)),
+                0x6 => (shifted >> 1, Some(shifted & 1)),
+                0x7 => (vy.wrapping_sub(vx), Some((vy >= vx) as u8)),
+                0xE => (shifted << 1, Some(shifted >> 7)),
+                _ => return invalid,
+            };
+            m.v[x] = result;
+            if let Some(flag) = flag {
+                m.v[0xF] = flag;
+            }
+        }
+        0x9 if n == 0 => if vx != vy { next += 2 },
+        0xA => m.i = nnn,
+        0xB => {
+            let offset = if quirks.jump_vx { vx } else { m.v[0] };
+            next = nnn + offset as u16;
+        }
+        0xC => return Expected::Random(Machine { pc: next, ..m }, x, nn),
+        0xD => {
+            if m.i as usize + n > 4096 {
+                return out_of_bounds;
+            }
+            let mut collision = false;
+            for row in 0..n {
+                let line = m.memory[m.i as usize + row];
+                for bit in 0..8 {
+                    let (px, py) = (vx as usize % 64 + bit, vy as usize % 32 + row);
+                    if quirks.clip_sprites && (px >= 64 || py >= 32) {
+                        continue;
+                    }
+                    if line & (0x80 >> bit) != 0 {
+                        let pixel = &mut m.screen[py % 32][px % 64];
+                        collision |= *pixel;
+                        *pixel = !*pixel;
+                    }
+                }
+            }
+            m.v[0xF] = collision as u8;
+        }
+        // The VIP only looks at the low nibble of VX
+        0xE if nn == 0x9E => if m.keys[vx as usize & 0xF] { next += 2 },
+        0xE if nn == 0xA1 => if !m.keys[vx as usize & 0xF] { next += 2 },
+        0xF => match nn {
+            0x07 => m.v[x] = m.dt,
+            // Waits for a key to be pressed after it started, keys already
+            // held don't count
+            0x0A => next = pc,
+            0x15 => m.dt = vx,
+            0x18 => m.st = vx,
+            0x1E => m.i = m.i.wrapping_add(vx as u16),
+            0x29 => m.i = 5 * vx as u16,
+            0x33 => {
+                if m.i as usize + 3 > 4096 {
+                    return out_of_bounds;
+                }
+                let i = m.i as usize;
+                m.memory[i] = vx / 100;
+                m.memory[i + 1] = vx / 10 % 10;
+                m.memory[i + 2] = vx % 10;
+            }
+            0x55 | 0x65 => {
+                if m.i as usize + x + 1 > 4096 {
+                    return out_of_bounds;
+                }
+                for reg in 0..(x + 1) {
+                    if nn == 0x55 {
+                        m.memory[m.i as usize + reg] = m.v[reg];
+                    } else {
+                        m.v[reg] = m.memory[m.i as usize + reg];
+                    }
+                }
+                if quirks.load_store_increment {
+                    m.i += x as u16 + 1;
+                }
+            }
+            _ => return invalid,
+        },
+        _ => return invalid,
+    }
+
+    m.pc = next;
+    Expected::State(m)
+}
+
+/// Run the instruction at PC on the emulator, returning the new state or the
+/// fault it stopped with.
+fn emulate(m: &Machine, quirks: &Quirks) -> Result<Machine, Fault> {
+    let input = (0..16)
+        .filter(|&key| m.keys[key])
+        .map(|key| InputEvent { frame: 0, key: Chip8Key::new(key as u8).unwrap(), pressed: true })
+        .collect();
+    let config = HeadlessConfig {
+        quirks: *quirks,
+        input: input,
+        ..HeadlessConfig::default()
+    };
+
+    let mut chip8 = headless(&[], &config).unwrap();
+    chip8.load_state(&m.save_state()).unwrap();
+    let mut frame = Framebuffer::new();
+    for (y, line) in m.screen.iter().enumerate() {
+        for (x, pixel) in line.iter().enumerate() {
+            frame.set_pixel(x, y, *pixel);
+        }
+    }
+    chip8.set_framebuffer(&frame);
+    chip8.poll_input();
+    chip8.step();
+
+    if let Some(fault) = chip8.fault() {
+        return Err(fault);
+    }
+    let mut result = m.clone();
+    result.load_state(&chip8.save_state());
+    for (y, line) in result.screen.iter_mut().enumerate() {
+        for (x, pixel) in line.iter_mut().enumerate() {
+            *pixel = chip8.framebuffer().pixel(x, y);
+        }
+    }
+    Ok(result)
+}
+
+fn presets() -> Vec<Quirks> {
+    let mut presets = vec![Quirks::default()];
+    presets.extend(PRESET_NAMES.iter().map(|name| Quirks::preset(name).unwrap()));
+    presets
+}
+
+fn check(m: &Machine) -> Result<(), TestCaseError> {
+    for quirks in presets() {
+        let found = emulate(m, &quirks);
+        match (reference(m, &quirks), found) {
+            (Expected::State(expected), Ok(found)) => {
+                prop_assert!(expected == found, "{:?}: {}", quirks, expected.diff(&found));
+            }
+            (Expected::Random(expected, x, mask), Ok(found)) => {
+                prop_assert_eq!(found.v[x] & !mask, 0);
+                let found = Machine { v: { let mut v = found.v; v[x] = expected.v[x]; v }, ..found };
+                prop_assert!(expected == found, "{:?}: {}", quirks, expected.diff(&found));
+            }
+            (Expected::Fault(expected), Err(found)) => prop_assert_eq!(expected, found),
+            (Expected::Fault(expected), Ok(_)) => {
+                prop_assert!(false, "{:?}: expected {}", quirks, expected);
+            }
+            (_, Err(found)) => prop_assert!(false, "{:?}: unexpected {}", quirks, found),
+        }
+    }
+    Ok(())
+}
+
+fn screen() -> BoxedStrategy<Screen> {
+    prop::collection::vec(any::<u64>(), 32)
+        .prop_map(|rows| {
+            let mut screen = [[false; 64]; 32];
+            for (line, bits) in screen.iter_mut().zip(rows) {
+                for (x, pixel) in line.iter_mut().enumerate() {
+                    *pixel = bits & (1 << x) != 0;
+                }
+            }
+            screen
+        })
+        .boxed()
+}
+
+/// A random machine about to execute `opcode`. I is often near the end of
+/// memory and the stack often empty or full to reach the faults.
+fn machine(opcode: BoxedStrategy<u16>) -> BoxedStrategy<Machine> {
+    let index = prop_oneof![0u16..0x1000, 0xFF0u16..0x1000, any::<u16>()];
+    let stack_ptr = prop_oneof![0u8..17, Just(0u8), Just(16u8)];
+    let cpu = (any::<[u8; 16]>(), (0x100u16..0x7FF).prop_map(|pc| pc * 2), index,
+               prop::array::uniform16(0u16..0x1000), stack_ptr, any::<u8>(), any::<u8>());
+    (prop::collection::vec(any::<u8>(), 4096), cpu, screen(), any::<u16>(), opcode)
+        .prop_map(|(mut memory, (v, pc, i, stack, sp, dt, st), screen, keys, opcode)| {
+            memory[pc as usize] = (opcode >> 8) as u8;
+            memory[pc as usize + 1] = opcode as u8;
+            let mut pressed = [false; 16];
+            for (key, pressed) in pressed.iter_mut().enumerate() {
+                *pressed = keys & (1 << key) != 0;
+            }
+            Machine {
+                memory: memory,
+                v: v,
+                pc: pc,
+                i: i,
+                stack: stack,
+                sp: sp,
+                dt: dt,
+                st: st,
+                screen: screen,
+                keys: pressed,
+            }
+        })
+        .boxed()
+}
+
+/// Opcodes of one instruction with random operands.
+fn opcodes(base: u16, mask: u16) -> BoxedStrategy<u16> {
+    any::<u16>().prop_map(move |operands| base | (operands & mask)).boxed()
+}
+
+proptest! {
+    #[test]
+    fn any_opcode(ref m in machine(any::<u16>().boxed())) {
+        check(m)?;
+    }
+
+    #[test]
+    fn flow(ref m in machine(prop_oneof![
+        Just(0x00EE), opcodes(0x1000, 0xFFF), opcodes(0x2000, 0xFFF), opcodes(0xB000, 0xFFF),
+        opcodes(0x3000, 0xFFF), opcodes(0x4000, 0xFFF), opcodes(0x5000, 0xFF0),
+        opcodes(0x9000, 0xFF0),
+    ].boxed())) {
+        check(m)?;
+    }
+
+    #[test]
+    fn alu(ref m in machine(prop_oneof![
+        opcodes(0x6000, 0xFFF), opcodes(0x7000, 0xFFF), opcodes(0xC000, 0xFFF),
+        opcodes(0x8000, 0xFF0), opcodes(0x8001, 0xFF0), opcodes(0x8002, 0xFF0),
+        opcodes(0x8003, 0xFF0), opcodes(0x8004, 0xFF0), opcodes(0x8005, 0xFF0),
+        opcodes(0x8006, 0xFF0), opcodes(0x8007, 0xFF0), opcodes(0x800E, 0xFF0),
+    ].boxed())) {
+        check(m)?;
+    }
+
+    #[test]
+    fn memory_and_screen(ref m in machine(prop_oneof![
+        Just(0x00E0), opcodes(0xA000, 0xFFF), opcodes(0xD000, 0xFFF), opcodes(0xF01E, 0xF00),
+        opcodes(0xF029, 0xF00), opcodes(0xF033, 0xF00), opcodes(0xF055, 0xF00),
+        opcodes(0xF065, 0xF00),
+    ].boxed())) {
+        check(m)?;
+    }
+
+    #[test]
+    fn timers_and_keys(ref m in machine(prop_oneof![
+        opcodes(0xE09E, 0xF00), opcodes(0xE0A1, 0xF00), opcodes(0xF007, 0xF00),
+        opcodes(0xF00A, 0xF00), opcodes(0xF015, 0xF00), opcodes(0xF018, 0xF00),
+    ].boxed())) {
+        check(m)?;
+    }
+}
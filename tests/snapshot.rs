@@ -0,0 +1,34 @@
+#![cfg(feature = "std")]
+
+extern crate chip8_emulator;
+
+mod programs;
+
+use std::env;
+use std::fs;
+use std::process;
+
+use chip8_emulator::headless::{frame_to_string, Framebuffer};
+use chip8_emulator::snapshot::{self, SnapshotSpec};
+
+#[test]
+fn record_and_verify() {
+    let dir = env::temp_dir().join(format!("chip8-snapshot-{}", process::id()));
+    let mut spec = SnapshotSpec::new(programs::FLOW, vec![200, 10]);
+    spec.input = "20:5 40:-5".to_string();
+
+    snapshot::record(&dir, programs::FLOW, &spec).unwrap();
+    assert_eq!(snapshot::load_spec(&dir).unwrap(), spec);
+    assert!(snapshot::verify(&dir, programs::FLOW).unwrap().is_empty());
+    assert!(snapshot::verify(&dir, programs::ALU).is_err());
+
+    // A blank screen where the program has printed its results
+    fs::write(dir.join("frame-00200.txt"), frame_to_string(&Framebuffer::new())).unwrap();
+    let mismatches = snapshot::verify(&dir, programs::FLOW).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].frame, 200);
+    assert!(mismatches[0].diff().contains('+'));
+    assert!(!mismatches[0].diff().contains('-'));
+}
@@ -0,0 +1,195 @@
+//! The emulator for web pages, built for `wasm32-unknown-unknown` without
+//! SDL and wrapped with wasm-bindgen. The page draws the screen and feeds
+//! key events in, see `examples/web`.
+
+#![allow(clippy::redundant_field_names)]
+
+extern crate chip8_emulator;
+extern crate wasm_bindgen;
+
+use wasm_bindgen::prelude::*;
+
+use chip8_emulator::chip8::{Chip8, FRAME_BYTES, STATE_SIZE};
+use chip8_emulator::error::Error;
+use chip8_emulator::peripherals::{Chip8Disp, Chip8Input, Chip8Key, Framebuffer, KeyEvent,
+                                  KeyQueue, Region};
+use chip8_emulator::quirks::{Quirks, PRESET_NAMES};
+
+/// Remembers whether the screen changed since the page last drew it.
+struct CanvasDisplay {
+    changed: bool,
+}
+
+impl Chip8Disp for CanvasDisplay {
+    fn present(&mut self, _frame: &Framebuffer, dirty: Option<Region>) {
+        if dirty.is_some() {
+            self.changed = true;
+        }
+    }
+}
+
+/// Keys pressed and released by the page's event handlers.
+struct PageKeys {
+    keys: KeyQueue,
+}
+
+impl Chip8Input for PageKeys {
+    fn key_pressed(&self, key: Chip8Key) -> bool {
+        self.keys.key_pressed(key)
+    }
+
+    fn next_event(&mut self) -> Option<KeyEvent> {
+        self.keys.next_event()
+    }
+
+    fn poll(&mut self) -> bool {
+        false
+    }
+}
+
+fn js_error(error: Error) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
+
+/// A Chip-8 machine, `Chip8` in JavaScript.
+#[wasm_bindgen(js_name = Chip8)]
+pub struct WebChip8 {
+    chip8: Chip8<CanvasDisplay, PageKeys>,
+    /// Loaded again on reset.
+    program: Vec<u8>,
+}
+
+#[wasm_bindgen(js_class = Chip8)]
+impl WebChip8 {
+    /// A machine running `rom`. There is no entropy to seed `RND` from
+    /// inside WebAssembly, pass e.g. `Math.random() * 2 ** 32` as `seed`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8], seed: u32) -> Result<WebChip8, JsValue> {
+        let mut chip8 = Chip8::new();
+        chip8.seed_rng(seed);
+        chip8.upload_rom(rom).map_err(js_error)?;
+        chip8.connect_display(CanvasDisplay { changed: true });
+        chip8.connect_keyboard(PageKeys { keys: KeyQueue::new() });
+
+        Ok(WebChip8 {
+            chip8: chip8,
+            program: rom.to_vec(),
+        })
+    }
+
+    /// Reset the machine and run another ROM. Quirks and speed are kept.
+    #[wasm_bindgen(js_name = loadRom)]
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsValue> {
+        self.chip8.reset();
+        self.program.clear();
+        self.chip8.upload_rom(rom).map_err(js_error)?;
+        self.program = rom.to_vec();
+        Ok(())
+    }
+
+    /// Return to the power-on state with the same ROM.
+    pub fn reset(&mut self) {
+        self.chip8.reset();
+        // Can't fail, only programs that fit are kept
+        let _ = self.chip8.upload_rom(&self.program);
+    }
+
+    /// Switch to the quirks of `chip8`, `schip` or `xochip`.
+    #[wasm_bindgen(js_name = setQuirks)]
+    pub fn set_quirks(&mut self, preset: &str) -> Result<(), JsValue> {
+        let quirks = Quirks::preset(preset).ok_or_else(|| {
+            JsValue::from_str(&format!("Unknown quirks preset: {} (expected one of {})",
+                                       preset, PRESET_NAMES.join(", ")))
+        })?;
+        self.chip8.set_quirks(quirks);
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = setCyclesPerFrame)]
+    pub fn set_cycles_per_frame(&mut self, cycles: usize) {
+        self.chip8.set_cycles_per_frame(cycles);
+    }
+
+    /// Execute a single instruction.
+    pub fn step(&mut self) {
+        self.chip8.step();
+        self.chip8.present();
+    }
+
+    /// Emulate one 60 Hz frame. Returns whether the screen changed and has
+    /// to be drawn again.
+    #[wasm_bindgen(js_name = runFrame)]
+    pub fn run_frame(&mut self) -> bool {
+        self.chip8.poll_input();
+        self.chip8.run_frame();
+        self.chip8.present();
+
+        let display = self.chip8.display_mut().unwrap();
+        let changed = display.changed;
+        display.changed = false;
+        changed
+    }
+
+    /// Press a Chip-8 key, 0 to F.
+    #[wasm_bindgen(js_name = pressKey)]
+    pub fn press_key(&mut self, key: u8) -> Result<(), JsValue> {
+        self.set_key(key, true)
+    }
+
+    #[wasm_bindgen(js_name = releaseKey)]
+    pub fn release_key(&mut self, key: u8) -> Result<(), JsValue> {
+        self.set_key(key, false)
+    }
+
+    /// The 64x32 screen, one byte per pixel row by row, 1 when lit.
+    pub fn framebuffer(&self) -> Vec<u8> {
+        let frame = self.chip8.framebuffer();
+        let mut pixels = Vec::with_capacity(64 * 32);
+        for y in 0..32 {
+            pixels.extend((0..64).map(|x| frame.pixel(x, y) as u8));
+        }
+
+        pixels
+    }
+
+    /// Whether a tone should be playing.
+    #[wasm_bindgen(js_name = soundActive)]
+    pub fn sound_active(&self) -> bool {
+        self.chip8.sound_active()
+    }
+
+    /// The fault that halted the machine, if any.
+    pub fn fault(&self) -> Option<String> {
+        self.chip8.fault().map(|fault| fault.to_string())
+    }
+
+    /// The machine state followed by the screen, in the same format as the
+    /// save state slots of the SDL frontend.
+    #[wasm_bindgen(js_name = saveState)]
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = self.chip8.save_state();
+        state.extend(self.chip8.save_frame());
+        state
+    }
+
+    /// Restore a state written by `saveState`.
+    #[wasm_bindgen(js_name = loadState)]
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), JsValue> {
+        if state.len() != STATE_SIZE + FRAME_BYTES {
+            return Err(js_error(Error::NotAState));
+        }
+
+        let (machine, frame) = state.split_at(STATE_SIZE);
+        self.chip8.load_state(machine)
+            .and_then(|_| self.chip8.load_frame(frame))
+            .map_err(js_error)
+    }
+}
+
+impl WebChip8 {
+    fn set_key(&mut self, key: u8, pressed: bool) -> Result<(), JsValue> {
+        let key = Chip8Key::new(key).map_err(js_error)?;
+        self.chip8.keyboard_mut().unwrap().keys.set_key(key, pressed);
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+//! The wrapper also builds natively and works as long as nothing creates a
+//! `JsValue`, so it is checked against a headless run of the same program.
+
+extern crate chip8_emulator;
+extern crate chip8_emulator_wasm;
+
+use chip8_emulator::chip8::STATE_SIZE;
+use chip8_emulator::headless::{headless, HeadlessConfig, ScriptedInput};
+use chip8_emulator_wasm::WebChip8;
+
+// LD V5, K; LD F, V5; DRW V0, V0, 5; end: JP end
+const SHOW_KEY: &[u8] = &[0xF5, 0x0A, 0xF5, 0x29, 0xD0, 0x05, 0x12, 0x06];
+
+#[test]
+fn same_as_headless() {
+    let config = HeadlessConfig {
+        input: ScriptedInput::parse("2:7 3:-7").unwrap(),
+        ..HeadlessConfig::default()
+    };
+    let mut expected = headless(SHOW_KEY, &config).unwrap();
+    let mut chip8 = WebChip8::new(SHOW_KEY, 0).unwrap();
+
+    let mut changed = Vec::new();
+    for frame in 0..5 {
+        match frame {
+            2 => chip8.press_key(7).unwrap(),
+            3 => chip8.release_key(7).unwrap(),
+            _ => {}
+        }
+        changed.push(chip8.run_frame());
+        expected.poll_input();
+        expected.run_frame();
+    }
+    // Redrawn at power on and once the key is drawn
+    assert_eq!(changed, [true, false, false, true, false]);
+    assert!(chip8.fault().is_none());
+
+    let state = chip8.save_state();
+    assert!(state[..STATE_SIZE] == expected.save_state()[..]);
+    let pixels = chip8.framebuffer();
+    assert_eq!(pixels.len(), 64 * 32);
+    for y in 0..32 {
+        for x in 0..64 {
+            assert_eq!(pixels[y * 64 + x] != 0, expected.framebuffer().pixel(x, y));
+        }
+    }
+    // The "7" glyph
+    assert_eq!(&pixels[..4], &[1, 1, 1, 1]);
+
+    chip8.reset();
+    assert!(chip8.run_frame());
+    assert!(chip8.framebuffer().iter().all(|&pixel| pixel == 0));
+    // The program runs again from the start
+    chip8.press_key(7).unwrap();
+    assert!(!chip8.run_frame());
+    chip8.release_key(7).unwrap();
+    assert!(chip8.run_frame());
+    assert!(chip8.fault().is_none());
+    assert_eq!(chip8.framebuffer(), pixels);
+    chip8.load_state(&state).unwrap();
+    assert_eq!(chip8.framebuffer(), pixels);
+    assert_eq!(chip8.save_state(), state);
+}